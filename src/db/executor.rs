@@ -1,31 +1,247 @@
-use std::collections::HashMap;
-use super::{parser::{ASTNode, WhereCondition}, query::Identifier, schema::Row, storage_engine::FileSystem};
+use std::borrow::Borrow;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use super::{
+    functions::as_f64,
+    parser::{
+        ASTNode, AggregateFunction, AlterTableOperation, BinaryOperator, ColumnConstraint,
+        ColumnDefinition, CommonTableExpression, ConflictAction, Expr, InsertSource, Join, JoinKind,
+        OnConflict, OrderByItem, RecursiveStep, SelectItem, SelectStatement, SetOperator,
+        TableConstraint, TableReference, WithClause,
+    },
+    query::{column_schema, primary_key_column, Identifier, JoinAlgorithm, QueryOptimizer},
+    parser::Parser,
+    schema::{Column, DataType, Row, Table, Value, View},
+    storage_engine::FileSystem,
+};
+
+/// A predicate over table rows, used where a condition cannot be evaluated row by row
+type RowFilter = Box<dyn Fn(&Row) -> bool>;
+type RowUpdate = Box<dyn Fn(&Row) -> Vec<(String, Value)>>;
+type ConflictHandler = Box<dyn Fn(&Row, &Row) -> Option<Vec<(String, Value)>>>;
+
+/// Iterations a recursive WITH query may run before it is stopped
+const MAX_RECURSIVE_ITERATIONS: usize = 1000;
+
+/// The rows a statement returns, with its output column names in order
+#[derive(Debug, Default)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Row>,
+    /// Number of rows written by INSERT, UPDATE or DELETE
+    pub affected_rows: Option<usize>,
+}
 
 pub struct QueryExecutor<'a> {
     filesystem: &'a mut FileSystem,
+    /// Rows of the views the statement reads, materialized before it runs, and of the
+    /// WITH queries of the query running
+    views: HashMap<String, Table>,
 }
 
 impl<'a> QueryExecutor<'a> {
     pub fn new(filesystem: &'a mut FileSystem) -> Self {
-        QueryExecutor { filesystem }
+        QueryExecutor { filesystem, views: HashMap::new() }
     }
 
-    pub fn execute(&mut self, query: ASTNode) -> Result<Vec<Row>, ExecutionError> {
+    /// Look up a table, or a view materialized for the statement
+    fn table(&self, name: &str) -> Option<&Table> {
+        self.views.get(name).or_else(|| self.filesystem.storage_engine.tables.get(name))
+    }
+
+    /// Run the queries of the views among `names`, and of the views those read, keeping
+    /// their rows as tables. `reading` holds the views being materialized, to catch a
+    /// view that reads itself.
+    fn materialize_views(&mut self, names: Vec<String>, reading: &mut Vec<String>) -> Result<(), ExecutionError> {
+        for name in names {
+            let Some(view) = self.filesystem.storage_engine.views.get(&name) else {
+                continue;
+            };
+            if self.views.contains_key(&name) {
+                continue;
+            }
+            if reading.contains(&name) {
+                return Err(ExecutionError::ViewFailed(format!("View '{}' reads itself", name)));
+            }
+
+            let select = Parser::parse_select(&view.definition).map_err(ExecutionError::ViewFailed)?;
+            reading.push(name.clone());
+            self.materialize_views(select.read_tables(), reading)?;
+            reading.pop();
+
+            let table = self.query_table(select, &[])?;
+            self.views.insert(name, table);
+        }
+        Ok(())
+    }
+
+    /// Run the query of a view or WITH query and keep its rows as a table. Columns are
+    /// named as the query exposes them: columns read from a table lose their qualifier,
+    /// also when `*` reads joined tables, unless `columns` renames them in order.
+    fn query_table(&mut self, select: SelectStatement, columns: &[Identifier]) -> Result<Table, ExecutionError> {
+        let projection = select.projection.iter()
+            .map(|item| match item {
+                SelectItem::Expr { expr, alias: None } => SelectItem::Expr {
+                    expr: expr.clone(),
+                    alias: item.view_column_name().map(Identifier),
+                },
+                item => item.clone(),
+            })
+            .collect();
+        let result = self.execute_query(SelectStatement { projection, ..select })?;
+
+        let names: Vec<(String, String)> = result.columns.iter()
+            .enumerate()
+            .map(|(position, column)| {
+                let name = match columns.get(position) {
+                    Some(name) => name.0.clone(),
+                    None => column.split_once('.').map_or(column.as_str(), |(_, name)| name).to_string(),
+                };
+                (column.clone(), name)
+            })
+            .collect();
+        let rows = result.rows.into_iter()
+            .map(|row| Row {
+                data: names.iter()
+                    .map(|(column, name)| (name.clone(), row.data.get(column).cloned().unwrap_or(Value::Null)))
+                    .collect(),
+            })
+            .collect();
+        let columns = names.into_iter().map(|(_, name)| name).collect();
+        Ok(typed_table(columns, rows))
+    }
+
+    /// Run a SELECT, first materializing the WITH queries it does not inline. They are
+    /// dropped afterwards, so the tables and views they hide can be read again.
+    fn execute_query(&mut self, select: SelectStatement) -> Result<QueryResult, ExecutionError> {
+        let mut select = QueryOptimizer::inline_ctes(&select);
+        let Some(with) = select.with.take() else {
+            return self.execute_select(select);
+        };
+
+        let mut hidden = Vec::new();
+        let result = self.materialize_ctes(&with, &mut hidden).and_then(|_| self.execute_select(select));
+        for (name, table) in hidden.into_iter().rev() {
+            match table {
+                Some(table) => self.views.insert(name, table),
+                None => self.views.remove(&name),
+            };
+        }
+        result
+    }
+
+    /// Materialize the WITH queries in order, each reading those before it. `hidden`
+    /// collects what their names meant before.
+    fn materialize_ctes(
+        &mut self,
+        with: &WithClause,
+        hidden: &mut Vec<(String, Option<Table>)>,
+    ) -> Result<(), ExecutionError> {
+        for cte in &with.queries {
+            let table = match cte.recursive_step {
+                Some(ref step) => self.materialize_recursive(cte, step)?,
+                None => self.query_table(cte.query.clone(), &cte.columns)?,
+            };
+            hidden.push((cte.name.0.clone(), self.views.insert(cte.name.0.clone(), table)));
+        }
+        Ok(())
+    }
+
+    /// Run a recursive WITH query: its first SELECT gives the first rows, then its step
+    /// runs on the rows the previous iteration added until an iteration adds none. UNION
+    /// drops rows seen before, which ends cycles; a query that keeps adding rows is
+    /// stopped after `MAX_RECURSIVE_ITERATIONS`.
+    fn materialize_recursive(&mut self, cte: &CommonTableExpression, step: &RecursiveStep) -> Result<Table, ExecutionError> {
+        let first = self.query_table(cte.query.clone(), &cte.columns)?;
+        let names: Vec<Identifier> = first.columns.iter().map(|column| Identifier(column.name.clone())).collect();
+        let mut seen = HashSet::new();
+        let mut new_rows = |rows: Vec<Row>| -> Vec<Row> {
+            rows.into_iter()
+                .filter(|row| step.all || seen.insert(names.iter().map(|name| row.data[&name.0].clone()).collect::<Vec<Value>>()))
+                .collect()
+        };
+
+        // The step reads the rows of the previous iteration under the query's name
+        let hidden = self.views.remove(&cte.name.0);
+        let mut rows = Vec::new();
+        let mut added = new_rows(first.rows.into_values().collect());
+        let mut iterations = 0;
+        let result = loop {
+            rows.extend(added.iter().cloned());
+            if added.is_empty() {
+                break Ok(());
+            }
+            if iterations == MAX_RECURSIVE_ITERATIONS {
+                break Err(ExecutionError::RecursionLimit(cte.name.0.clone(), MAX_RECURSIVE_ITERATIONS));
+            }
+            iterations += 1;
+
+            let working = Table { columns: first.columns.clone(), rows: added.into_iter().enumerate().collect(), primary_key: None };
+            self.views.insert(cte.name.0.clone(), working);
+            match self.query_table(step.query.clone(), &names) {
+                Ok(table) => added = new_rows(table.rows.into_values().collect()),
+                Err(e) => break Err(e),
+            }
+        };
+        match hidden {
+            Some(table) => self.views.insert(cte.name.0.clone(), table),
+            None => self.views.remove(&cte.name.0),
+        };
+
+        result.map(|_| typed_table(names.into_iter().map(|name| name.0).collect(), rows))
+    }
+
+    pub fn execute(&mut self, query: ASTNode) -> Result<QueryResult, ExecutionError> {
+        // Placeholders only have values once a prepared statement binds them
+        let parameters = query.parameter_count();
+        if parameters > 0 {
+            return Err(ExecutionError::UnboundParameter(parameters));
+        }
+
+        if !matches!(query, ASTNode::CreateViewStatement { .. }) {
+            self.materialize_views(query.read_tables(), &mut Vec::new())?;
+        }
+
         match query {
-            ASTNode::SelectStatement { projection, table, condition } => {
-                Ok(self.execute_select(projection, table, condition)?)
+            ASTNode::SelectStatement(select) => {
+                Ok(self.execute_query(select)?)
             }
-            ASTNode::DeleteStatement { table, condition } => {
-                self.execute_delete(table, condition)?;
-                Ok(vec![])
+            ASTNode::DeleteStatement { table, condition, returning } => {
+                let rows = self.execute_delete(&table, condition)?;
+                self.write_result(&table, returning, rows)
             }
-            ASTNode::InsertStatement { table, columns, values } => {
-                self.execute_insert(table, columns, values)?;
-                Ok(vec![])
+            ASTNode::InsertStatement { table, columns, source, on_conflict, returning } => {
+                let rows = self.execute_insert(&table, columns, source, on_conflict)?;
+                self.write_result(&table, returning, rows)
             }
-            ASTNode::UpdateStatement { table, assignments, condition } => {
-                self.execute_update(table, assignments, condition)?;
-                Ok(vec![])
+            ASTNode::UpdateStatement { table, assignments, condition, returning } => {
+                let rows = self.execute_update(&table, assignments, condition)?;
+                self.write_result(&table, returning, rows)
+            }
+            ASTNode::CreateTableStatement { table, columns, constraints, if_not_exists } => {
+                self.execute_create_table(table, columns, constraints, if_not_exists)?;
+                Ok(QueryResult::default())
+            }
+            ASTNode::DropTableStatement { table, if_exists } => {
+                self.execute_drop_table(table, if_exists)?;
+                Ok(QueryResult::default())
+            }
+            ASTNode::AlterTableStatement { table, operation } => {
+                self.execute_alter_table(table, operation)?;
+                Ok(QueryResult::default())
+            }
+            ASTNode::CreateViewStatement { view, definition, or_replace, .. } => {
+                self.filesystem.create_view(&view.0, View { definition }, or_replace)
+                    .map_err(|e| ExecutionError::CreateViewFailed(e.to_string()))?;
+                Ok(QueryResult::default())
+            }
+            ASTNode::DropViewStatement { view, if_exists } => {
+                if if_exists && !self.filesystem.storage_engine.views.contains_key(&view.0) {
+                    return Ok(QueryResult::default());
+                }
+                self.filesystem.drop_view(&view.0)
+                    .map_err(|e| ExecutionError::DropViewFailed(e.to_string()))?;
+                Ok(QueryResult::default())
             }
             ASTNode::Identifier(_) => {
                 Err(ExecutionError::InvalidQuery)
@@ -33,132 +249,1710 @@ impl<'a> QueryExecutor<'a> {
         }
     }
 
-    fn execute_select(
-        &self,
-        projection: Vec<Identifier>,
-        table: Identifier,
-        condition: Option<WhereCondition>,
-    ) -> Result<Vec<Row>, ExecutionError> {
-        let table = self
-            .filesystem
-            .storage_engine
-            .tables
-            .get(&table.0)
-            .ok_or(ExecutionError::TableNotFound)?;
+    fn execute_select(&self, select: SelectStatement) -> Result<QueryResult, ExecutionError> {
+        if !select.compound.is_empty() {
+            return self.execute_compound(select);
+        }
+        let scope = self.scope(&select)?;
+        let resolved = scope.resolve_select(&select)?;
+        let joins = resolved.joins.iter()
+            .map(|join| {
+                let condition = join.condition.as_ref().map(|condition| self.uncorrelated(condition, "JOIN ... ON"));
+                Ok(Join { condition: condition.transpose()?, ..join.clone() })
+            })
+            .collect::<Result<Vec<Join>, ExecutionError>>()?;
+        if resolved.group_by.iter().any(Expr::has_subqueries) {
+            return Err(ExecutionError::SubqueryFailed("Subqueries are not supported in GROUP BY".to_string()));
+        }
 
-        let mut result = Vec::new();
-        for row in table.rows.values() {
-            // Apply WHERE condition if present
-            if let Some(ref cond) = condition {
-                if !cond.evaluate(row) {
-                    continue;
+        // Join the tables, or read a single table's rows in place
+        let joined = if joins.is_empty() { Vec::new() } else { join_rows(&scope, &joins) };
+        let groups: Vec<Row>;
+        let rows: Box<dyn Iterator<Item = (usize, &Row)>> = if joins.is_empty() {
+            Box::new(scope.tables[0].1.rows.iter().map(|(id, row)| (*id, row)))
+        } else {
+            Box::new(joined.iter().enumerate())
+        };
+
+        // Uncorrelated IN subqueries in WHERE run once as hash semi-joins; other
+        // uncorrelated subqueries are replaced by their results
+        let mut semi_joins = Vec::new();
+        let mut remaining = Vec::new();
+        for conjunct in resolved.condition.iter().flat_map(Expr::conjuncts) {
+            match QueryOptimizer::semi_join_subquery(conjunct, &|table, column| self.has_column(table, column)) {
+                Some((operand, subquery)) => {
+                    semi_joins.push(SemiJoin::new(operand.clone(), self.subquery_values(subquery)?))
                 }
+                None => remaining.push(conjunct.clone()),
             }
+        }
+        let condition = Expr::conjunction(remaining)
+            .map(|condition| self.bind_subqueries(&condition, None))
+            .transpose()?;
 
-            let mut row_data = HashMap::new();
-            
-            // Handle SELECT * or specific columns
-            if projection.len() == 1 && projection[0].0 == "*" {
-                // Select all columns
-                for (key, value) in &row.data {
-                    row_data.insert(key.clone(), value.clone());
+        // Apply WHERE condition if present
+        let rows = rows.filter(|(_, row)| semi_joins.iter().all(|semi_join| semi_join.contains(row)));
+        let matching: Box<dyn Iterator<Item = (usize, &Row)>> = match condition {
+            // Correlated subqueries run for every row, so filter eagerly to surface their errors
+            Some(ref condition) if condition.has_subqueries() => {
+                let mut kept = Vec::new();
+                for (position, row) in rows {
+                    if self.bind_subqueries(condition, Some((&scope, row)))?.evaluate(row) {
+                        kept.push((position, row));
+                    }
                 }
-            } else {
-                // Select specific columns
-                for column in &projection {
-                    row_data.insert(
-                        column.0.clone(),
-                        row.data.get(&column.0).cloned().unwrap_or_default(),
-                    );
+                Box::new(kept.into_iter())
+            }
+            _ => Box::new(rows.filter(|(_, row)| condition.as_ref().is_none_or(|cond| cond.evaluate(row)))),
+        };
+
+        // Aggregate queries continue with one row per group; later clauses read the
+        // grouping expressions and aggregates from the group rows
+        let is_aggregate = resolved.is_aggregate();
+        let resolve = |expr: &Expr| {
+            let expr = if is_aggregate { grouped_expr(expr, &resolved.group_by) } else { expr.clone() };
+            self.bind_subqueries(&expr, None)
+        };
+        let source: Box<dyn Iterator<Item = (usize, &Row)>> = if is_aggregate {
+            // Collapse the matching rows into groups, then filter the groups with HAVING
+            let having = resolved.having.as_ref().map(resolve).transpose()?;
+            let mut kept = Vec::new();
+            for group in hash_aggregate(matching.map(|(_, row)| row), &resolved.group_by, &resolved.aggregates()) {
+                let keep = match having {
+                    Some(ref having) if having.has_subqueries() => {
+                        self.bind_subqueries(having, Some((&scope, &group)))?.evaluate(&group)
+                    }
+                    Some(ref having) => having.evaluate(&group),
+                    None => true,
+                };
+                if keep {
+                    kept.push(group);
                 }
             }
-            
-            result.push(Row { data: row_data });
+            groups = kept;
+            Box::new(groups.iter().enumerate())
+        } else {
+            Box::new(matching)
+        };
+
+        // Handle SELECT * or specific columns, named as written in the query
+        let mut outputs = Vec::new();
+        for (item, resolved_item) in select.projection.iter().zip(&resolved.projection) {
+            match resolved_item {
+                SelectItem::Wildcard if is_aggregate => return Err(ExecutionError::InvalidQuery),
+                // Select all columns; columns without a stored value are NULL
+                SelectItem::Wildcard => outputs.extend(scope.wildcard_columns()),
+                SelectItem::Expr { expr, .. } => outputs.push((item.column_name().unwrap_or_default(), resolve(expr)?)),
+            }
         }
 
-        Ok(result)
+        let columns: Vec<String> = outputs.iter().map(|(name, _)| name.clone()).collect();
+
+        // Correlated subqueries in the select list run once per output row
+        let correlated = outputs.iter().any(|(_, expr)| expr.has_subqueries());
+        let project = |row: &Row| -> Result<Row, ExecutionError> {
+            if !correlated {
+                return Ok(project_row(row, &outputs));
+            }
+            let bound = outputs.iter()
+                .map(|(name, expr)| Ok((name.clone(), self.bind_subqueries(expr, Some((&scope, row)))?)))
+                .collect::<Result<Vec<_>, ExecutionError>>()?;
+            Ok(project_row(row, &bound))
+        };
+
+        // A position the parser left (e.g., `ORDER BY 2` after a `*`) counts each column
+        // `*` expands to
+        let position = |expr: &Expr| match *expr {
+            Expr::Literal(Value::Integer(position)) => Some(
+                usize::try_from(position).ok()
+                    .and_then(|position| outputs.get(position.checked_sub(1)?))
+                    .ok_or(ExecutionError::InvalidQuery),
+            ),
+            _ => None,
+        };
+
+        if !select.distinct {
+            let order_by = resolved.order_by.iter()
+                .map(|item| {
+                    let expr = match position(&item.expr) {
+                        Some(output) => output?.1.clone(),
+                        None => resolve(&item.expr)?,
+                    };
+                    Ok(OrderByItem { expr: self.uncorrelated(&expr, "ORDER BY")?, ..item.clone() })
+                })
+                .collect::<Result<Vec<OrderByItem>, ExecutionError>>()?;
+            let rows = order_and_limit(source, &order_by, select.offset, select.limit);
+            let rows = rows.into_iter().map(&project).collect::<Result<_, _>>()?;
+            return Ok(QueryResult { columns, rows, affected_rows: None });
+        }
+
+        // Deduplicate the projected rows; ORDER BY then sorts by the output columns
+        let projected: Box<dyn Iterator<Item = (usize, Row)>> = if correlated {
+            let rows = source
+                .map(|(position, row)| Ok((position, project(row)?)))
+                .collect::<Result<Vec<_>, ExecutionError>>()?;
+            Box::new(rows.into_iter())
+        } else {
+            Box::new(source.map(|(position, row)| (position, project_row(row, &outputs))))
+        };
+        let mut seen = HashSet::new();
+        let distinct = projected.filter(|(_, row)| {
+            seen.insert(outputs.iter().map(|(name, _)| row.data[name].clone()).collect::<Vec<Value>>())
+        });
+        let order_by: Vec<OrderByItem> = select.order_by.iter()
+            .map(|item| {
+                if let Some(output) = position(&item.expr) {
+                    return Ok(OrderByItem { expr: Expr::Column(output?.0.clone()), ..item.clone() });
+                }
+                let output = select.projection.iter()
+                    .find(|output| output.expr() == Some(&item.expr))
+                    .and_then(SelectItem::column_name);
+                Ok(OrderByItem { expr: Expr::Column(output.unwrap_or_else(|| item.expr.output_name())), ..item.clone() })
+            })
+            .collect::<Result<_, ExecutionError>>()?;
+        let rows = order_and_limit(distinct, &order_by, select.offset, select.limit);
+        Ok(QueryResult { columns, rows, affected_rows: None })
     }
 
-    fn execute_insert(
-        &mut self,
-        table: Identifier,
-        columns: Vec<Identifier>,
-        values: Vec<String>,
-    ) -> Result<(), ExecutionError> {
-        let mut row_data = HashMap::new();
-        
-        if columns.is_empty() {
-            // If no columns specified, assume values are in table column order
-            if let Some(table_info) = self.filesystem.storage_engine.tables.get(&table.0) {
-                for (i, column) in table_info.columns.iter().enumerate() {
-                    if let Some(value) = values.get(i) {
-                        row_data.insert(column.clone(), value.clone());
+    /// Run a compound SELECT. Each SELECT runs on its own and its rows take the first
+    /// SELECT's column names by position. The SELECTs combine left to right, each with
+    /// the rows before it, as the plan shows them; ORDER BY, OFFSET and LIMIT apply to
+    /// the combined rows.
+    fn execute_compound(&self, mut select: SelectStatement) -> Result<QueryResult, ExecutionError> {
+        let operations = std::mem::take(&mut select.compound);
+        let order_by = std::mem::take(&mut select.order_by);
+        let (offset, limit) = (select.offset.take(), select.limit.take());
+        let first = self.execute_select(select)?;
+        let columns = first.columns;
+
+        let mut rows = first.rows;
+        for operation in operations {
+            let result = self.execute_select(operation.query)?;
+            if result.columns.len() != columns.len() {
+                return Err(ExecutionError::SetOperationFailed(format!(
+                    "Each SELECT of {} must return the same number of columns, but they return {} and {}",
+                    operation.operator, columns.len(), result.columns.len()
+                )));
+            }
+            let right = result.rows.into_iter()
+                .map(|row| Row {
+                    data: columns.iter()
+                        .zip(&result.columns)
+                        .map(|(name, column)| (name.clone(), row.data.get(column).cloned().unwrap_or(Value::Null)))
+                        .collect(),
+                })
+                .collect();
+            rows = set_operation(rows, right, operation.operator, operation.all, &columns);
+        }
+
+        // Sort keys read the output columns, which may be named without their qualifier
+        let output = |name: &str| {
+            columns.iter()
+                .find(|column| *column == name)
+                .or_else(|| columns.iter().find(|column| column.rsplit('.').next() == name.rsplit('.').next()))
+        };
+        let order_by = order_by.iter()
+            .map(|item| {
+                let expr = match item.expr {
+                    Expr::Literal(Value::Integer(position)) => {
+                        let column = usize::try_from(position).ok()
+                            .and_then(|position| columns.get(position.checked_sub(1)?))
+                            .ok_or_else(|| ExecutionError::SetOperationFailed(
+                                format!("ORDER BY position {} does not name a selected column", position)
+                            ))?;
+                        Expr::Column(column.clone())
+                    }
+                    _ => item.expr.clone(),
+                };
+                let expr = expr.rewrite(&mut |node| match node {
+                    Expr::Column(name) => match output(name) {
+                        Some(column) => Ok(Some(Expr::Column(column.clone()))),
+                        None => Err(ExecutionError::SetOperationFailed(format!(
+                            "ORDER BY of a compound SELECT can only use its output columns, not '{}'", name
+                        ))),
+                    },
+                    _ => Ok(None),
+                })?;
+                Ok(OrderByItem { expr: self.uncorrelated(&expr, "ORDER BY")?, ..item.clone() })
+            })
+            .collect::<Result<Vec<OrderByItem>, ExecutionError>>()?;
+        let rows = order_and_limit(rows.into_iter().enumerate(), &order_by, offset, limit);
+        Ok(QueryResult { columns, rows, affected_rows: None })
+    }
+
+    /// Look up the tables a SELECT reads
+    fn scope(&self, select: &SelectStatement) -> Result<Scope<'_>, ExecutionError> {
+        let mut tables: Vec<(String, &Table)> = Vec::new();
+        for reference in select.tables() {
+            let table = self.table(&reference.name.0).ok_or(ExecutionError::TableNotFound)?;
+            if tables.iter().any(|(qualifier, _)| qualifier == reference.qualifier()) {
+                return Err(ExecutionError::InvalidQuery);
+            }
+            tables.push((reference.qualifier().to_string(), table));
+        }
+        Ok(Scope { tables })
+    }
+
+    /// Whether a table of the database, or a view, defines a column
+    fn has_column(&self, table: &TableReference, column: &str) -> bool {
+        self.table(&table.name.0).is_some_and(|table| table.has_column(column))
+    }
+
+    fn is_correlated(&self, subquery: &SelectStatement) -> bool {
+        !subquery.outer_references(&|table, column| self.has_column(table, column)).is_empty()
+    }
+
+    /// Replace the subqueries of an expression with their results. Correlated subqueries
+    /// are first bound to the `outer` row; without one they are left to run per row.
+    fn bind_subqueries(&self, expr: &Expr, outer: Option<(&Scope, &Row)>) -> Result<Expr, ExecutionError> {
+        if !expr.has_subqueries() {
+            return Ok(expr.clone());
+        }
+
+        expr.rewrite(&mut |node| {
+            let (Expr::Subquery(subquery) | Expr::Exists(subquery) | Expr::InSubquery { subquery, .. }) = node else {
+                return Ok(None);
+            };
+            let subquery = match outer {
+                _ if !self.is_correlated(subquery) => subquery.as_ref().clone(),
+                Some((scope, row)) => self.bind_outer_references(subquery, scope, row)?,
+                None => return match node {
+                    // The operand may still hold uncorrelated subqueries
+                    Expr::InSubquery { operand, subquery, negated } => Ok(Some(Expr::InSubquery {
+                        operand: Box::new(self.bind_subqueries(operand, None)?),
+                        subquery: subquery.clone(),
+                        negated: *negated,
+                    })),
+                    _ => Ok(None),
+                },
+            };
+
+            Ok(Some(match node {
+                Expr::Exists(_) => {
+                    // One row is enough to answer EXISTS
+                    let limit = Some(subquery.limit.map_or(1, |limit| limit.min(1)));
+                    let result = self.execute_select(SelectStatement { limit, ..subquery })?;
+                    Expr::Literal(Value::Boolean(!result.rows.is_empty()))
+                }
+                Expr::InSubquery { operand, negated, .. } => Expr::InList {
+                    operand: Box::new(self.bind_subqueries(operand, outer)?),
+                    list: self.subquery_values(&subquery)?.into_iter().map(Expr::Literal).collect(),
+                    negated: *negated,
+                },
+                _ => {
+                    // A second row is enough to reject a scalar subquery
+                    let limit = Some(subquery.limit.map_or(2, |limit| limit.min(2)));
+                    let mut values = self.subquery_values(&SelectStatement { limit, ..subquery })?;
+                    if values.len() > 1 {
+                        return Err(ExecutionError::SubqueryFailed(
+                            "Scalar subquery returned more than one row".to_string()
+                        ));
                     }
+                    Expr::Literal(values.pop().unwrap_or(Value::Null))
                 }
+            }))
+        })
+    }
+
+    /// Run the subqueries of a clause that cannot evaluate correlated subqueries
+    fn uncorrelated(&self, expr: &Expr, clause: &str) -> Result<Expr, ExecutionError> {
+        let bound = self.bind_subqueries(expr, None)?;
+        if bound.has_subqueries() {
+            return Err(ExecutionError::SubqueryFailed(
+                format!("Correlated subqueries are not supported in {}", clause)
+            ));
+        }
+        Ok(bound)
+    }
+
+    /// Substitute the outer row's values for the columns a subquery takes from it
+    fn bind_outer_references(&self, subquery: &SelectStatement, scope: &Scope, row: &Row) -> Result<SelectStatement, ExecutionError> {
+        subquery.rewrite_outer_references(&|table, column| self.has_column(table, column), &mut |name| {
+            if !scope.provides(name) {
+                return Ok(None);
             }
-        } else {
-            // Map columns to values
-            for (i, column) in columns.iter().enumerate() {
-                if let Some(value) = values.get(i) {
-                    row_data.insert(column.0.clone(), value.clone());
+            let key = scope.resolve(name)?;
+            Ok(row.data.get(&key).map(|value| Expr::Literal(value.clone())))
+        })
+    }
+
+    /// Run a subquery that must produce a single column and return that column's values
+    fn subquery_values(&self, subquery: &SelectStatement) -> Result<Vec<Value>, ExecutionError> {
+        let scope = self.scope(subquery)?;
+        let width: usize = subquery.projection.iter()
+            .map(|item| match item {
+                SelectItem::Wildcard => scope.wildcard_columns().len(),
+                SelectItem::Expr { .. } => 1,
+            })
+            .sum();
+        if width != 1 {
+            return Err(ExecutionError::SubqueryFailed(
+                format!("Subquery must return exactly one column, but returns {}", width)
+            ));
+        }
+
+        let mut result = self.execute_select(subquery.clone())?;
+        Ok(result.rows
+            .iter_mut()
+            .map(|row| row.data.remove(&result.columns[0]).unwrap_or(Value::Null))
+            .collect())
+    }
+
+    /// The scope of an INSERT, UPDATE or DELETE: the target table alone, which may
+    /// qualify its own columns
+    fn target_scope(&self, table: &Identifier) -> Result<Scope<'_>, ExecutionError> {
+        let table_data = self.filesystem.storage_engine.tables
+            .get(&table.0)
+            .ok_or(ExecutionError::TableNotFound)?;
+        Ok(Scope { tables: vec![(table.0.clone(), table_data)] })
+    }
+
+    /// Turn an UPDATE or DELETE condition with subqueries into a row filter. Correlated
+    /// subqueries need the executor, so the matching rows are found up front and hashed.
+    /// The condition's columns must already be resolved against `target_scope`.
+    fn subquery_filter(&self, table: &Identifier, condition: &Expr) -> Result<RowFilter, ExecutionError> {
+        let scope = self.target_scope(table)?;
+        let condition = self.bind_subqueries(condition, None)?;
+        if !condition.has_subqueries() {
+            return Ok(Box::new(move |row| condition.evaluate(row)));
+        }
+
+        let mut matching = HashSet::new();
+        for row in scope.tables[0].1.rows.values() {
+            if self.bind_subqueries(&condition, Some((&scope, row)))?.evaluate(row) {
+                matching.insert(row_key(row));
+            }
+        }
+        Ok(Box::new(move |row| matching.contains(&row_key(row))))
+    }
+
+    /// Turn UPDATE assignments into a function from a row to its new values. Like
+    /// `subquery_filter`, correlated subqueries are evaluated for every row up front.
+    fn subquery_assignments(
+        &self,
+        table: &Identifier,
+        assignments: Vec<(Identifier, Expr)>,
+    ) -> Result<RowUpdate, ExecutionError> {
+        let evaluate = |assignments: &[(String, Expr)], row: &Row| {
+            assignments.iter()
+                .map(|(column, expr)| (column.clone(), expr.eval(row)))
+                .collect::<Vec<_>>()
+        };
+        let scope = self.target_scope(table)?;
+        let mut assignments = assignments.into_iter()
+            .map(|(column, expr)| Ok((column.0, scope.resolve_expr(&expr)?)))
+            .collect::<Result<Vec<(String, Expr)>, ExecutionError>>()?;
+        if !assignments.iter().any(|(_, expr)| expr.has_subqueries()) {
+            return Ok(Box::new(move |row| evaluate(&assignments, row)));
+        }
+
+        for (_, expr) in &mut assignments {
+            *expr = self.bind_subqueries(expr, None)?;
+        }
+        if !assignments.iter().any(|(_, expr)| expr.has_subqueries()) {
+            return Ok(Box::new(move |row| evaluate(&assignments, row)));
+        }
+
+        let mut computed = HashMap::new();
+        for row in scope.tables[0].1.rows.values() {
+            let mut values = Vec::new();
+            for (column, expr) in &assignments {
+                let expr = self.bind_subqueries(expr, Some((&scope, row)))?;
+                values.push((column.clone(), expr.eval(row)));
+            }
+            computed.insert(row_key(row), values);
+        }
+        Ok(Box::new(move |row| computed.get(&row_key(row)).cloned().unwrap_or_default()))
+    }
+
+    /// Report the rows a write affected, projected through its RETURNING list if any
+    fn write_result(
+        &self,
+        table: &Identifier,
+        returning: Option<Vec<SelectItem>>,
+        rows: Vec<Row>,
+    ) -> Result<QueryResult, ExecutionError> {
+        let affected_rows = Some(rows.len());
+        let Some(returning) = returning else {
+            return Ok(QueryResult { affected_rows, ..QueryResult::default() });
+        };
+
+        let scope = self.target_scope(table)?;
+        let mut outputs = Vec::new();
+        for item in &returning {
+            match item {
+                SelectItem::Wildcard => outputs.extend(scope.wildcard_columns()),
+                SelectItem::Expr { expr, .. } => {
+                    let expr = self.uncorrelated(&scope.resolve_expr(expr)?, "RETURNING")?;
+                    outputs.push((item.column_name().unwrap_or_default(), expr));
                 }
             }
         }
 
-        let row = Row { data: row_data };
-        self.filesystem.insert_row(&table.0, row)
-            .map_err(|_| ExecutionError::InsertFailed)?;
-        
-        Ok(())
+        Ok(QueryResult {
+            columns: outputs.iter().map(|(name, _)| name.clone()).collect(),
+            rows: rows.iter().map(|row| project_row(row, &outputs)).collect(),
+            affected_rows,
+        })
     }
 
-    fn execute_update(
+    fn execute_insert(
         &mut self,
-        table: Identifier,
-        assignments: Vec<(Identifier, String)>,
-        condition: Option<WhereCondition>,
-    ) -> Result<(), ExecutionError> {
-        let mut updates = HashMap::new();
-        for (column, value) in assignments {
-            updates.insert(column.0, value);
+        table: &Identifier,
+        columns: Vec<Identifier>,
+        source: InsertSource,
+        on_conflict: Option<OnConflict>,
+    ) -> Result<Vec<Row>, ExecutionError> {
+        // If no columns specified, assume values are in table column order
+        let targets: Vec<String> = if columns.is_empty() {
+            self.filesystem.storage_engine.tables.get(&table.0)
+                .ok_or(ExecutionError::TableNotFound)?
+                .column_names()
+        } else {
+            columns.into_iter().map(|column| column.0).collect()
+        };
+
+        let tuples: Vec<Vec<Value>> = match source {
+            InsertSource::Values(tuples) => {
+                let empty = Row { data: HashMap::new() };
+                tuples.iter()
+                    .map(|values| values.iter().map(|value| value.eval(&empty)).collect())
+                    .collect()
+            }
+            InsertSource::Select(select) => {
+                let result = self.execute_query(*select)?;
+                if result.columns.len() != targets.len() {
+                    return Err(ExecutionError::InsertFailed(format!(
+                        "INSERT has {} target columns but the query returns {}",
+                        targets.len(),
+                        result.columns.len()
+                    )));
+                }
+                result.rows.iter()
+                    .map(|row| result.columns.iter().map(|column| row.data.get(column).cloned().unwrap_or(Value::Null)).collect())
+                    .collect()
+            }
+        };
+
+        // Map columns to values
+        let rows = tuples.into_iter()
+            .map(|values| Row { data: targets.iter().cloned().zip(values).collect() })
+            .collect();
+        let result = match on_conflict {
+            Some(on_conflict) => {
+                let on_conflict = self.conflict_handler(table, on_conflict)?;
+                self.filesystem.upsert_rows(&table.0, rows, on_conflict)
+            }
+            None => self.filesystem.insert_rows(&table.0, rows),
+        };
+        result.map_err(|e| ExecutionError::InsertFailed(e.to_string()))
+    }
+
+    /// Turn an ON CONFLICT clause into the storage engine's conflict callback. DO UPDATE
+    /// expressions read the stored row, as `column` or `table.column`, and the proposed
+    /// one through `excluded.column`.
+    fn conflict_handler(&self, table: &Identifier, on_conflict: OnConflict) -> Result<ConflictHandler, ExecutionError> {
+        let scope = self.target_scope(table)?;
+        let table_data = scope.tables[0].1;
+        let Some(pk) = &table_data.primary_key else {
+            return Err(ExecutionError::InsertFailed(
+                format!("ON CONFLICT needs a primary key, and table '{}' has none", table.0)
+            ));
+        };
+        if let Some(target) = on_conflict.target.filter(|target| &target.0 != pk) {
+            return Err(ExecutionError::InsertFailed(
+                format!("ON CONFLICT column '{}' is not the primary key of table '{}'", target.0, table.0)
+            ));
         }
 
+        let assignments = match on_conflict.action {
+            ConflictAction::Nothing => return Ok(Box::new(|_, _| None)),
+            ConflictAction::Update(assignments) => assignments.into_iter()
+                .map(|(column, expr)| {
+                    Ok((column.0, self.uncorrelated(&scope.resolve_expr(&expr)?, "ON CONFLICT DO UPDATE")?))
+                })
+                .collect::<Result<Vec<(String, Expr)>, ExecutionError>>()?,
+        };
+        Ok(Box::new(move |existing, proposed| {
+            let mut row = existing.clone();
+            for (column, value) in &proposed.data {
+                row.data.insert(format!("excluded.{}", column), value.clone());
+            }
+            Some(assignments.iter().map(|(column, expr)| (column.clone(), expr.eval(&row))).collect())
+        }))
+    }
+
+    fn execute_update(
+        &mut self,
+        table: &Identifier,
+        assignments: Vec<(Identifier, Expr)>,
+        condition: Option<Expr>,
+    ) -> Result<Vec<Row>, ExecutionError> {
+        let condition = self.target_scope(table)?.resolve_optional(&condition)?;
+        let filter = match condition {
+            Some(ref cond) if cond.has_subqueries() => Some(self.subquery_filter(table, cond)?),
+            _ => None,
+        };
         let condition_fn = move |row: &Row| -> bool {
-            if let Some(ref cond) = condition {
+            if let Some(ref filter) = filter {
+                filter(row)
+            } else if let Some(ref cond) = condition {
                 cond.evaluate(row)
             } else {
                 true // Update all rows if no condition
             }
         };
 
+        // Assignments are evaluated against the row's old values
+        let updates = self.subquery_assignments(table, assignments)?;
         self.filesystem.update_rows(&table.0, updates, condition_fn)
-            .map_err(|_| ExecutionError::UpdateFailed)?;
-
-        Ok(())
+            .map_err(ExecutionError::UpdateFailed)
     }
 
     fn execute_delete(
         &mut self,
-        table: Identifier,
-        condition: Option<WhereCondition>,
-    ) -> Result<(), ExecutionError> {
+        table: &Identifier,
+        condition: Option<Expr>,
+    ) -> Result<Vec<Row>, ExecutionError> {
+        let condition = self.target_scope(table)?.resolve_optional(&condition)?;
+        let filter = match condition {
+            Some(ref cond) if cond.has_subqueries() => Some(self.subquery_filter(table, cond)?),
+            _ => None,
+        };
         let condition_fn = move |row: &Row| -> bool {
-            if let Some(ref cond) = condition {
+            if let Some(ref filter) = filter {
+                filter(row)
+            } else if let Some(ref cond) = condition {
                 cond.evaluate(row)
             } else {
                 false // Don't delete all rows if no condition for safety
             }
         };
 
-        self.filesystem.delete_rows(&table.0, condition_fn);
+        self.filesystem.delete_rows(&table.0, condition_fn)
+            .map_err(ExecutionError::DeleteFailed)
+    }
+
+    fn execute_create_table(
+        &mut self,
+        table: Identifier,
+        columns: Vec<ColumnDefinition>,
+        constraints: Vec<TableConstraint>,
+        if_not_exists: bool,
+    ) -> Result<(), ExecutionError> {
+        if if_not_exists && self.filesystem.storage_engine.tables.contains_key(&table.0) {
+            return Ok(());
+        }
+
+        let schema = column_schema(&columns)
+            .map_err(|e| ExecutionError::CreateTableFailed(e.to_string()))?;
+        let primary_key = primary_key_column(&columns, &constraints)
+            .map_err(|e| ExecutionError::CreateTableFailed(e.to_string()))?
+            .map(|id| id.0.clone());
+
+        self.filesystem.create_table(&table.0, schema, primary_key.as_deref())
+            .map_err(|e| ExecutionError::CreateTableFailed(e.to_string()))?;
+
         Ok(())
     }
+
+    fn execute_drop_table(&mut self, table: Identifier, if_exists: bool) -> Result<(), ExecutionError> {
+        if !self.filesystem.storage_engine.tables.contains_key(&table.0) {
+            return if if_exists { Ok(()) } else { Err(ExecutionError::TableNotFound) };
+        }
+
+        self.filesystem.drop_table(&table.0)
+            .map_err(|e| ExecutionError::DropTableFailed(e.to_string()))
+    }
+
+    fn execute_alter_table(
+        &mut self,
+        table: Identifier,
+        operation: AlterTableOperation,
+    ) -> Result<(), ExecutionError> {
+        if !self.filesystem.storage_engine.tables.contains_key(&table.0) {
+            return Err(ExecutionError::TableNotFound);
+        }
+
+        let result = match operation {
+            AlterTableOperation::AddColumn(column) => {
+                let primary_key = column.constraints.contains(&ColumnConstraint::PrimaryKey);
+                match column_schema(std::slice::from_ref(&column)) {
+                    Ok(mut schema) => self.filesystem.add_column(&table.0, schema.remove(0), primary_key),
+                    Err(e) => return Err(ExecutionError::AlterTableFailed(e.to_string())),
+                }
+            }
+            AlterTableOperation::DropColumn(column) => {
+                self.filesystem.drop_column(&table.0, &column.0)
+            }
+            AlterTableOperation::RenameColumn { column, new_name } => {
+                self.filesystem.rename_column(&table.0, &column.0, &new_name.0)
+            }
+            AlterTableOperation::RenameTable(new_name) => {
+                self.filesystem.rename_table(&table.0, &new_name.0)
+            }
+        };
+
+        result.map_err(|e| ExecutionError::AlterTableFailed(e.to_string()))
+    }
+}
+
+/// The tables a SELECT reads, by qualifier, and how column references map to row keys
+struct Scope<'t> {
+    tables: Vec<(String, &'t Table)>,
+}
+
+impl Scope<'_> {
+    fn is_joined(&self) -> bool {
+        self.tables.len() > 1
+    }
+
+    /// The row key of a column reference: the bare column name when reading a single
+    /// table, and `qualifier.column` in joined rows
+    fn resolve(&self, name: &str) -> Result<String, ExecutionError> {
+        match name.split_once('.') {
+            Some((qualifier, column)) if !self.is_joined() && qualifier == self.tables[0].0 => {
+                Ok(column.to_string())
+            }
+            Some(_) => Ok(name.to_string()),
+            None if !self.is_joined() => Ok(name.to_string()),
+            None => {
+                let mut owners = self.tables.iter().filter(|(_, table)| table.has_column(name));
+                match (owners.next(), owners.next()) {
+                    (Some((qualifier, _)), None) => Ok(format!("{}.{}", qualifier, name)),
+                    (Some(_), Some(_)) => Err(ExecutionError::AmbiguousColumn(name.to_string())),
+                    // Unknown columns evaluate to NULL, as for a single table
+                    (None, _) => Ok(name.to_string()),
+                }
+            }
+        }
+    }
+
+    /// Whether a column reference names a column of these tables
+    fn provides(&self, name: &str) -> bool {
+        match name.split_once('.') {
+            Some((qualifier, column)) => self.tables.iter()
+                .any(|(name, table)| name == qualifier && table.has_column(column)),
+            None => self.tables.iter().any(|(_, table)| table.has_column(name)),
+        }
+    }
+
+    fn resolve_expr(&self, expr: &Expr) -> Result<Expr, ExecutionError> {
+        expr.rewrite(&mut |node| match node {
+            Expr::Column(name) => self.resolve(name).map(|key| Some(Expr::Column(key))),
+            _ => Ok(None),
+        })
+    }
+
+    fn resolve_optional(&self, expr: &Option<Expr>) -> Result<Option<Expr>, ExecutionError> {
+        expr.as_ref().map(|expr| self.resolve_expr(expr)).transpose()
+    }
+
+    /// Rewrite every column reference of a SELECT to its row key
+    fn resolve_select(&self, select: &SelectStatement) -> Result<SelectStatement, ExecutionError> {
+        Ok(SelectStatement {
+            projection: select.projection.iter()
+                .map(|item| match item {
+                    SelectItem::Wildcard => Ok(SelectItem::Wildcard),
+                    SelectItem::Expr { expr, alias } => Ok(SelectItem::Expr {
+                        expr: self.resolve_expr(expr)?,
+                        alias: alias.clone(),
+                    }),
+                })
+                .collect::<Result<_, _>>()?,
+            joins: select.joins.iter()
+                .map(|join| Ok(Join { condition: self.resolve_optional(&join.condition)?, ..join.clone() }))
+                .collect::<Result<_, ExecutionError>>()?,
+            condition: self.resolve_optional(&select.condition)?,
+            group_by: select.group_by.iter()
+                .map(|expr| self.resolve_expr(expr))
+                .collect::<Result<_, _>>()?,
+            having: self.resolve_optional(&select.having)?,
+            order_by: select.order_by.iter()
+                .map(|item| Ok(OrderByItem { expr: self.resolve_expr(&item.expr)?, ..item.clone() }))
+                .collect::<Result<_, ExecutionError>>()?,
+            ..select.clone()
+        })
+    }
+
+    /// The data type of a resolved joined column (`qualifier.column`)
+    fn column_type(&self, key: &str) -> Option<DataType> {
+        let (qualifier, column) = key.split_once('.')?;
+        let (_, table) = self.tables.iter().find(|(name, _)| name == qualifier)?;
+        table.column(column).map(|column| column.data_type)
+    }
+
+    /// The output columns of `SELECT *` in table and declaration order
+    fn wildcard_columns(&self) -> Vec<(String, Expr)> {
+        self.tables
+            .iter()
+            .flat_map(|(qualifier, table)| table.columns.iter().map(move |column| {
+                if self.is_joined() { format!("{}.{}", qualifier, column.name) } else { column.name.clone() }
+            }))
+            .map(|key| (key.clone(), Expr::Column(key)))
+            .collect()
+    }
+}
+
+/// A table of query results; each column takes the type of its first non-NULL value,
+/// and TEXT when there is none
+fn typed_table(names: Vec<String>, rows: Vec<Row>) -> Table {
+    let columns = names.iter()
+        .map(|name| {
+            let data_type = rows.iter()
+                .find_map(|row| row.data.get(name).and_then(Value::data_type))
+                .unwrap_or(DataType::Text);
+            Column::new(name, data_type)
+        })
+        .collect();
+    Table { columns, rows: rows.into_iter().enumerate().collect(), primary_key: None }
+}
+
+/// Join the scope's tables left to right, choosing an operator for each join
+fn join_rows(scope: &Scope, joins: &[Join]) -> Vec<Row> {
+    let (qualifier, table) = &scope.tables[0];
+    let mut rows = qualified_rows(qualifier, table);
+    let mut left_qualifiers = vec![qualifier.as_str()];
+
+    for (join, (qualifier, table)) in joins.iter().zip(&scope.tables[1..]) {
+        let right = qualified_rows(qualifier, table);
+        let keys = join.condition.as_ref()
+            .map(|condition| equality_keys(condition, scope, &left_qualifiers, qualifier))
+            .unwrap_or_default();
+
+        // Unmatched rows of a LEFT join are padded with NULLs
+        let padding = Row {
+            data: table.columns.iter()
+                .map(|column| (format!("{}.{}", qualifier, column.name), Value::Null))
+                .collect(),
+        };
+        let padding = (join.kind == JoinKind::Left).then_some(&padding);
+
+        rows = match QueryOptimizer::choose_join_algorithm(rows.len(), right.len(), !keys.is_empty()) {
+            JoinAlgorithm::NestedLoop => nested_loop_join(&rows, &right, join.condition.as_ref(), padding),
+            JoinAlgorithm::HashJoin => hash_join(&rows, &right, &keys, join.condition.as_ref(), padding),
+        };
+        left_qualifiers.push(qualifier);
+    }
+
+    rows
+}
+
+/// A table's rows in insertion order, keyed `qualifier.column` with NULL for unset columns
+fn qualified_rows(qualifier: &str, table: &Table) -> Vec<Row> {
+    let mut rows: Vec<(&usize, &Row)> = table.rows.iter().collect();
+    rows.sort_by_key(|(id, _)| **id);
+    rows.into_iter()
+        .map(|(_, row)| Row {
+            data: table.columns.iter()
+                .map(|column| (
+                    format!("{}.{}", qualifier, column.name),
+                    row.data.get(&column.name).cloned().unwrap_or(Value::Null),
+                ))
+                .collect(),
+        })
+        .collect()
+}
+
+fn combine_rows(left: &Row, right: &Row) -> Row {
+    let mut data = left.data.clone();
+    data.extend(right.data.iter().map(|(key, value)| (key.clone(), value.clone())));
+    Row { data }
+}
+
+/// Compare every left row with every right row
+fn nested_loop_join(left: &[Row], right: &[Row], condition: Option<&Expr>, padding: Option<&Row>) -> Vec<Row> {
+    let mut output = Vec::new();
+    for left_row in left {
+        let mut matched = false;
+        for right_row in right {
+            let row = combine_rows(left_row, right_row);
+            if condition.is_none_or(|condition| condition.evaluate(&row)) {
+                output.push(row);
+                matched = true;
+            }
+        }
+        if let (false, Some(padding)) = (matched, padding) {
+            output.push(combine_rows(left_row, padding));
+        }
+    }
+    output
+}
+
+/// Hash the right rows on their join keys and probe with each left row. Candidates
+/// sharing a key are still checked against the full join condition.
+fn hash_join(
+    left: &[Row],
+    right: &[Row],
+    keys: &[(Expr, Expr)],
+    condition: Option<&Expr>,
+    padding: Option<&Row>,
+) -> Vec<Row> {
+    let mut buckets: HashMap<Vec<Value>, Vec<&Row>> = HashMap::new();
+    for row in right {
+        if let Some(key) = hash_key(keys.iter().map(|(_, right)| right), row) {
+            buckets.entry(key).or_default().push(row);
+        }
+    }
+
+    let mut output = Vec::new();
+    for left_row in left {
+        let mut matched = false;
+        let candidates = hash_key(keys.iter().map(|(left, _)| left), left_row)
+            .and_then(|key| buckets.get(&key));
+        for right_row in candidates.into_iter().flatten() {
+            let row = combine_rows(left_row, right_row);
+            if condition.is_none_or(|condition| condition.evaluate(&row)) {
+                output.push(row);
+                matched = true;
+            }
+        }
+        if let (false, Some(padding)) = (matched, padding) {
+            output.push(combine_rows(left_row, padding));
+        }
+    }
+    output
+}
+
+/// Evaluate join key expressions; rows with a NULL key never match
+fn hash_key<'e>(expressions: impl Iterator<Item = &'e Expr>, row: &Row) -> Option<Vec<Value>> {
+    expressions.map(|expr| hash_value(expr.eval(row))).collect()
+}
+
+/// The form of a value used as a hash key, or `None` for NULL, which matches nothing.
+/// Values hash as they compare, so a REAL holding an integer exactly meets that INTEGER
+/// while integers themselves stay exact, even beyond 2^53.
+fn hash_value(value: Value) -> Option<Value> {
+    (!value.is_null()).then_some(value)
+}
+
+/// A hashable form of a whole row, for finding rows that were evaluated up front
+fn row_key(row: &Row) -> Vec<(String, Value)> {
+    let mut key: Vec<(String, Value)> = row.data.iter()
+        .map(|(column, value)| (column.clone(), value.clone()))
+        .collect();
+    key.sort_by(|a, b| a.0.cmp(&b.0));
+    key
+}
+
+/// The type class of a hash key. INTEGER and REAL keys hash alike, so only other
+/// pairings can hide equal values from a hash lookup.
+fn key_type(key: &Value) -> Option<DataType> {
+    match key.data_type()? {
+        DataType::Real => Some(DataType::Integer),
+        data_type => Some(data_type),
+    }
+}
+
+/// The hashed results of an uncorrelated `IN (SELECT ...)` in WHERE
+struct SemiJoin {
+    operand: Expr,
+    keys: HashSet<Value>,
+    types: HashSet<DataType>,
+}
+
+impl SemiJoin {
+    fn new(operand: Expr, values: Vec<Value>) -> Self {
+        let keys: HashSet<Value> = values.into_iter().filter_map(hash_value).collect();
+        let types = keys.iter().filter_map(key_type).collect();
+        SemiJoin { operand, keys, types }
+    }
+
+    fn contains(&self, row: &Row) -> bool {
+        let Some(key) = hash_value(self.operand.eval(row)) else {
+            return false;
+        };
+        if self.keys.contains(&key) {
+            return true;
+        }
+
+        // Text compares equal to numbers it parses as, which hashing cannot see
+        self.types.iter().any(|data_type| Some(*data_type) != key_type(&key))
+            && self.keys.iter().any(|value| key.compare(value) == Some(Ordering::Equal))
+    }
+}
+
+/// Conjuncts `left_column = right_column` that can serve as hash join keys. Columns
+/// must have the same type (or both be numeric) so equal values hash alike.
+fn equality_keys(condition: &Expr, scope: &Scope, left_qualifiers: &[&str], right_qualifier: &str) -> Vec<(Expr, Expr)> {
+    let side = |key: &str| key.split_once('.').map(|(qualifier, _)| qualifier == right_qualifier);
+    let numeric = |data_type| matches!(data_type, DataType::Integer | DataType::Real);
+
+    condition.conjuncts().into_iter()
+        .filter_map(|conjunct| match conjunct {
+            Expr::Binary { left, operator: BinaryOperator::Equal, right } => {
+                match (left.as_ref(), right.as_ref()) {
+                    (Expr::Column(a), Expr::Column(b)) => {
+                        let (left_key, right_key) = match (side(a)?, side(b)?) {
+                            (false, true) => (a, b),
+                            (true, false) => (b, a),
+                            _ => return None,
+                        };
+                        let left_qualifier = left_key.split_once('.')?.0;
+                        if !left_qualifiers.contains(&left_qualifier) {
+                            return None;
+                        }
+
+                        let (left_type, right_type) = (scope.column_type(left_key)?, scope.column_type(right_key)?);
+                        (left_type == right_type || (numeric(left_type) && numeric(right_type)))
+                            .then(|| (Expr::Column(left_key.clone()), Expr::Column(right_key.clone())))
+                    }
+                    _ => None,
+                }
+            }
+            _ => None,
+        })
+        .collect()
+}
+
+/// Evaluate the output columns of a query against a row
+fn project_row(row: &Row, outputs: &[(String, Expr)]) -> Row {
+    Row {
+        data: outputs.iter().map(|(name, expr)| (name.clone(), expr.eval(row))).collect(),
+    }
+}
+
+/// Combine the rows of two SELECTs, whose columns share names. Rows match when their
+/// values are equal, NULLs included. Without ALL the result holds distinct rows; with
+/// it, INTERSECT keeps a row as often as both sides have it and EXCEPT removes one
+/// left row per right row.
+fn set_operation(left: Vec<Row>, right: Vec<Row>, operator: SetOperator, all: bool, columns: &[String]) -> Vec<Row> {
+    let key = |row: &Row| -> Vec<Option<Value>> {
+        columns.iter().map(|column| hash_value(row.data.get(column).cloned().unwrap_or(Value::Null))).collect()
+    };
+
+    let mut rows: Vec<Row> = match operator {
+        SetOperator::Union => left.into_iter().chain(right).collect(),
+        SetOperator::Intersect | SetOperator::Except => {
+            let mut counts: HashMap<Vec<Option<Value>>, usize> = HashMap::new();
+            for row in &right {
+                *counts.entry(key(row)).or_default() += 1;
+            }
+            left.into_iter()
+                .filter(|row| {
+                    let matched = match counts.get_mut(&key(row)) {
+                        Some(count) if *count > 0 => {
+                            if all {
+                                *count -= 1;
+                            }
+                            true
+                        }
+                        _ => false,
+                    };
+                    matched == (operator == SetOperator::Intersect)
+                })
+                .collect()
+        }
+    };
+    if !all {
+        let mut seen = HashSet::new();
+        rows.retain(|row| seen.insert(key(row)));
+    }
+    rows
+}
+
+/// Apply ORDER BY, OFFSET and LIMIT to rows tagged with their position
+fn order_and_limit<R: Borrow<Row>>(
+    rows: impl Iterator<Item = (usize, R)>,
+    order_by: &[OrderByItem],
+    offset: Option<usize>,
+    limit: Option<usize>,
+) -> Vec<R> {
+    let offset = offset.unwrap_or(0);
+    let limit = limit.unwrap_or(usize::MAX);
+
+    if order_by.is_empty() {
+        // No sort needed, so stop scanning as soon as the limit is satisfied
+        rows.skip(offset).take(limit).map(|(_, row)| row).collect()
+    } else {
+        // Start from insertion order so rows with equal keys keep a stable order
+        let mut rows: Vec<(usize, R)> = rows.collect();
+        rows.sort_by_key(|(id, _)| *id);
+        sort_rows(rows.into_iter().map(|(_, row)| row).collect(), order_by)
+            .into_iter()
+            .skip(offset)
+            .take(limit)
+            .collect()
+    }
+}
+
+/// Hash aggregation: one set of accumulators per distinct grouping key. Each output
+/// row holds the grouping expressions and aggregates under their display names.
+fn hash_aggregate<'r>(
+    rows: impl Iterator<Item = &'r Row>,
+    group_by: &[Expr],
+    aggregates: &[&Expr],
+) -> Vec<Row> {
+    let calls: Vec<(Accumulator, Option<&Expr>)> = aggregates.iter()
+        .filter_map(|aggregate| match aggregate {
+            Expr::Aggregate { function, argument, distinct } => {
+                Some((Accumulator::new(*function, *distinct), argument.as_deref()))
+            }
+            _ => None,
+        })
+        .collect();
+    let new_accumulators = || calls.iter().map(|(accumulator, _)| accumulator.clone()).collect::<Vec<_>>();
+
+    let mut groups: Vec<(Vec<Value>, Vec<Accumulator>)> = Vec::new();
+    let mut positions: HashMap<Vec<Value>, usize> = HashMap::new();
+    for row in rows {
+        // NULL grouping values form a single group
+        let key: Vec<Value> = group_by.iter().map(|expr| expr.eval(row)).collect();
+        let position = *positions.entry(key.clone()).or_insert_with(|| {
+            groups.push((key, new_accumulators()));
+            groups.len() - 1
+        });
+
+        for (accumulator, (_, argument)) in groups[position].1.iter_mut().zip(&calls) {
+            accumulator.update(argument.map(|argument| argument.eval(row)));
+        }
+    }
+
+    // Aggregates without GROUP BY produce one row even when no rows matched
+    if group_by.is_empty() && groups.is_empty() {
+        groups.push((Vec::new(), new_accumulators()));
+    }
+
+    groups
+        .into_iter()
+        .map(|(key, accumulators)| {
+            let mut data: HashMap<String, Value> = group_by.iter().map(|expr| expr.to_string()).zip(key).collect();
+            for (aggregate, accumulator) in aggregates.iter().zip(accumulators) {
+                data.insert(aggregate.to_string(), accumulator.finish());
+            }
+            Row { data }
+        })
+        .collect()
+}
+
+/// Rewrite an expression over groups so that grouping expressions and aggregate
+/// calls read the columns produced by `hash_aggregate`
+fn grouped_expr(expr: &Expr, group_by: &[Expr]) -> Expr {
+    let grouped: Result<Expr, std::convert::Infallible> = expr.rewrite(&mut |node| {
+        let computed = group_by.contains(node) || matches!(node, Expr::Aggregate { .. });
+        Ok(computed.then(|| Expr::Column(node.to_string())))
+    });
+    grouped.unwrap_or_else(|never| match never {})
+}
+
+/// Running state of one aggregate call within a group
+#[derive(Clone)]
+enum Accumulator {
+    Count(i64),
+    Sum(Option<Value>),
+    Avg { sum: f64, count: i64 },
+    Min(Option<Value>),
+    Max(Option<Value>),
+    /// Feeds each distinct value to the inner accumulator once
+    Distinct { seen: HashSet<Value>, inner: Box<Accumulator> },
+}
+
+impl Accumulator {
+    fn new(function: AggregateFunction, distinct: bool) -> Self {
+        let accumulator = match function {
+            AggregateFunction::Count => Accumulator::Count(0),
+            AggregateFunction::Sum => Accumulator::Sum(None),
+            AggregateFunction::Avg => Accumulator::Avg { sum: 0.0, count: 0 },
+            AggregateFunction::Min => Accumulator::Min(None),
+            AggregateFunction::Max => Accumulator::Max(None),
+        };
+
+        if distinct {
+            Accumulator::Distinct { seen: HashSet::new(), inner: Box::new(accumulator) }
+        } else {
+            accumulator
+        }
+    }
+
+    /// Add the argument value of one row; `None` is the `*` of COUNT(*)
+    fn update(&mut self, value: Option<Value>) {
+        let value = match value {
+            None => Value::Boolean(true), // COUNT(*) counts every row
+            Some(value) if value.is_null() => return, // Aggregates ignore NULLs
+            Some(value) => value,
+        };
+
+        match self {
+            Accumulator::Distinct { seen, inner } => {
+                if seen.insert(value.clone()) {
+                    inner.update(Some(value));
+                }
+            }
+            Accumulator::Count(count) => *count += 1,
+            Accumulator::Sum(sum) => {
+                let Some(value) = numeric(value) else { return };
+                *sum = Some(match (sum.take(), value) {
+                    (None, value) => value,
+                    (Some(Value::Integer(a)), Value::Integer(b)) => {
+                        // Fall back to REAL rather than overflow
+                        a.checked_add(b).map_or(Value::Real(a as f64 + b as f64), Value::Integer)
+                    }
+                    (Some(a), b) => Value::Real(as_f64(&a) + as_f64(&b)),
+                });
+            }
+            Accumulator::Avg { sum, count } => {
+                if let Some(value) = numeric(value) {
+                    *sum += as_f64(&value);
+                    *count += 1;
+                }
+            }
+            Accumulator::Min(min) => {
+                if min.as_ref().is_none_or(|min| value.sort_cmp(min) == Ordering::Less) {
+                    *min = Some(value);
+                }
+            }
+            Accumulator::Max(max) => {
+                if max.as_ref().is_none_or(|max| value.sort_cmp(max) == Ordering::Greater) {
+                    *max = Some(value);
+                }
+            }
+        }
+    }
+
+    /// The aggregate result; empty SUM, AVG, MIN and MAX are NULL
+    fn finish(self) -> Value {
+        match self {
+            Accumulator::Distinct { inner, .. } => inner.finish(),
+            Accumulator::Count(count) => Value::Integer(count),
+            Accumulator::Avg { count: 0, .. } => Value::Null,
+            Accumulator::Avg { sum, count } => Value::Real(sum / count as f64),
+            Accumulator::Sum(value) | Accumulator::Min(value) | Accumulator::Max(value) => {
+                value.unwrap_or(Value::Null)
+            }
+        }
+    }
+}
+
+/// Numbers pass through; other values are used only if they convert to a number
+fn numeric(value: Value) -> Option<Value> {
+    match value {
+        Value::Integer(_) | Value::Real(_) => Some(value),
+        other => other.coerce_to(DataType::Real),
+    }
+}
+
+/// Sort rows by ORDER BY keys, evaluating each key once per row
+fn sort_rows<R: Borrow<Row>>(rows: Vec<R>, order_by: &[OrderByItem]) -> Vec<R> {
+    let mut keyed: Vec<(Vec<Value>, R)> = rows
+        .into_iter()
+        .map(|row| (order_by.iter().map(|item| item.expr.eval(row.borrow())).collect(), row))
+        .collect();
+
+    keyed.sort_by(|(left, _), (right, _)| {
+        for (item, (a, b)) in order_by.iter().zip(left.iter().zip(right)) {
+            let ordering = match (a.is_null(), b.is_null()) {
+                (true, true) => Ordering::Equal,
+                (true, false) if item.nulls_first => Ordering::Less,
+                (true, false) => Ordering::Greater,
+                (false, true) if item.nulls_first => Ordering::Greater,
+                (false, true) => Ordering::Less,
+                (false, false) if item.descending => b.sort_cmp(a),
+                (false, false) => a.sort_cmp(b),
+            };
+            if ordering != Ordering::Equal {
+                return ordering;
+            }
+        }
+        Ordering::Equal
+    });
+
+    keyed.into_iter().map(|(_, row)| row).collect()
 }
 
 #[derive(Debug)]
 pub enum ExecutionError {
     TableNotFound,
-    InsertFailed,
-    UpdateFailed,
+    InsertFailed(String),
+    UpdateFailed(String),
+    DeleteFailed(String),
+    CreateTableFailed(String),
+    DropTableFailed(String),
+    AlterTableFailed(String),
+    CreateViewFailed(String),
+    DropViewFailed(String),
+    /// A view's query could not be read or run
+    ViewFailed(String),
+    /// A recursive WITH query was still adding rows after this many iterations
+    RecursionLimit(String, usize),
+    /// The SELECTs of a UNION, INTERSECT or EXCEPT could not be combined
+    SetOperationFailed(String),
+    AmbiguousColumn(String),
+    SubqueryFailed(String),
+    UnboundParameter(usize),
     InvalidQuery,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::parser::Parser;
+
+    fn ages(values: &[Option<i64>]) -> Vec<Row> {
+        values.iter()
+            .map(|age| Row { data: HashMap::from([("age".to_string(), age.map_or(Value::Null, Value::Integer))]) })
+            .collect()
+    }
+
+    fn sorted(rows: &[Row], descending: bool, nulls_first: bool) -> Vec<Option<i64>> {
+        let order_by = [OrderByItem { expr: Expr::Column("age".to_string()), descending, nulls_first }];
+        sort_rows(rows.iter().collect(), &order_by).into_iter().map(age).collect()
+    }
+
+    fn age(row: &Row) -> Option<i64> {
+        match row.data["age"] {
+            Value::Integer(age) => Some(age),
+            _ => None,
+        }
+    }
+
+    #[test]
+    fn nulls_sort_where_asked_in_either_direction() {
+        let rows = ages(&[Some(2), None, Some(3), Some(1)]);
+        assert_eq!(sorted(&rows, false, false), vec![Some(1), Some(2), Some(3), None]);
+        assert_eq!(sorted(&rows, false, true), vec![None, Some(1), Some(2), Some(3)]);
+        assert_eq!(sorted(&rows, true, true), vec![None, Some(3), Some(2), Some(1)]);
+        assert_eq!(sorted(&rows, true, false), vec![Some(3), Some(2), Some(1), None]);
+    }
+
+    #[test]
+    fn later_keys_break_ties() {
+        let rows: Vec<Row> = [(1, "b"), (2, "a"), (1, "a")].iter()
+            .map(|&(age, name)| Row { data: HashMap::from([
+                ("age".to_string(), Value::Integer(age)),
+                ("name".to_string(), Value::Text(name.to_string())),
+            ]) })
+            .collect();
+        let order_by = [
+            OrderByItem { expr: Expr::Column("age".to_string()), descending: true, nulls_first: true },
+            OrderByItem { expr: Expr::Column("name".to_string()), descending: false, nulls_first: false },
+        ];
+        let names: Vec<String> = sort_rows(rows.iter().collect(), &order_by).iter()
+            .map(|row| row.data["name"].to_string())
+            .collect();
+        assert_eq!(names, vec!["a", "a", "b"]);
+    }
+
+    #[test]
+    fn limit_without_order_by_stops_scanning_early() {
+        let rows = ages(&[Some(1), Some(2), Some(3), Some(4), Some(5)]);
+        let scanned = std::cell::Cell::new(0);
+        let tagged = rows.iter().enumerate().inspect(|_| scanned.set(scanned.get() + 1));
+
+        let limited: Vec<Option<i64>> = order_and_limit(tagged, &[], Some(1), Some(2)).into_iter().map(age).collect();
+        assert_eq!(limited, vec![Some(2), Some(3)]);
+        assert_eq!(scanned.get(), 3);
+    }
+
+    #[test]
+    fn offset_and_limit_apply_after_sorting() {
+        let rows = ages(&[Some(3), Some(1), Some(2), Some(5), Some(4)]);
+        let order_by = [OrderByItem { expr: Expr::Column("age".to_string()), descending: true, nulls_first: true }];
+
+        let page: Vec<Option<i64>> = order_and_limit(rows.iter().enumerate(), &order_by, Some(1), Some(3))
+            .into_iter()
+            .map(age)
+            .collect();
+        assert_eq!(page, vec![Some(4), Some(3), Some(2)]);
+        assert!(order_and_limit(rows.iter().enumerate(), &order_by, Some(10), None).is_empty());
+    }
+
+    fn aggregate(function: AggregateFunction, column: Option<&str>) -> Expr {
+        Expr::Aggregate {
+            function,
+            argument: column.map(|column| Box::new(Expr::Column(column.to_string()))),
+            distinct: false,
+        }
+    }
+
+    fn aggregate_rows(rows: &[Row], group_by: &[Expr], aggregates: &[Expr]) -> Vec<Row> {
+        hash_aggregate(rows.iter(), group_by, &aggregates.iter().collect::<Vec<_>>())
+    }
+
+    #[test]
+    fn aggregates_over_no_rows_give_one_row_of_empty_results() {
+        let aggregates = [
+            aggregate(AggregateFunction::Count, None),
+            aggregate(AggregateFunction::Sum, Some("age")),
+            aggregate(AggregateFunction::Avg, Some("age")),
+            aggregate(AggregateFunction::Min, Some("age")),
+            aggregate(AggregateFunction::Max, Some("age")),
+        ];
+        let result = aggregate_rows(&[], &[], &aggregates);
+        assert_eq!(result.len(), 1);
+        assert_eq!(result[0].data["COUNT(*)"], Value::Integer(0));
+        for name in ["SUM(age)", "AVG(age)", "MIN(age)", "MAX(age)"] {
+            assert_eq!(result[0].data[name], Value::Null, "{}", name);
+        }
+
+        // With GROUP BY there is no group at all
+        assert!(aggregate_rows(&[], &[Expr::Column("age".to_string())], &aggregates).is_empty());
+    }
+
+    #[test]
+    fn aggregates_skip_nulls_except_count_star() {
+        let aggregates = [
+            aggregate(AggregateFunction::Count, None),
+            aggregate(AggregateFunction::Count, Some("age")),
+            aggregate(AggregateFunction::Sum, Some("age")),
+            aggregate(AggregateFunction::Avg, Some("age")),
+            aggregate(AggregateFunction::Min, Some("age")),
+            aggregate(AggregateFunction::Max, Some("age")),
+        ];
+        let result = aggregate_rows(&ages(&[Some(1), None, Some(3)]), &[], &aggregates);
+        let data = &result[0].data;
+        assert_eq!(data["COUNT(*)"], Value::Integer(3));
+        assert_eq!(data["COUNT(age)"], Value::Integer(2));
+        assert_eq!(data["SUM(age)"], Value::Integer(4));
+        assert_eq!(data["AVG(age)"], Value::Real(2.0));
+        assert_eq!(data["MIN(age)"], Value::Integer(1));
+        assert_eq!(data["MAX(age)"], Value::Integer(3));
+    }
+
+    #[test]
+    fn null_grouping_values_form_one_group() {
+        let group_by = [Expr::Column("age".to_string())];
+        let result = aggregate_rows(
+            &ages(&[None, Some(1), None, Some(1), Some(2)]),
+            &group_by,
+            &[aggregate(AggregateFunction::Count, None)],
+        );
+        let mut counts: Vec<(Option<i64>, Value)> = result.iter()
+            .map(|group| (age(group), group.data["COUNT(*)"].clone()))
+            .collect();
+        counts.sort_by_key(|(age, _)| *age);
+        assert_eq!(counts, vec![
+            (None, Value::Integer(2)),
+            (Some(1), Value::Integer(2)),
+            (Some(2), Value::Integer(1)),
+        ]);
+    }
+
+    #[test]
+    fn distinct_aggregates_count_each_value_once() {
+        let distinct = |function| Expr::Aggregate {
+            function,
+            argument: Some(Box::new(Expr::Column("age".to_string()))),
+            distinct: true,
+        };
+        let mut rows = ages(&[Some(1), Some(2), None, Some(2), Some(1)]);
+        rows.push(Row { data: HashMap::from([("age".to_string(), Value::Real(1.0))]) });
+
+        let aggregates = [
+            distinct(AggregateFunction::Count),
+            distinct(AggregateFunction::Sum),
+            distinct(AggregateFunction::Avg),
+            aggregate(AggregateFunction::Count, Some("age")),
+        ];
+        let result = aggregate_rows(&rows, &[], &aggregates);
+        let data = &result[0].data;
+        assert_eq!(data["COUNT(DISTINCT age)"], Value::Integer(2));
+        assert_eq!(data["SUM(DISTINCT age)"], Value::Integer(3));
+        assert_eq!(data["AVG(DISTINCT age)"], Value::Real(1.5));
+        assert_eq!(data["COUNT(age)"], Value::Integer(5));
+    }
+
+    const BIG: i64 = 9_007_199_254_740_992;
+
+    /// Rows of one column `table.id`, with NULL for `None`
+    fn keyed(table: &str, values: &[Option<Value>]) -> Vec<Row> {
+        values.iter()
+            .map(|value| Row { data: HashMap::from([(format!("{table}.id"), value.clone().unwrap_or(Value::Null))]) })
+            .collect()
+    }
+
+    fn id_pairs(rows: &[Row]) -> Vec<(String, String)> {
+        let mut pairs: Vec<(String, String)> = rows.iter()
+            .map(|row| (row.data["a.id"].to_string(), row.data["b.id"].to_string()))
+            .collect();
+        pairs.sort();
+        pairs
+    }
+
+    #[test]
+    fn left_join_pads_unmatched_rows_with_nulls() {
+        let left = keyed("a", &[Some(Value::Integer(1)), Some(Value::Integer(2)), None]);
+        let right = keyed("b", &[Some(Value::Integer(1)), Some(Value::Integer(1))]);
+        let padding = Row { data: HashMap::from([("b.id".to_string(), Value::Null)]) };
+        let condition = Expr::Binary {
+            left: Box::new(Expr::Column("a.id".to_string())),
+            operator: BinaryOperator::Equal,
+            right: Box::new(Expr::Column("b.id".to_string())),
+        };
+
+        let joined = nested_loop_join(&left, &right, Some(&condition), Some(&padding));
+        assert_eq!(id_pairs(&joined), vec![
+            ("1".to_string(), "1".to_string()),
+            ("1".to_string(), "1".to_string()),
+            ("2".to_string(), "NULL".to_string()),
+            ("NULL".to_string(), "NULL".to_string()),
+        ]);
+    }
+
+    #[test]
+    fn hash_join_agrees_with_nested_loop() {
+        let left = keyed("a", &[
+            Some(Value::Integer(1)), Some(Value::Real(2.0)), Some(Value::Real(2.5)), None, Some(Value::Integer(4)),
+        ]);
+        let right = keyed("b", &[
+            Some(Value::Real(1.0)), Some(Value::Integer(2)), Some(Value::Integer(2)), None, Some(Value::Integer(3)),
+        ]);
+        let keys = [(Expr::Column("a.id".to_string()), Expr::Column("b.id".to_string()))];
+        let condition = Expr::Binary {
+            left: Box::new(keys[0].0.clone()),
+            operator: BinaryOperator::Equal,
+            right: Box::new(keys[0].1.clone()),
+        };
+        let padding = Row { data: HashMap::from([("b.id".to_string(), Value::Null)]) };
+
+        for padding in [None, Some(&padding)] {
+            assert_eq!(
+                id_pairs(&hash_join(&left, &right, &keys, Some(&condition), padding)),
+                id_pairs(&nested_loop_join(&left, &right, Some(&condition), padding)),
+            );
+        }
+    }
+
+    #[test]
+    fn hash_join_keeps_integers_beyond_2_pow_53_apart() {
+        let left = keyed("a", &[Some(Value::Integer(BIG))]);
+        let right = keyed("b", &[Some(Value::Integer(BIG)), Some(Value::Integer(BIG + 1))]);
+        let keys = [(Expr::Column("a.id".to_string()), Expr::Column("b.id".to_string()))];
+        let joined = hash_join(&left, &right, &keys, None, None);
+        assert_eq!(joined.len(), 1);
+        assert_eq!(joined[0].data["b.id"], Value::Integer(BIG));
+    }
+
+    #[test]
+    fn semi_join_keeps_integers_beyond_2_pow_53_apart() {
+        let semi_join = SemiJoin::new(Expr::Column("a.id".to_string()), vec![Value::Integer(BIG + 1)]);
+        let rows = keyed("a", &[Some(Value::Integer(BIG)), Some(Value::Integer(BIG + 1))]);
+        assert!(!semi_join.contains(&rows[0]));
+        assert!(semi_join.contains(&rows[1]));
+    }
+
+    #[test]
+    fn semi_join_matches_integral_reals_and_never_null() {
+        let semi_join = SemiJoin::new(Expr::Column("a.id".to_string()), vec![Value::Real(2.0), Value::Null]);
+        let rows = keyed("a", &[Some(Value::Integer(2)), Some(Value::Integer(3)), None]);
+        assert!(semi_join.contains(&rows[0]));
+        assert!(!semi_join.contains(&rows[1]));
+        assert!(!semi_join.contains(&rows[2]));
+    }
+
+    /// A database in a scratch file, removed again when dropped
+    struct Scratch {
+        path: std::path::PathBuf,
+        filesystem: FileSystem,
+    }
+
+    impl Scratch {
+        fn new(name: &str, statements: &[&str]) -> Self {
+            let path = std::env::temp_dir().join(format!("hyper_vault_{}_{}.db", name, std::process::id()));
+            let _ = std::fs::remove_file(&path);
+            let mut scratch = Scratch { filesystem: FileSystem::new(path.to_str().unwrap()), path };
+            for sql in statements {
+                scratch.run(sql).unwrap();
+            }
+            scratch
+        }
+
+        fn run(&mut self, sql: &str) -> Result<QueryResult, ExecutionError> {
+            let ast = Parser::parse(sql).unwrap();
+            QueryExecutor::new(&mut self.filesystem).execute(ast)
+        }
+
+        /// The rows a query returns, each as its values in output column order
+        fn values(&mut self, sql: &str) -> Vec<Vec<Value>> {
+            let result = self.run(sql).unwrap();
+            result.rows.iter()
+                .map(|row| result.columns.iter().map(|column| row.data[column].clone()).collect())
+                .collect()
+        }
+    }
+
+    impl Drop for Scratch {
+        fn drop(&mut self) {
+            let _ = std::fs::remove_file(&self.path);
+        }
+    }
+
+    fn text(text: &str) -> Value {
+        Value::Text(text.to_string())
+    }
+
+    #[test]
+    fn returning_reports_the_rows_each_write_affected() {
+        let mut db = Scratch::new("returning", &[
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)",
+            "INSERT INTO users VALUES (1, 'Ann'), (2, 'Bo')",
+        ]);
+
+        assert_eq!(db.values("INSERT INTO users VALUES (3, 'Cy') RETURNING id, UPPER(name) AS shout"), vec![
+            vec![Value::Integer(3), text("CY")],
+        ]);
+        assert_eq!(db.values("UPDATE users SET name = 'Al' WHERE id < 2 RETURNING users.name"), vec![
+            vec![text("Al")],
+        ]);
+        let mut deleted = db.values("DELETE FROM users WHERE id >= 2 RETURNING *");
+        deleted.sort_by(|a, b| a[0].sort_cmp(&b[0]));
+        assert_eq!(deleted, vec![vec![Value::Integer(2), text("Bo")], vec![Value::Integer(3), text("Cy")]]);
+
+        let quiet = db.run("DELETE FROM users WHERE id = 9").unwrap();
+        assert_eq!(quiet.affected_rows, Some(0));
+        assert!(quiet.columns.is_empty());
+    }
+
+    #[test]
+    fn on_conflict_do_nothing_skips_rows_whose_key_is_taken() {
+        let mut db = Scratch::new("on_conflict_nothing", &[
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)",
+            "INSERT INTO users VALUES (1, 'Ann')",
+        ]);
+
+        let inserted = db.values("INSERT INTO users VALUES (1, 'Al'), (2, 'Bo') ON CONFLICT (id) DO NOTHING RETURNING id");
+        assert_eq!(inserted, vec![vec![Value::Integer(2)]]);
+        assert_eq!(db.values("SELECT name FROM users ORDER BY id"), vec![vec![text("Ann")], vec![text("Bo")]]);
+    }
+
+    #[test]
+    fn on_conflict_do_update_reads_the_stored_and_the_proposed_row() {
+        let mut db = Scratch::new("on_conflict_update", &[
+            "CREATE TABLE stock (item TEXT PRIMARY KEY, count INTEGER)",
+            "INSERT INTO stock VALUES ('nut', 5)",
+        ]);
+
+        db.run("INSERT INTO stock VALUES ('nut', 2), ('bolt', 1) \
+                ON CONFLICT (item) DO UPDATE SET count = stock.count + excluded.count").unwrap();
+        assert_eq!(db.values("SELECT item, count FROM stock ORDER BY item"), vec![
+            vec![text("bolt"), Value::Integer(1)],
+            vec![text("nut"), Value::Integer(7)],
+        ]);
+
+        // A batch cannot update the same row twice, whichever row would win
+        let twice = db.run("INSERT INTO stock VALUES ('bolt', 1), ('bolt', 2) \
+                            ON CONFLICT (item) DO UPDATE SET count = excluded.count");
+        assert!(matches!(twice, Err(ExecutionError::InsertFailed(message)) if message.contains("second time")));
+        assert_eq!(db.values("SELECT count FROM stock WHERE item = 'bolt'"), vec![vec![Value::Integer(1)]]);
+    }
+
+    fn graph(name: &str) -> Scratch {
+        Scratch::new(name, &[
+            "CREATE TABLE edges (id INTEGER PRIMARY KEY, source INTEGER, target INTEGER)",
+            "INSERT INTO edges VALUES (1, 1, 2), (2, 2, 3), (3, 3, 1)",
+        ])
+    }
+
+    #[test]
+    fn recursive_union_stops_once_a_cycle_adds_nothing_new() {
+        let mut db = graph("recursive_union");
+        let reachable = db.values(
+            "WITH RECURSIVE reach(node) AS (\
+                SELECT target FROM edges WHERE source = 1 \
+                UNION SELECT edges.target FROM edges JOIN reach ON edges.source = reach.node\
+            ) SELECT node FROM reach ORDER BY node",
+        );
+        assert_eq!(reachable, vec![vec![Value::Integer(1)], vec![Value::Integer(2)], vec![Value::Integer(3)]]);
+    }
+
+    #[test]
+    fn recursive_union_all_stops_when_the_step_adds_no_rows() {
+        let mut db = graph("recursive_union_all");
+        let steps = db.values(
+            "WITH RECURSIVE walk(node, hops) AS (\
+                SELECT target, 1 FROM edges WHERE source = 1 \
+                UNION ALL SELECT edges.target, walk.hops + 1 FROM edges JOIN walk ON edges.source = walk.node \
+                WHERE walk.hops < 5\
+            ) SELECT hops, node FROM walk ORDER BY hops",
+        );
+        let hops: Vec<Value> = steps.iter().map(|row| row[0].clone()).collect();
+        assert_eq!(hops, (1..=5).map(Value::Integer).collect::<Vec<_>>());
+        assert_eq!(steps[3][1], Value::Integer(2));
+    }
+
+    #[test]
+    fn recursive_union_all_around_a_cycle_hits_the_iteration_limit() {
+        let mut db = graph("recursive_limit");
+        let endless = db.run(
+            "WITH RECURSIVE walk(node) AS (\
+                SELECT target FROM edges WHERE source = 1 \
+                UNION ALL SELECT edges.target FROM edges JOIN walk ON edges.source = walk.node\
+            ) SELECT node FROM walk",
+        );
+        assert!(matches!(
+            endless,
+            Err(ExecutionError::RecursionLimit(name, MAX_RECURSIVE_ITERATIONS)) if name == "walk"
+        ));
+    }
+
+    fn ids(values: &[i64]) -> Vec<Row> {
+        values.iter()
+            .map(|&id| Row { data: HashMap::from([("id".to_string(), Value::Integer(id))]) })
+            .collect()
+    }
+
+    fn combine(left: &[i64], right: &[i64], operator: SetOperator, all: bool) -> Vec<i64> {
+        let mut result: Vec<i64> = set_operation(ids(left), ids(right), operator, all, &["id".to_string()])
+            .into_iter()
+            .map(|row| match row.data["id"] {
+                Value::Integer(id) => id,
+                ref other => panic!("unexpected value {other}"),
+            })
+            .collect();
+        result.sort();
+        result
+    }
+
+    #[test]
+    fn all_keeps_duplicates_that_the_plain_operators_drop() {
+        assert_eq!(combine(&[1, 1, 2], &[2, 3], SetOperator::Union, false), vec![1, 2, 3]);
+        assert_eq!(combine(&[1, 1, 2], &[2, 3], SetOperator::Union, true), vec![1, 1, 2, 2, 3]);
+        assert_eq!(combine(&[1, 1, 1, 2], &[1, 1], SetOperator::Intersect, true), vec![1, 1]);
+        assert_eq!(combine(&[1, 1, 1, 2], &[1], SetOperator::Except, true), vec![1, 1, 2]);
+        assert_eq!(combine(&[1, 1, 1, 2], &[1], SetOperator::Except, false), vec![2]);
+    }
+
+    #[test]
+    fn set_operations_keep_integers_beyond_2_pow_53_apart() {
+        assert_eq!(combine(&[BIG], &[BIG + 1], SetOperator::Union, false), vec![BIG, BIG + 1]);
+        assert_eq!(combine(&[BIG, BIG + 1], &[BIG + 1], SetOperator::Intersect, false), vec![BIG + 1]);
+        assert_eq!(combine(&[BIG, BIG + 1], &[BIG], SetOperator::Except, false), vec![BIG + 1]);
+    }
+
+    #[test]
+    fn set_operations_match_integral_reals_with_integers() {
+        let left = ids(&[1, 2]);
+        let right = vec![Row { data: HashMap::from([("id".to_string(), Value::Real(2.0))]) }];
+        let result = set_operation(left, right, SetOperator::Except, false, &["id".to_string()]);
+        assert_eq!(result, ids(&[1]));
+    }
+
+    #[test]
+    fn compound_selects_sort_by_output_position() {
+        let mut db = Scratch::new("compound_order", &[
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)",
+            "INSERT INTO users VALUES (1, 'Cy'), (2, 'Ann'), (3, 'Bo')",
+        ]);
+        let names = db.values("SELECT id, name FROM users WHERE id < 3 UNION SELECT * FROM users WHERE id > 1 ORDER BY 2 DESC");
+        assert_eq!(names, vec![
+            vec![Value::Integer(1), text("Cy")],
+            vec![Value::Integer(3), text("Bo")],
+            vec![Value::Integer(2), text("Ann")],
+        ]);
+        assert!(db.run("SELECT id FROM users UNION SELECT id FROM users ORDER BY 2").is_err());
+    }
+
+    #[test]
+    fn order_by_positions_count_the_columns_of_a_wildcard() {
+        let mut db = Scratch::new("wildcard_order", &[
+            "CREATE TABLE users (id INTEGER PRIMARY KEY, name TEXT)",
+            "INSERT INTO users VALUES (1, 'Cy'), (2, 'Ann'), (3, 'Bo')",
+        ]);
+        let first = |rows: Vec<Vec<Value>>| rows.into_iter().map(|row| row[0].clone()).collect::<Vec<_>>();
+        let ids = |ids: &[i64]| ids.iter().copied().map(Value::Integer).collect::<Vec<_>>();
+        assert_eq!(first(db.values("SELECT * FROM users ORDER BY 1 DESC")), ids(&[3, 2, 1]));
+        assert_eq!(first(db.values("SELECT id, * FROM users ORDER BY 2 DESC")), ids(&[3, 2, 1]));
+        assert_eq!(first(db.values("SELECT id, * FROM users ORDER BY 3")), ids(&[2, 3, 1]));
+        assert_eq!(first(db.values("SELECT DISTINCT * FROM users ORDER BY 2")), ids(&[2, 3, 1]));
+        assert!(db.run("SELECT * FROM users ORDER BY 3").is_err());
+    }
+}
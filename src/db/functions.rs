@@ -0,0 +1,255 @@
+//! Built-in scalar functions and CAST conversions.
+
+use std::cmp::Ordering;
+use super::schema::{DataType, Value};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScalarFunction {
+    Upper,
+    Lower,
+    Length,
+    Substr,
+    Trim,
+    Replace,
+    Coalesce,
+    Nullif,
+    Abs,
+    Round,
+}
+
+impl ScalarFunction {
+    /// Look up a function by its SQL name, ignoring case
+    pub fn from_name(name: &str) -> Option<Self> {
+        let function = match name.to_uppercase().as_str() {
+            "UPPER" => ScalarFunction::Upper,
+            "LOWER" => ScalarFunction::Lower,
+            "LENGTH" => ScalarFunction::Length,
+            "SUBSTR" | "SUBSTRING" => ScalarFunction::Substr,
+            "TRIM" => ScalarFunction::Trim,
+            "REPLACE" => ScalarFunction::Replace,
+            "COALESCE" => ScalarFunction::Coalesce,
+            "NULLIF" => ScalarFunction::Nullif,
+            "ABS" => ScalarFunction::Abs,
+            "ROUND" => ScalarFunction::Round,
+            _ => return None,
+        };
+        Some(function)
+    }
+
+    /// The fewest and most arguments the function takes; `None` means no limit
+    pub fn arity(self) -> (usize, Option<usize>) {
+        match self {
+            ScalarFunction::Upper | ScalarFunction::Lower | ScalarFunction::Length | ScalarFunction::Abs => (1, Some(1)),
+            ScalarFunction::Trim | ScalarFunction::Round => (1, Some(2)),
+            ScalarFunction::Substr => (2, Some(3)),
+            ScalarFunction::Nullif => (2, Some(2)),
+            ScalarFunction::Replace => (3, Some(3)),
+            ScalarFunction::Coalesce => (1, None),
+        }
+    }
+
+    /// Apply the function to its evaluated arguments. Any NULL argument makes the result
+    /// NULL, except for COALESCE and NULLIF, and so do arguments of the wrong type.
+    pub fn call(self, arguments: &[Value]) -> Value {
+        match self {
+            ScalarFunction::Coalesce => {
+                return arguments.iter().find(|value| !value.is_null()).cloned().unwrap_or(Value::Null);
+            }
+            ScalarFunction::Nullif => {
+                let value = arguments.first().cloned().unwrap_or(Value::Null);
+                return match arguments.get(1).map(|other| value.compare(other)) {
+                    Some(Some(Ordering::Equal)) => Value::Null,
+                    _ => value,
+                };
+            }
+            _ => {}
+        }
+
+        if arguments.iter().any(Value::is_null) {
+            return Value::Null;
+        }
+        self.apply(arguments).unwrap_or(Value::Null)
+    }
+
+    fn apply(self, arguments: &[Value]) -> Option<Value> {
+        let text = |index: usize| match arguments.get(index)?.coerce_to(DataType::Text)? {
+            Value::Text(text) => Some(text),
+            _ => None,
+        };
+        let integer = |index: usize| match arguments.get(index)?.coerce_to(DataType::Integer)? {
+            Value::Integer(integer) => Some(integer),
+            _ => None,
+        };
+
+        Some(match self {
+            ScalarFunction::Upper => Value::Text(text(0)?.to_uppercase()),
+            ScalarFunction::Lower => Value::Text(text(0)?.to_lowercase()),
+            ScalarFunction::Length => match arguments.first()? {
+                Value::Blob(bytes) => Value::Integer(bytes.len() as i64),
+                _ => Value::Integer(text(0)?.chars().count() as i64),
+            },
+            ScalarFunction::Substr => {
+                // Positions count from 1; the part of the range outside the text is dropped
+                let chars: Vec<char> = text(0)?.chars().collect();
+                let start = integer(1)?;
+                let end = match arguments.get(2) {
+                    Some(_) => start.saturating_add(integer(2).filter(|length| *length >= 0)?),
+                    None => i64::MAX,
+                };
+                let length = chars.len() as i64;
+                let from = start.max(1).saturating_sub(1).min(length);
+                let to = end.saturating_sub(1).clamp(from, length);
+                Value::Text(chars[from as usize..to as usize].iter().collect())
+            }
+            ScalarFunction::Trim => match arguments.get(1) {
+                Some(_) => {
+                    let characters: Vec<char> = text(1)?.chars().collect();
+                    Value::Text(text(0)?.trim_matches(|c| characters.contains(&c)).to_string())
+                }
+                None => Value::Text(text(0)?.trim().to_string()),
+            },
+            ScalarFunction::Replace => {
+                let (text, from, to) = (text(0)?, text(1)?, text(2)?);
+                if from.is_empty() {
+                    Value::Text(text)
+                } else {
+                    Value::Text(text.replace(&from, &to))
+                }
+            }
+            ScalarFunction::Abs => match numeric(arguments.first()?)? {
+                Value::Integer(i) => i.checked_abs().map_or(Value::Real((i as f64).abs()), Value::Integer),
+                Value::Real(r) => Value::Real(r.abs()),
+                _ => return None,
+            },
+            ScalarFunction::Round => {
+                let digits = if arguments.len() > 1 { integer(1)? } else { 0 };
+                match numeric(arguments.first()?)? {
+                    Value::Integer(i) if digits >= 0 => Value::Integer(i),
+                    value => {
+                        let factor = 10f64.powi(digits.clamp(-308, 308) as i32);
+                        Value::Real((as_f64(&value) * factor).round() / factor)
+                    }
+                }
+            }
+            ScalarFunction::Coalesce | ScalarFunction::Nullif => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for ScalarFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            ScalarFunction::Upper => "UPPER",
+            ScalarFunction::Lower => "LOWER",
+            ScalarFunction::Length => "LENGTH",
+            ScalarFunction::Substr => "SUBSTR",
+            ScalarFunction::Trim => "TRIM",
+            ScalarFunction::Replace => "REPLACE",
+            ScalarFunction::Coalesce => "COALESCE",
+            ScalarFunction::Nullif => "NULLIF",
+            ScalarFunction::Abs => "ABS",
+            ScalarFunction::Round => "ROUND",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Convert a value for `CAST(value AS type)`. Unlike column coercion, REAL values and
+/// decimal text truncate toward zero when cast to INTEGER. Values that cannot be
+/// converted become NULL.
+pub fn cast(value: &Value, data_type: DataType) -> Value {
+    if let Some(converted) = value.coerce_to(data_type) {
+        return converted;
+    }
+
+    let truncate = |real: f64| {
+        if real.is_finite() && real.abs() < i64::MAX as f64 {
+            Value::Integer(real.trunc() as i64)
+        } else {
+            Value::Null
+        }
+    };
+    match (value, data_type) {
+        (Value::Real(real), DataType::Integer) => truncate(*real),
+        (Value::Text(text), DataType::Integer) => text.trim().parse().map_or(Value::Null, truncate),
+        (Value::Blob(bytes), DataType::Text) => {
+            String::from_utf8(bytes.clone()).map_or(Value::Null, Value::Text)
+        }
+        _ => Value::Null,
+    }
+}
+
+/// Numbers pass through; text is used when it reads as an INTEGER or REAL
+pub fn numeric(value: &Value) -> Option<Value> {
+    match value {
+        Value::Integer(_) | Value::Real(_) => Some(value.clone()),
+        Value::Text(_) => value.coerce_to(DataType::Integer).or_else(|| value.coerce_to(DataType::Real)),
+        _ => None,
+    }
+}
+
+pub fn as_f64(value: &Value) -> f64 {
+    match value {
+        Value::Integer(i) => *i as f64,
+        Value::Real(r) => *r,
+        _ => 0.0,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(text: &str) -> Value {
+        Value::Text(text.to_string())
+    }
+
+    #[test]
+    fn null_arguments_give_null_except_for_coalesce_and_nullif() {
+        assert_eq!(ScalarFunction::Upper.call(&[Value::Null]), Value::Null);
+        assert_eq!(ScalarFunction::Replace.call(&[text("a"), Value::Null, text("b")]), Value::Null);
+        assert_eq!(ScalarFunction::Coalesce.call(&[Value::Null, Value::Integer(2), Value::Integer(3)]), Value::Integer(2));
+        assert_eq!(ScalarFunction::Coalesce.call(&[Value::Null]), Value::Null);
+        assert_eq!(ScalarFunction::Nullif.call(&[Value::Integer(1), Value::Real(1.0)]), Value::Null);
+        assert_eq!(ScalarFunction::Nullif.call(&[Value::Integer(1), Value::Null]), Value::Integer(1));
+    }
+
+    #[test]
+    fn substr_counts_from_one_and_drops_what_lies_outside_the_text() {
+        let substr = |arguments: &[Value]| ScalarFunction::Substr.call(arguments);
+        assert_eq!(substr(&[text("hello"), Value::Integer(2), Value::Integer(3)]), text("ell"));
+        assert_eq!(substr(&[text("hello"), Value::Integer(4)]), text("lo"));
+        assert_eq!(substr(&[text("hello"), Value::Integer(0), Value::Integer(2)]), text("h"));
+        assert_eq!(substr(&[text("hello"), Value::Integer(9)]), text(""));
+        assert_eq!(substr(&[text("hello"), Value::Integer(1), Value::Integer(-1)]), Value::Null);
+    }
+
+    #[test]
+    fn substr_accepts_extreme_start_and_length() {
+        let substr = |arguments: &[Value]| ScalarFunction::Substr.call(arguments);
+        assert_eq!(substr(&[text("hello"), Value::Integer(i64::MIN), Value::Integer(0)]), text(""));
+        assert_eq!(substr(&[text("hello"), Value::Integer(i64::MIN), Value::Integer(i64::MAX)]), text(""));
+        assert_eq!(substr(&[text("hello"), Value::Integer(2), Value::Integer(i64::MAX)]), text("ello"));
+        assert_eq!(substr(&[text("hello"), Value::Integer(i64::MAX), Value::Integer(i64::MAX)]), text(""));
+        assert_eq!(substr(&[text("hello"), Value::Integer(i64::MIN)]), text("hello"));
+    }
+
+    #[test]
+    fn numeric_functions_keep_integers_exact() {
+        assert_eq!(ScalarFunction::Abs.call(&[Value::Integer(-3)]), Value::Integer(3));
+        assert_eq!(ScalarFunction::Abs.call(&[Value::Integer(i64::MIN)]), Value::Real(9223372036854775808.0));
+        assert_eq!(ScalarFunction::Round.call(&[Value::Integer(7)]), Value::Integer(7));
+        assert_eq!(ScalarFunction::Round.call(&[Value::Real(2.345), Value::Integer(2)]), Value::Real(2.35));
+        assert_eq!(ScalarFunction::Round.call(&[text("1250"), Value::Integer(-2)]), Value::Real(1300.0));
+        assert_eq!(ScalarFunction::Length.call(&[Value::Integer(-12)]), Value::Integer(3));
+    }
+
+    #[test]
+    fn cast_truncates_toward_zero_and_gives_null_for_what_cannot_convert() {
+        assert_eq!(cast(&Value::Real(-2.7), DataType::Integer), Value::Integer(-2));
+        assert_eq!(cast(&text(" 3.9 "), DataType::Integer), Value::Integer(3));
+        assert_eq!(cast(&Value::Real(f64::NAN), DataType::Integer), Value::Null);
+        assert_eq!(cast(&text("abc"), DataType::Integer), Value::Null);
+        assert_eq!(cast(&Value::Integer(5), DataType::Text), text("5"));
+    }
+}
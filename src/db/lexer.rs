@@ -49,11 +49,11 @@ impl<'a> Lexer<'a> {
                     Some(Token::Identifier(identifier))
                 }
             }
-            Some(c) if c.is_digit(10) => {
+            Some(c) if c.is_ascii_digit() => {
                 let mut literal = String::new();
                 literal.push(c);
                 while let Some(&next) = chars.peek() {
-                    if next.is_digit(10) {
+                    if next.is_ascii_digit() {
                         literal.push(next);
                         chars.next();
                     } else {
@@ -5,4 +5,6 @@ pub mod storage_engine;
 pub mod executor;
 pub mod query;
 pub mod parser;
-pub mod lexer;
\ No newline at end of file
+pub mod lexer;
+pub mod pattern;
+pub mod functions;
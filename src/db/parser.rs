@@ -1,14 +1,19 @@
-use super::{query::Identifier, schema::Row};
+use super::{
+    functions::{self, ScalarFunction},
+    pattern::{LikePattern, Regex},
+    query::Identifier,
+    schema::{DataType, Row, Value},
+};
+use std::cmp::Ordering;
 use nom::{
     branch::alt,
-    bytes::complete::{is_not, tag, tag_no_case, take_until},
-    character::complete::{alphanumeric1, char, multispace0, multispace1},
-    combinator::{map, opt, recognize},
-    multi::separated_list0,
-    sequence::{delimited, preceded, separated_pair, tuple},
+    bytes::complete::{tag, tag_no_case, take_while},
+    character::complete::{alphanumeric1, char, digit0, digit1, multispace0, multispace1, one_of, satisfy},
+    combinator::{consumed, map, map_opt, map_res, not, opt, peek, recognize, verify},
+    multi::{fold_many0, many0, many1, separated_list0, separated_list1},
+    sequence::{delimited, pair, preceded, separated_pair, terminated, tuple},
     IResult,
 };
-use std::collections::HashMap;
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Token {
@@ -25,228 +30,2693 @@ pub enum Token {
 
 #[derive(Debug, Clone)]
 pub enum ASTNode {
-    SelectStatement {
-        projection: Vec<Identifier>,
-        table: Identifier,
-        condition: Option<WhereCondition>,
-    },
+    SelectStatement(SelectStatement),
     DeleteStatement {
         table: Identifier,
-        condition: Option<WhereCondition>,
+        condition: Option<Expr>,
+        returning: Option<Vec<SelectItem>>,
     },
     UpdateStatement {
         table: Identifier,
-        assignments: Vec<(Identifier, String)>,
-        condition: Option<WhereCondition>,
+        assignments: Vec<(Identifier, Expr)>,
+        condition: Option<Expr>,
+        returning: Option<Vec<SelectItem>>,
     },
     InsertStatement {
         table: Identifier,
         columns: Vec<Identifier>,
-        values: Vec<String>,
+        source: InsertSource,
+        on_conflict: Option<OnConflict>,
+        returning: Option<Vec<SelectItem>>,
+    },
+    CreateTableStatement {
+        table: Identifier,
+        columns: Vec<ColumnDefinition>,
+        constraints: Vec<TableConstraint>,
+        if_not_exists: bool,
+    },
+    DropTableStatement {
+        table: Identifier,
+        if_exists: bool,
+    },
+    AlterTableStatement {
+        table: Identifier,
+        operation: AlterTableOperation,
+    },
+    CreateViewStatement {
+        view: Identifier,
+        query: Box<SelectStatement>,
+        /// The SELECT as written, which is what the database stores
+        definition: String,
+        or_replace: bool,
+    },
+    DropViewStatement {
+        view: Identifier,
+        if_exists: bool,
     },
     Identifier(String),
 }
 
-#[derive(Debug, Clone)]
-pub struct WhereCondition {
-    pub column: String,
-    pub operator: String,
-    pub value: String,
-}
+impl ASTNode {
+    /// Rebuild the statement with `replace` applied to all of its expressions,
+    /// including those of subqueries and of the query feeding INSERT ... SELECT
+    pub fn rewrite_expressions<E>(
+        &self,
+        replace: &mut dyn FnMut(&Expr) -> Result<Option<Expr>, E>,
+    ) -> Result<ASTNode, E> {
+        let rewrite_all = |exprs: &[Expr], replace: &mut dyn FnMut(&Expr) -> Result<Option<Expr>, E>| {
+            exprs.iter().map(|expr| expr.rewrite_all(replace)).collect::<Result<Vec<Expr>, E>>()
+        };
+        let rewrite_optional = |expr: &Option<Expr>, replace: &mut dyn FnMut(&Expr) -> Result<Option<Expr>, E>| {
+            expr.as_ref().map(|expr| expr.rewrite_all(replace)).transpose()
+        };
+        let rewrite_assignments = |assignments: &[(Identifier, Expr)], replace: &mut dyn FnMut(&Expr) -> Result<Option<Expr>, E>| {
+            assignments.iter()
+                .map(|(column, expr)| Ok((column.clone(), expr.rewrite_all(replace)?)))
+                .collect::<Result<Vec<_>, E>>()
+        };
+        let rewrite_returning = |returning: &Option<Vec<SelectItem>>, replace: &mut dyn FnMut(&Expr) -> Result<Option<Expr>, E>| {
+            returning.as_ref()
+                .map(|items| items.iter().map(|item| item.rewrite_all(replace)).collect::<Result<Vec<_>, E>>())
+                .transpose()
+        };
+
+        Ok(match self {
+            ASTNode::SelectStatement(select) => ASTNode::SelectStatement(select.rewrite_expressions(replace)?),
+            ASTNode::DeleteStatement { table, condition, returning } => ASTNode::DeleteStatement {
+                table: table.clone(),
+                condition: rewrite_optional(condition, replace)?,
+                returning: rewrite_returning(returning, replace)?,
+            },
+            ASTNode::UpdateStatement { table, assignments, condition, returning } => ASTNode::UpdateStatement {
+                table: table.clone(),
+                assignments: rewrite_assignments(assignments, replace)?,
+                condition: rewrite_optional(condition, replace)?,
+                returning: rewrite_returning(returning, replace)?,
+            },
+            ASTNode::InsertStatement { table, columns, source, on_conflict, returning } => ASTNode::InsertStatement {
+                table: table.clone(),
+                columns: columns.clone(),
+                source: match source {
+                    InsertSource::Values(tuples) => InsertSource::Values(
+                        tuples.iter().map(|values| rewrite_all(values, replace)).collect::<Result<_, E>>()?
+                    ),
+                    InsertSource::Select(select) => InsertSource::Select(Box::new(select.rewrite_expressions(replace)?)),
+                },
+                on_conflict: match on_conflict {
+                    Some(OnConflict { target, action: ConflictAction::Update(assignments) }) => Some(OnConflict {
+                        target: target.clone(),
+                        action: ConflictAction::Update(rewrite_assignments(assignments, replace)?),
+                    }),
+                    other => other.clone(),
+                },
+                returning: rewrite_returning(returning, replace)?,
+            },
+            ASTNode::CreateViewStatement { view, query, definition, or_replace } => ASTNode::CreateViewStatement {
+                view: view.clone(),
+                query: Box::new(query.rewrite_expressions(replace)?),
+                definition: definition.clone(),
+                or_replace: *or_replace,
+            },
+            _ => self.clone(),
+        })
+    }
+
+    /// Names of the tables and views the statement reads, including those read by
+    /// subqueries, WITH queries and the query feeding INSERT ... SELECT, in order of
+    /// appearance. References to WITH queries are not included.
+    pub fn read_tables(&self) -> Vec<String> {
+        let mut names: Vec<String> = Vec::new();
+        match self {
+            ASTNode::SelectStatement(select) => select.add_read_tables(&mut names, &[]),
+            ASTNode::InsertStatement { source: InsertSource::Select(select), .. } => select.add_read_tables(&mut names, &[]),
+            ASTNode::CreateViewStatement { query, .. } => query.add_read_tables(&mut names, &[]),
+            _ => {}
+        }
+        if !matches!(self, ASTNode::SelectStatement(_) | ASTNode::CreateViewStatement { .. }) {
+            // Subqueries of UPDATE and DELETE conditions, assignments and RETURNING lists
+            let statement = match self {
+                ASTNode::InsertStatement { table, columns, on_conflict, returning, .. } => ASTNode::InsertStatement {
+                    table: table.clone(),
+                    columns: columns.clone(),
+                    source: InsertSource::Values(Vec::new()),
+                    on_conflict: on_conflict.clone(),
+                    returning: returning.clone(),
+                },
+                statement => statement.clone(),
+            };
+            let _: Result<_, std::convert::Infallible> = statement.rewrite_expressions(&mut |node| {
+                if let Expr::Subquery(subquery) | Expr::Exists(subquery) | Expr::InSubquery { subquery, .. } = node {
+                    subquery.add_read_tables(&mut names, &[]);
+                }
+                Ok(None)
+            });
+        }
+        names
+    }
+
+    /// The number of parameters the statement takes, i.e. its highest `$n` placeholder
+    pub fn parameter_count(&self) -> usize {
+        let mut count = 0;
+        let _: Result<_, std::convert::Infallible> = self.rewrite_expressions(&mut |node| {
+            if let Expr::Parameter(index) = node {
+                count = count.max(*index);
+            }
+            Ok(None)
+        });
+        count
+    }
+
+    /// Substitute `parameters[n - 1]` for each `$n` placeholder
+    pub fn bind_parameters(&self, parameters: &[Value]) -> ASTNode {
+        let bound: Result<_, std::convert::Infallible> = self.rewrite_expressions(&mut |node| match node {
+            Expr::Parameter(index) => {
+                Ok(Some(Expr::Literal(parameters.get(index - 1).cloned().unwrap_or(Value::Null))))
+            }
+            _ => Ok(None),
+        });
+        match bound {
+            Ok(ast) => ast,
+            Err(never) => match never {},
+        }
+    }
+}
+
+/// The rows an INSERT adds: literal tuples or the result of a query
+#[derive(Debug, Clone)]
+pub enum InsertSource {
+    Values(Vec<Vec<Expr>>),
+    Select(Box<SelectStatement>),
+}
+
+/// What an INSERT does with a row whose primary key is already stored
+#[derive(Debug, Clone)]
+pub struct OnConflict {
+    /// The column named in `ON CONFLICT (column)`
+    pub target: Option<Identifier>,
+    pub action: ConflictAction,
+}
+
+#[derive(Debug, Clone)]
+pub enum ConflictAction {
+    Nothing,
+    /// `DO UPDATE SET ...`, where `excluded.column` reads the proposed row
+    Update(Vec<(Identifier, Expr)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct SelectStatement {
+    /// Named queries the statement reads like tables; only a statement's outermost
+    /// SELECT has them
+    pub with: Option<WithClause>,
+    pub distinct: bool,
+    pub projection: Vec<SelectItem>,
+    pub from: TableReference,
+    pub joins: Vec<Join>,
+    pub condition: Option<Expr>,
+    pub group_by: Vec<Expr>,
+    pub having: Option<Expr>,
+    /// SELECTs combined with this one by UNION, INTERSECT or EXCEPT, left to right with
+    /// equal precedence, as in SQLite. ORDER BY, LIMIT and OFFSET then apply to the
+    /// combined rows, and ORDER BY names output columns.
+    pub compound: Vec<SetOperation>,
+    pub order_by: Vec<OrderByItem>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+}
+
+/// `UNION [ALL] SELECT ...`, `INTERSECT [ALL] SELECT ...` or `EXCEPT [ALL] SELECT ...`
+#[derive(Debug, Clone, PartialEq)]
+pub struct SetOperation {
+    pub operator: SetOperator,
+    /// Keep duplicate rows rather than returning distinct rows
+    pub all: bool,
+    pub query: SelectStatement,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SetOperator {
+    Union,
+    Intersect,
+    Except,
+}
+
+impl std::fmt::Display for SetOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            SetOperator::Union => "UNION",
+            SetOperator::Intersect => "INTERSECT",
+            SetOperator::Except => "EXCEPT",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl std::fmt::Display for SetOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}{} {}", self.operator, if self.all { " ALL" } else { "" }, self.query)
+    }
+}
+
+/// `WITH [RECURSIVE] name AS (SELECT ...), ...` before a SELECT
+#[derive(Debug, Clone, PartialEq)]
+pub struct WithClause {
+    /// Whether a query may read its own rows through a recursive step
+    pub recursive: bool,
+    pub queries: Vec<CommonTableExpression>,
+}
+
+/// A named query of a WITH clause (e.g., `tree (id, depth) AS (SELECT ...)`)
+#[derive(Debug, Clone, PartialEq)]
+pub struct CommonTableExpression {
+    pub name: Identifier,
+    /// Names for the query's output columns; empty to keep the query's own names
+    pub columns: Vec<Identifier>,
+    pub query: SelectStatement,
+    /// The `UNION [ALL] SELECT ...` of a recursive query, which reads the rows the
+    /// previous iteration added
+    pub recursive_step: Option<RecursiveStep>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct RecursiveStep {
+    /// UNION ALL keeps every row; UNION drops rows seen before, which ends cycles
+    pub all: bool,
+    pub query: SelectStatement,
+}
+
+impl std::fmt::Display for CommonTableExpression {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", quote_identifier(&self.name.0))?;
+        if !self.columns.is_empty() {
+            let columns: Vec<String> = self.columns.iter().map(|column| quote_identifier(&column.0)).collect();
+            write!(f, " ({})", columns.join(", "))?;
+        }
+        write!(f, " AS ({}", self.query)?;
+        if let Some(ref step) = self.recursive_step {
+            write!(f, " UNION {}{}", if step.all { "ALL " } else { "" }, step.query)?;
+        }
+        write!(f, ")")
+    }
+}
+
+impl SelectStatement {
+    /// Whether the query collapses rows into groups (GROUP BY, HAVING or any aggregate call)
+    pub fn is_aggregate(&self) -> bool {
+        !self.group_by.is_empty() || self.having.is_some() || !self.aggregates().is_empty()
+    }
+
+    /// All distinct aggregate calls used by the projection, HAVING and ORDER BY. The
+    /// ORDER BY of a compound SELECT sorts the combined rows, so it is left out.
+    pub fn aggregates(&self) -> Vec<&Expr> {
+        let mut aggregates: Vec<&Expr> = Vec::new();
+        let order_by = if self.compound.is_empty() { self.order_by.as_slice() } else { &[] };
+        let expressions = self.projection.iter()
+            .filter_map(SelectItem::expr)
+            .chain(self.having.iter())
+            .chain(order_by.iter().map(|item| &item.expr));
+        for expr in expressions {
+            for aggregate in expr.aggregates() {
+                if !aggregates.contains(&aggregate) {
+                    aggregates.push(aggregate);
+                }
+            }
+        }
+        aggregates
+    }
+
+    /// The tables in FROM and JOIN, in join order
+    pub fn tables(&self) -> impl Iterator<Item = &TableReference> {
+        std::iter::once(&self.from).chain(self.joins.iter().map(|join| &join.table))
+    }
+
+    /// Names of the tables and views this statement reads, as `ASTNode::read_tables`
+    pub fn read_tables(&self) -> Vec<String> {
+        let mut names = Vec::new();
+        self.add_read_tables(&mut names, &[]);
+        names
+    }
+
+    /// Add the tables this statement, its WITH queries and its subqueries read to
+    /// `names`, skipping the WITH queries in scope (`ctes`)
+    fn add_read_tables(&self, names: &mut Vec<String>, ctes: &[&str]) {
+        let mut scope = ctes.to_vec();
+        if let Some(ref with) = self.with {
+            for cte in &with.queries {
+                // A recursive query reads its own rows; otherwise its name still means the table
+                if with.recursive {
+                    scope.push(&cte.name.0);
+                }
+                cte.query.add_read_tables(names, &scope);
+                if let Some(ref step) = cte.recursive_step {
+                    step.query.add_read_tables(names, &scope);
+                }
+                if !with.recursive {
+                    scope.push(&cte.name.0);
+                }
+            }
+        }
+
+        for table in self.tables() {
+            if !scope.contains(&table.name.0.as_str()) && !names.contains(&table.name.0) {
+                names.push(table.name.0.clone());
+            }
+        }
+        for expr in self.expressions() {
+            for subquery in expr.subqueries() {
+                subquery.add_read_tables(names, &scope);
+            }
+        }
+        for operation in &self.compound {
+            operation.query.add_read_tables(names, &scope);
+        }
+    }
+
+    /// The expressions of the statement's own clauses, not those of its WITH queries
+    /// or of the SELECTs combined with it
+    pub fn expressions(&self) -> impl Iterator<Item = &Expr> {
+        self.projection.iter()
+            .filter_map(SelectItem::expr)
+            .chain(self.joins.iter().filter_map(|join| join.condition.as_ref()))
+            .chain(self.condition.iter())
+            .chain(self.group_by.iter())
+            .chain(self.having.iter())
+            .chain(self.order_by.iter().map(|item| &item.expr))
+    }
+
+    /// Whether a column reference is satisfied by this statement's own tables;
+    /// `has_column` tells whether a table defines a column
+    fn provides(&self, name: &str, has_column: &dyn Fn(&TableReference, &str) -> bool) -> bool {
+        match name.split_once('.') {
+            Some((qualifier, _)) => self.tables().any(|table| table.qualifier() == qualifier),
+            None => self.tables().any(|table| has_column(table, name)),
+        }
+    }
+
+    /// Rewrite the column references that this statement's tables do not provide, i.e.
+    /// references to an enclosing query, including those inside nested subqueries
+    pub fn rewrite_outer_references<E>(
+        &self,
+        has_column: &dyn Fn(&TableReference, &str) -> bool,
+        replace: &mut dyn FnMut(&str) -> Result<Option<Expr>, E>,
+    ) -> Result<SelectStatement, E> {
+        let mut select = self.clone();
+        // The ORDER BY of a compound SELECT names its output columns
+        let order_by = if select.compound.is_empty() { select.order_by.as_mut_slice() } else { &mut [] };
+        let expressions = select.projection.iter_mut()
+            .filter_map(|item| match item {
+                SelectItem::Expr { expr, .. } => Some(expr),
+                SelectItem::Wildcard => None,
+            })
+            .chain(select.joins.iter_mut().filter_map(|join| join.condition.as_mut()))
+            .chain(select.condition.iter_mut())
+            .chain(select.group_by.iter_mut())
+            .chain(select.having.iter_mut())
+            .chain(order_by.iter_mut().map(|item| &mut item.expr));
+        for expr in expressions {
+            *expr = self.rewrite_outer_expr(expr, has_column, replace)?;
+        }
+        // Each combined SELECT reads its own tables
+        for operation in select.compound.iter_mut() {
+            operation.query = operation.query.rewrite_outer_references(has_column, replace)?;
+        }
+        Ok(select)
+    }
+
+    fn rewrite_outer_expr<E>(
+        &self,
+        expr: &Expr,
+        has_column: &dyn Fn(&TableReference, &str) -> bool,
+        replace: &mut dyn FnMut(&str) -> Result<Option<Expr>, E>,
+    ) -> Result<Expr, E> {
+        expr.rewrite(&mut |node| match node {
+            Expr::Column(name) if !self.provides(name, has_column) => replace(name),
+            Expr::Subquery(subquery) => {
+                Ok(Some(Expr::Subquery(self.rewrite_nested(subquery, has_column, replace)?)))
+            }
+            Expr::Exists(subquery) => {
+                Ok(Some(Expr::Exists(self.rewrite_nested(subquery, has_column, replace)?)))
+            }
+            Expr::InSubquery { operand, subquery, negated } => Ok(Some(Expr::InSubquery {
+                operand: Box::new(self.rewrite_outer_expr(operand, has_column, replace)?),
+                subquery: self.rewrite_nested(subquery, has_column, replace)?,
+                negated: *negated,
+            })),
+            _ => Ok(None),
+        })
+    }
+
+    /// Nested subqueries see this statement's columns before the outer ones
+    fn rewrite_nested<E>(
+        &self,
+        subquery: &SelectStatement,
+        has_column: &dyn Fn(&TableReference, &str) -> bool,
+        replace: &mut dyn FnMut(&str) -> Result<Option<Expr>, E>,
+    ) -> Result<Box<SelectStatement>, E> {
+        subquery
+            .rewrite_outer_references(has_column, &mut |name| {
+                if self.provides(name, has_column) { Ok(None) } else { replace(name) }
+            })
+            .map(Box::new)
+    }
+
+    /// Names of the columns this statement takes from an enclosing query
+    pub fn outer_references(&self, has_column: &dyn Fn(&TableReference, &str) -> bool) -> Vec<String> {
+        let mut references = Vec::new();
+        let _: Result<_, std::convert::Infallible> = self.rewrite_outer_references(has_column, &mut |name| {
+            references.push(name.to_string());
+            Ok(None)
+        });
+        references
+    }
+
+    /// Rebuild the statement with `replace` applied to all of its expressions,
+    /// including those of nested subqueries
+    pub fn rewrite_expressions<E>(
+        &self,
+        replace: &mut dyn FnMut(&Expr) -> Result<Option<Expr>, E>,
+    ) -> Result<SelectStatement, E> {
+        let mut select = self.clone();
+        // WITH queries come first, as written
+        for cte in select.with.iter_mut().flat_map(|with| with.queries.iter_mut()) {
+            cte.query = cte.query.rewrite_expressions(replace)?;
+            if let Some(ref mut step) = cte.recursive_step {
+                step.query = step.query.rewrite_expressions(replace)?;
+            }
+        }
+        let expressions = select.projection.iter_mut()
+            .filter_map(|item| match item {
+                SelectItem::Expr { expr, .. } => Some(expr),
+                SelectItem::Wildcard => None,
+            })
+            .chain(select.joins.iter_mut().filter_map(|join| join.condition.as_mut()))
+            .chain(select.condition.iter_mut())
+            .chain(select.group_by.iter_mut())
+            .chain(select.having.iter_mut());
+        for expr in expressions {
+            *expr = expr.rewrite_all(replace)?;
+        }
+        for operation in select.compound.iter_mut() {
+            operation.query = operation.query.rewrite_expressions(replace)?;
+        }
+        for item in select.order_by.iter_mut() {
+            item.expr = item.expr.rewrite_all(replace)?;
+        }
+        Ok(select)
+    }
+}
+
+impl std::fmt::Display for SelectStatement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let list = |items: Vec<String>| items.join(", ");
+
+        if let Some(ref with) = self.with {
+            let queries = list(with.queries.iter().map(|cte| cte.to_string()).collect());
+            write!(f, "WITH {}{} ", if with.recursive { "RECURSIVE " } else { "" }, queries)?;
+        }
+        write!(f, "SELECT {}", if self.distinct { "DISTINCT " } else { "" })?;
+        write!(f, "{} FROM {}", list(self.projection.iter().map(|item| item.to_string()).collect()), self.from)?;
+        for join in &self.joins {
+            write!(f, " {}", join)?;
+        }
+        if let Some(ref condition) = self.condition {
+            write!(f, " WHERE {}", condition)?;
+        }
+        if !self.group_by.is_empty() {
+            write!(f, " GROUP BY {}", list(self.group_by.iter().map(|expr| expr.to_string()).collect()))?;
+        }
+        if let Some(ref having) = self.having {
+            write!(f, " HAVING {}", having)?;
+        }
+        for operation in &self.compound {
+            write!(f, " {}", operation)?;
+        }
+        if !self.order_by.is_empty() {
+            write!(f, " ORDER BY {}", list(self.order_by.iter().map(|item| item.to_string()).collect()))?;
+        }
+        if let Some(limit) = self.limit {
+            write!(f, " LIMIT {}", limit)?;
+        }
+        if let Some(offset) = self.offset {
+            write!(f, " OFFSET {}", offset)?;
+        }
+        Ok(())
+    }
+}
+
+/// A table in FROM or JOIN, optionally renamed with an alias
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableReference {
+    pub name: Identifier,
+    pub alias: Option<Identifier>,
+}
+
+impl TableReference {
+    /// The name that qualifies this table's columns (e.g., `u` in `u.id`)
+    pub fn qualifier(&self) -> &str {
+        &self.alias.as_ref().unwrap_or(&self.name).0
+    }
+}
+
+impl std::fmt::Display for TableReference {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self.alias {
+            Some(ref alias) => write!(f, "{} {}", quote_identifier(&self.name.0), quote_identifier(&alias.0)),
+            None => write!(f, "{}", quote_identifier(&self.name.0)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Join {
+    pub kind: JoinKind,
+    pub table: TableReference,
+    pub condition: Option<Expr>,
+}
+
+impl std::fmt::Display for Join {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let keyword = match self.kind {
+            JoinKind::Inner => "JOIN",
+            JoinKind::Left => "LEFT JOIN",
+            JoinKind::Cross => "CROSS JOIN",
+        };
+        write!(f, "{} {}", keyword, self.table)?;
+        match self.condition {
+            Some(ref condition) => write!(f, " ON {}", condition),
+            None => Ok(()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinKind {
+    Inner,
+    Left,
+    Cross,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum SelectItem {
+    Wildcard,
+    Expr {
+        expr: Expr,
+        alias: Option<Identifier>,
+    },
+}
+
+impl SelectItem {
+    fn rewrite_all<E>(&self, replace: &mut dyn FnMut(&Expr) -> Result<Option<Expr>, E>) -> Result<SelectItem, E> {
+        Ok(match self {
+            SelectItem::Wildcard => SelectItem::Wildcard,
+            SelectItem::Expr { expr, alias } => SelectItem::Expr { expr: expr.rewrite_all(replace)?, alias: alias.clone() },
+        })
+    }
+
+    pub fn expr(&self) -> Option<&Expr> {
+        match self {
+            SelectItem::Expr { expr, .. } => Some(expr),
+            SelectItem::Wildcard => None,
+        }
+    }
+
+    /// The output column name: the alias, or else the expression as written
+    pub fn column_name(&self) -> Option<String> {
+        match self {
+            SelectItem::Expr { alias: Some(alias), .. } => Some(alias.0.clone()),
+            SelectItem::Expr { expr, alias: None } => Some(expr.output_name()),
+            SelectItem::Wildcard => None,
+        }
+    }
+
+    /// The name of the column this item gives a view: like `column_name`, but a column
+    /// read from a table drops its qualifier (`u.id` becomes `id`)
+    pub fn view_column_name(&self) -> Option<String> {
+        match self {
+            SelectItem::Expr { expr: Expr::Column(name), alias: None } => {
+                Some(name.split_once('.').map_or(name.as_str(), |(_, column)| column).to_string())
+            }
+            _ => self.column_name(),
+        }
+    }
+}
+
+impl std::fmt::Display for SelectItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SelectItem::Wildcard => write!(f, "*"),
+            SelectItem::Expr { expr, alias: Some(alias) } => write!(f, "{} AS {}", expr, quote_identifier(&alias.0)),
+            SelectItem::Expr { expr, alias: None } => write!(f, "{}", expr),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct OrderByItem {
+    pub expr: Expr,
+    pub descending: bool,
+    pub nulls_first: bool,
+}
+
+impl std::fmt::Display for OrderByItem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.expr)?;
+        if self.descending {
+            write!(f, " DESC")?;
+        }
+        // Only print NULLS placement when it differs from the direction's default
+        if self.nulls_first != self.descending {
+            write!(f, " NULLS {}", if self.nulls_first { "FIRST" } else { "LAST" })?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ColumnDefinition {
+    pub name: Identifier,
+    pub data_type: String,
+    pub constraints: Vec<ColumnConstraint>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum ColumnConstraint {
+    PrimaryKey,
+}
+
+#[derive(Debug, Clone)]
+pub enum TableConstraint {
+    PrimaryKey(Vec<Identifier>),
+}
+
+#[derive(Debug, Clone)]
+pub enum AlterTableOperation {
+    AddColumn(ColumnDefinition),
+    DropColumn(Identifier),
+    RenameColumn { column: Identifier, new_name: Identifier },
+    RenameTable(Identifier),
+}
+
+enum TableElement {
+    Column(ColumnDefinition),
+    Constraint(TableConstraint),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    Column(String),
+    Literal(Value),
+    /// A `$n` placeholder, numbered from 1, for a prepared statement parameter
+    Parameter(usize),
+    Binary {
+        left: Box<Expr>,
+        operator: BinaryOperator,
+        right: Box<Expr>,
+    },
+    Unary {
+        operator: UnaryOperator,
+        operand: Box<Expr>,
+    },
+    IsNull {
+        operand: Box<Expr>,
+        negated: bool,
+    },
+    /// Aggregate call; a missing argument means `*`
+    Aggregate {
+        function: AggregateFunction,
+        argument: Option<Box<Expr>>,
+        distinct: bool,
+    },
+    /// Scalar subquery, e.g. `(SELECT MAX(age) FROM users)`
+    Subquery(Box<SelectStatement>),
+    Exists(Box<SelectStatement>),
+    InSubquery {
+        operand: Box<Expr>,
+        subquery: Box<SelectStatement>,
+        negated: bool,
+    },
+    /// `expr [NOT] LIKE pattern [ESCAPE 'c']`; ILIKE ignores case
+    Like {
+        operand: Box<Expr>,
+        pattern: Box<Expr>,
+        escape: Option<char>,
+        case_insensitive: bool,
+        negated: bool,
+    },
+    /// `expr [NOT] REGEXP pattern`, true when the pattern matches anywhere in the text
+    Regexp {
+        operand: Box<Expr>,
+        pattern: Box<Expr>,
+        negated: bool,
+    },
+    /// `expr [NOT] IN (v1, v2, ...)`; uncorrelated IN subqueries are materialized into this form
+    InList {
+        operand: Box<Expr>,
+        list: Vec<Expr>,
+        negated: bool,
+    },
+    /// `expr [NOT] BETWEEN low AND high`, inclusive at both ends
+    Between {
+        operand: Box<Expr>,
+        low: Box<Expr>,
+        high: Box<Expr>,
+        negated: bool,
+    },
+    /// Scalar function call, e.g. `UPPER(name)`
+    Function {
+        function: ScalarFunction,
+        arguments: Vec<Expr>,
+    },
+    /// `CAST(expr AS type)`
+    Cast {
+        operand: Box<Expr>,
+        data_type: DataType,
+    },
+    /// `CASE [operand] WHEN value THEN result ... [ELSE result] END`. Without an operand
+    /// each WHEN is a condition; with one, each WHEN value is compared with it.
+    Case {
+        operand: Option<Box<Expr>>,
+        branches: Vec<(Expr, Expr)>,
+        else_result: Option<Box<Expr>>,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AggregateFunction {
+    Count,
+    Sum,
+    Avg,
+    Min,
+    Max,
+}
+
+impl std::fmt::Display for AggregateFunction {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            AggregateFunction::Count => "COUNT",
+            AggregateFunction::Sum => "SUM",
+            AggregateFunction::Avg => "AVG",
+            AggregateFunction::Min => "MIN",
+            AggregateFunction::Max => "MAX",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOperator {
+    Equal,
+    NotEqual,
+    LessThan,
+    LessThanOrEqual,
+    GreaterThan,
+    GreaterThanOrEqual,
+    And,
+    Or,
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Concat,
+}
+
+impl BinaryOperator {
+    /// Binding strength used when printing expressions
+    fn precedence(self) -> u8 {
+        match self {
+            BinaryOperator::Or => 1,
+            BinaryOperator::And => 2,
+            BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Concat => 4,
+            BinaryOperator::Multiply | BinaryOperator::Divide | BinaryOperator::Modulo => 5,
+            _ => 3,
+        }
+    }
+
+    /// Whether the operator compares its operands (`=`, `<`, ...)
+    pub fn is_comparison(self) -> bool {
+        self.precedence() == 3
+    }
+}
+
+impl std::fmt::Display for BinaryOperator {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let symbol = match self {
+            BinaryOperator::Equal => "=",
+            BinaryOperator::NotEqual => "<>",
+            BinaryOperator::LessThan => "<",
+            BinaryOperator::LessThanOrEqual => "<=",
+            BinaryOperator::GreaterThan => ">",
+            BinaryOperator::GreaterThanOrEqual => ">=",
+            BinaryOperator::And => "AND",
+            BinaryOperator::Or => "OR",
+            BinaryOperator::Add => "+",
+            BinaryOperator::Subtract => "-",
+            BinaryOperator::Multiply => "*",
+            BinaryOperator::Divide => "/",
+            BinaryOperator::Modulo => "%",
+            BinaryOperator::Concat => "||",
+        };
+        write!(f, "{}", symbol)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOperator {
+    Not,
+    Minus,
+}
+
+impl Expr {
+    /// The name of an output column holding this expression: a column keeps its own
+    /// unquoted name, anything else is named as written
+    pub fn output_name(&self) -> String {
+        match self {
+            Expr::Column(name) => name.clone(),
+            expr => expr.to_string(),
+        }
+    }
+
+    /// Evaluate the expression as a predicate against a row.
+    /// Only TRUE satisfies a predicate; FALSE and unknown (NULL) do not.
+    pub fn evaluate(&self, row: &Row) -> bool {
+        self.eval(row).as_bool() == Some(true)
+    }
+
+    /// Evaluate the expression to a value using SQL three-valued logic
+    pub fn eval(&self, row: &Row) -> Value {
+        match self {
+            Expr::Column(name) => row.data.get(name).cloned().unwrap_or(Value::Null),
+            Expr::Literal(value) => value.clone(),
+            // Parameters are bound before a statement executes
+            Expr::Parameter(_) => Value::Null,
+            Expr::IsNull { operand, negated } => {
+                Value::Boolean(operand.eval(row).is_null() != *negated)
+            }
+            Expr::Unary { operator: UnaryOperator::Not, operand } => {
+                match operand.eval(row).as_bool() {
+                    Some(value) => Value::Boolean(!value),
+                    None => Value::Null,
+                }
+            }
+            Expr::Unary { operator: UnaryOperator::Minus, operand } => {
+                match functions::numeric(&operand.eval(row)) {
+                    Some(Value::Integer(i)) => i.checked_neg().map_or(Value::Real(-(i as f64)), Value::Integer),
+                    Some(Value::Real(r)) => Value::Real(-r),
+                    _ => Value::Null,
+                }
+            }
+            Expr::Binary { left, operator: BinaryOperator::And, right } => {
+                match (left.eval(row).as_bool(), right.eval(row).as_bool()) {
+                    (Some(false), _) | (_, Some(false)) => Value::Boolean(false),
+                    (Some(true), Some(true)) => Value::Boolean(true),
+                    _ => Value::Null,
+                }
+            }
+            Expr::Binary { left, operator: BinaryOperator::Or, right } => {
+                match (left.eval(row).as_bool(), right.eval(row).as_bool()) {
+                    (Some(true), _) | (_, Some(true)) => Value::Boolean(true),
+                    (Some(false), Some(false)) => Value::Boolean(false),
+                    _ => Value::Null,
+                }
+            }
+            Expr::Binary { left, operator, right } if operator.is_comparison() => {
+                Expr::compare(&left.eval(row), *operator, &right.eval(row))
+            }
+            Expr::Binary { left, operator, right } => {
+                Expr::arithmetic(left.eval(row), *operator, right.eval(row))
+            }
+            Expr::Function { function, arguments } => {
+                let arguments: Vec<Value> = arguments.iter().map(|argument| argument.eval(row)).collect();
+                function.call(&arguments)
+            }
+            Expr::Cast { operand, data_type } => functions::cast(&operand.eval(row), *data_type),
+            // The first matching WHEN gives the result; a NULL operand matches none
+            Expr::Case { operand, branches, else_result } => {
+                let operand = operand.as_ref().map(|operand| operand.eval(row));
+                let matched = branches.iter().find(|(when, _)| match operand {
+                    Some(ref value) => Expr::compare(value, BinaryOperator::Equal, &when.eval(row)).as_bool() == Some(true),
+                    None => when.evaluate(row),
+                });
+                match (matched, else_result) {
+                    (Some((_, result)), _) => result.eval(row),
+                    (None, Some(else_result)) => else_result.eval(row),
+                    (None, None) => Value::Null,
+                }
+            }
+            Expr::Like { operand, pattern, escape, case_insensitive, negated } => {
+                let (Some(text), Some(pattern)) = (Expr::text(operand.eval(row)), Expr::text(pattern.eval(row))) else {
+                    return Value::Null;
+                };
+                match LikePattern::cached(&pattern, *escape, *case_insensitive) {
+                    Some(pattern) => Value::Boolean(pattern.is_match(&text) != *negated),
+                    None => Value::Null,
+                }
+            }
+            Expr::Regexp { operand, pattern, negated } => {
+                let (Some(text), Some(pattern)) = (Expr::text(operand.eval(row)), Expr::text(pattern.eval(row))) else {
+                    return Value::Null;
+                };
+                match Regex::cached(&pattern) {
+                    Some(regex) => Value::Boolean(regex.is_match(&text) != *negated),
+                    None => Value::Null,
+                }
+            }
+            Expr::InList { operand, list, negated } => {
+                let value = operand.eval(row);
+                if value.is_null() {
+                    return Value::Null;
+                }
+
+                // No match against a list containing NULL is unknown rather than FALSE
+                let mut result = Value::Boolean(false);
+                for item in list {
+                    match value.compare(&item.eval(row)) {
+                        Some(Ordering::Equal) => {
+                            result = Value::Boolean(true);
+                            break;
+                        }
+                        None => result = Value::Null,
+                        Some(_) => {}
+                    }
+                }
+                match result {
+                    Value::Boolean(found) => Value::Boolean(found != *negated),
+                    _ => Value::Null,
+                }
+            }
+            Expr::Between { operand, low, high, negated } => {
+                let value = operand.eval(row);
+                let above = Expr::compare(&value, BinaryOperator::GreaterThanOrEqual, &low.eval(row));
+                let below = Expr::compare(&value, BinaryOperator::LessThanOrEqual, &high.eval(row));
+                match (above.as_bool(), below.as_bool()) {
+                    (Some(false), _) | (_, Some(false)) => Value::Boolean(*negated),
+                    (Some(true), Some(true)) => Value::Boolean(!*negated),
+                    _ => Value::Null,
+                }
+            }
+            // Aggregates are computed by the aggregation operator, never per row
+            Expr::Aggregate { .. } => Value::Null,
+            // Subqueries are replaced with their results by the executor before evaluation
+            Expr::Subquery(_) | Expr::Exists(_) | Expr::InSubquery { .. } => Value::Null,
+        }
+    }
+
+    /// The text a pattern predicate matches against; numbers and booleans match their
+    /// printed form, while NULL and blobs have none
+    fn text(value: Value) -> Option<String> {
+        match value.coerce_to(DataType::Text)? {
+            Value::Text(text) => Some(text),
+            _ => None,
+        }
+    }
+
+    fn compare(left: &Value, operator: BinaryOperator, right: &Value) -> Value {
+        let Some(ordering) = left.compare(right) else {
+            // Comparisons with NULL or incompatible operands are unknown
+            return Value::Null;
+        };
+
+        Value::Boolean(match operator {
+            BinaryOperator::Equal => ordering == Ordering::Equal,
+            BinaryOperator::NotEqual => ordering != Ordering::Equal,
+            BinaryOperator::LessThan => ordering == Ordering::Less,
+            BinaryOperator::LessThanOrEqual => ordering != Ordering::Greater,
+            BinaryOperator::GreaterThan => ordering == Ordering::Greater,
+            BinaryOperator::GreaterThanOrEqual => ordering != Ordering::Less,
+            _ => return Value::Null,
+        })
+    }
+
+    /// Apply an arithmetic operator or `||`. Text operands take part in arithmetic when
+    /// they read as numbers; integer overflow falls back to REAL and division by zero is NULL.
+    fn arithmetic(left: Value, operator: BinaryOperator, right: Value) -> Value {
+        if left.is_null() || right.is_null() {
+            return Value::Null;
+        }
+        if operator == BinaryOperator::Concat {
+            return match (Expr::text(left), Expr::text(right)) {
+                (Some(left), Some(right)) => Value::Text(left + &right),
+                _ => Value::Null,
+            };
+        }
+
+        let (Some(left), Some(right)) = (functions::numeric(&left), functions::numeric(&right)) else {
+            return Value::Null;
+        };
+        if let (Value::Integer(a), Value::Integer(b)) = (&left, &right) {
+            let result = match operator {
+                BinaryOperator::Divide | BinaryOperator::Modulo if *b == 0 => return Value::Null,
+                BinaryOperator::Add => a.checked_add(*b),
+                BinaryOperator::Subtract => a.checked_sub(*b),
+                BinaryOperator::Multiply => a.checked_mul(*b),
+                BinaryOperator::Divide => a.checked_div(*b),
+                BinaryOperator::Modulo => a.checked_rem(*b),
+                _ => return Value::Null,
+            };
+            if let Some(result) = result {
+                return Value::Integer(result);
+            }
+        }
+
+        let (a, b) = (functions::as_f64(&left), functions::as_f64(&right));
+        let result = match operator {
+            BinaryOperator::Divide | BinaryOperator::Modulo if b == 0.0 => return Value::Null,
+            BinaryOperator::Add => a + b,
+            BinaryOperator::Subtract => a - b,
+            BinaryOperator::Multiply => a * b,
+            BinaryOperator::Divide => a / b,
+            BinaryOperator::Modulo => a % b,
+            _ => return Value::Null,
+        };
+        if result.is_finite() { Value::Real(result) } else { Value::Null }
+    }
+
+    /// Collect the names of all columns referenced by the expression
+    pub fn referenced_columns(&self) -> Vec<&str> {
+        let mut columns = Vec::new();
+        self.collect_columns(&mut columns);
+        columns
+    }
+
+    fn collect_columns<'a>(&'a self, columns: &mut Vec<&'a str>) {
+        match self {
+            Expr::Column(name) => columns.push(name),
+            Expr::Literal(_) | Expr::Parameter(_) => {}
+            Expr::Binary { left, right, .. } => {
+                left.collect_columns(columns);
+                right.collect_columns(columns);
+            }
+            Expr::Unary { operand, .. } | Expr::IsNull { operand, .. } => {
+                operand.collect_columns(columns)
+            }
+            Expr::Aggregate { argument, .. } => {
+                if let Some(argument) = argument {
+                    argument.collect_columns(columns);
+                }
+            }
+            Expr::InList { operand, list, .. } => {
+                operand.collect_columns(columns);
+                for item in list {
+                    item.collect_columns(columns);
+                }
+            }
+            Expr::Between { operand, low, high, .. } => {
+                operand.collect_columns(columns);
+                low.collect_columns(columns);
+                high.collect_columns(columns);
+            }
+            Expr::Function { arguments, .. } => {
+                for argument in arguments {
+                    argument.collect_columns(columns);
+                }
+            }
+            Expr::Cast { operand, .. } => operand.collect_columns(columns),
+            Expr::Case { .. } => {
+                for child in self.case_children() {
+                    child.collect_columns(columns);
+                }
+            }
+            Expr::Like { operand, pattern, .. } | Expr::Regexp { operand, pattern, .. } => {
+                operand.collect_columns(columns);
+                pattern.collect_columns(columns);
+            }
+            // Columns inside a subquery belong to its own scope
+            Expr::InSubquery { operand, .. } => operand.collect_columns(columns),
+            Expr::Subquery(_) | Expr::Exists(_) => {}
+        }
+    }
+
+    /// Collect every aggregate call in the expression
+    pub fn aggregates(&self) -> Vec<&Expr> {
+        let mut aggregates = Vec::new();
+        self.collect_aggregates(&mut aggregates);
+        aggregates
+    }
+
+    fn collect_aggregates<'a>(&'a self, aggregates: &mut Vec<&'a Expr>) {
+        match self {
+            Expr::Aggregate { .. } => aggregates.push(self),
+            Expr::Column(_) | Expr::Literal(_) | Expr::Parameter(_) | Expr::Subquery(_) | Expr::Exists(_) => {}
+            Expr::Binary { left, right, .. } => {
+                left.collect_aggregates(aggregates);
+                right.collect_aggregates(aggregates);
+            }
+            Expr::Unary { operand, .. } | Expr::IsNull { operand, .. } | Expr::InSubquery { operand, .. } => {
+                operand.collect_aggregates(aggregates)
+            }
+            Expr::Like { operand, pattern, .. } | Expr::Regexp { operand, pattern, .. } => {
+                operand.collect_aggregates(aggregates);
+                pattern.collect_aggregates(aggregates);
+            }
+            Expr::InList { operand, list, .. } => {
+                operand.collect_aggregates(aggregates);
+                for item in list {
+                    item.collect_aggregates(aggregates);
+                }
+            }
+            Expr::Between { operand, low, high, .. } => {
+                operand.collect_aggregates(aggregates);
+                low.collect_aggregates(aggregates);
+                high.collect_aggregates(aggregates);
+            }
+            Expr::Function { arguments, .. } => {
+                for argument in arguments {
+                    argument.collect_aggregates(aggregates);
+                }
+            }
+            Expr::Cast { operand, .. } => operand.collect_aggregates(aggregates),
+            Expr::Case { .. } => {
+                for child in self.case_children() {
+                    child.collect_aggregates(aggregates);
+                }
+            }
+        }
+    }
+
+    /// Collect the subqueries of the expression, not counting those nested inside them
+    pub fn subqueries(&self) -> Vec<&SelectStatement> {
+        let mut subqueries = Vec::new();
+        self.collect_subqueries(&mut subqueries);
+        subqueries
+    }
+
+    fn collect_subqueries<'a>(&'a self, subqueries: &mut Vec<&'a SelectStatement>) {
+        match self {
+            Expr::Subquery(subquery) | Expr::Exists(subquery) => subqueries.push(subquery),
+            Expr::InSubquery { operand, subquery, .. } => {
+                operand.collect_subqueries(subqueries);
+                subqueries.push(subquery);
+            }
+            Expr::Column(_) | Expr::Literal(_) | Expr::Parameter(_) => {}
+            Expr::Binary { left, right, .. } => {
+                left.collect_subqueries(subqueries);
+                right.collect_subqueries(subqueries);
+            }
+            Expr::Unary { operand, .. } | Expr::IsNull { operand, .. } => operand.collect_subqueries(subqueries),
+            Expr::Aggregate { argument, .. } => {
+                if let Some(argument) = argument {
+                    argument.collect_subqueries(subqueries);
+                }
+            }
+            Expr::Like { operand, pattern, .. } | Expr::Regexp { operand, pattern, .. } => {
+                operand.collect_subqueries(subqueries);
+                pattern.collect_subqueries(subqueries);
+            }
+            Expr::InList { operand, list, .. } => {
+                operand.collect_subqueries(subqueries);
+                for item in list {
+                    item.collect_subqueries(subqueries);
+                }
+            }
+            Expr::Between { operand, low, high, .. } => {
+                operand.collect_subqueries(subqueries);
+                low.collect_subqueries(subqueries);
+                high.collect_subqueries(subqueries);
+            }
+            Expr::Function { arguments, .. } => {
+                for argument in arguments {
+                    argument.collect_subqueries(subqueries);
+                }
+            }
+            Expr::Cast { operand, .. } => operand.collect_subqueries(subqueries),
+            Expr::Case { .. } => {
+                for child in self.case_children() {
+                    child.collect_subqueries(subqueries);
+                }
+            }
+        }
+    }
+
+    /// The operand, WHEN values, results and ELSE result of a CASE, as written
+    pub fn case_children(&self) -> Vec<&Expr> {
+        let Expr::Case { operand, branches, else_result } = self else {
+            return Vec::new();
+        };
+        operand.iter()
+            .map(Box::as_ref)
+            .chain(branches.iter().flat_map(|(when, result)| [when, result]))
+            .chain(else_result.iter().map(Box::as_ref))
+            .collect()
+    }
+
+    /// Whether the expression contains a subquery that the executor must run
+    pub fn has_subqueries(&self) -> bool {
+        !self.subqueries().is_empty()
+    }
+
+    /// Rebuild the expression, letting `replace` substitute any node. Nodes it
+    /// leaves alone (`None`) are copied with their children rewritten.
+    pub fn rewrite<E>(&self, replace: &mut impl FnMut(&Expr) -> Result<Option<Expr>, E>) -> Result<Expr, E> {
+        if let Some(replacement) = replace(self)? {
+            return Ok(replacement);
+        }
+
+        Ok(match self {
+            Expr::Column(_) | Expr::Literal(_) | Expr::Parameter(_) => self.clone(),
+            Expr::Binary { left, operator, right } => Expr::Binary {
+                left: Box::new(left.rewrite(replace)?),
+                operator: *operator,
+                right: Box::new(right.rewrite(replace)?),
+            },
+            Expr::Unary { operator, operand } => Expr::Unary {
+                operator: *operator,
+                operand: Box::new(operand.rewrite(replace)?),
+            },
+            Expr::IsNull { operand, negated } => Expr::IsNull {
+                operand: Box::new(operand.rewrite(replace)?),
+                negated: *negated,
+            },
+            Expr::Aggregate { function, argument, distinct } => Expr::Aggregate {
+                function: *function,
+                argument: match argument {
+                    Some(argument) => Some(Box::new(argument.rewrite(replace)?)),
+                    None => None,
+                },
+                distinct: *distinct,
+            },
+            // A subquery's statement is its own scope; only the IN operand belongs here
+            Expr::Subquery(_) | Expr::Exists(_) => self.clone(),
+            Expr::InSubquery { operand, subquery, negated } => Expr::InSubquery {
+                operand: Box::new(operand.rewrite(replace)?),
+                subquery: subquery.clone(),
+                negated: *negated,
+            },
+            Expr::Like { operand, pattern, escape, case_insensitive, negated } => Expr::Like {
+                operand: Box::new(operand.rewrite(replace)?),
+                pattern: Box::new(pattern.rewrite(replace)?),
+                escape: *escape,
+                case_insensitive: *case_insensitive,
+                negated: *negated,
+            },
+            Expr::Regexp { operand, pattern, negated } => Expr::Regexp {
+                operand: Box::new(operand.rewrite(replace)?),
+                pattern: Box::new(pattern.rewrite(replace)?),
+                negated: *negated,
+            },
+            Expr::InList { operand, list, negated } => Expr::InList {
+                operand: Box::new(operand.rewrite(replace)?),
+                list: list.iter().map(|item| item.rewrite(replace)).collect::<Result<_, E>>()?,
+                negated: *negated,
+            },
+            Expr::Between { operand, low, high, negated } => Expr::Between {
+                operand: Box::new(operand.rewrite(replace)?),
+                low: Box::new(low.rewrite(replace)?),
+                high: Box::new(high.rewrite(replace)?),
+                negated: *negated,
+            },
+            Expr::Function { function, arguments } => Expr::Function {
+                function: *function,
+                arguments: arguments.iter().map(|argument| argument.rewrite(replace)).collect::<Result<_, E>>()?,
+            },
+            Expr::Cast { operand, data_type } => Expr::Cast {
+                operand: Box::new(operand.rewrite(replace)?),
+                data_type: *data_type,
+            },
+            Expr::Case { operand, branches, else_result } => Expr::Case {
+                operand: match operand {
+                    Some(operand) => Some(Box::new(operand.rewrite(replace)?)),
+                    None => None,
+                },
+                branches: branches.iter()
+                    .map(|(when, result)| Ok((when.rewrite(replace)?, result.rewrite(replace)?)))
+                    .collect::<Result<_, E>>()?,
+                else_result: match else_result {
+                    Some(else_result) => Some(Box::new(else_result.rewrite(replace)?)),
+                    None => None,
+                },
+            },
+        })
+    }
+
+    /// Like `rewrite`, but also descends into the statements of subqueries
+    pub fn rewrite_all<E>(&self, replace: &mut dyn FnMut(&Expr) -> Result<Option<Expr>, E>) -> Result<Expr, E> {
+        self.rewrite(&mut |node| {
+            if let Some(replacement) = replace(node)? {
+                return Ok(Some(replacement));
+            }
+            Ok(match node {
+                Expr::Subquery(subquery) => Some(Expr::Subquery(Box::new(subquery.rewrite_expressions(replace)?))),
+                Expr::Exists(subquery) => Some(Expr::Exists(Box::new(subquery.rewrite_expressions(replace)?))),
+                Expr::InSubquery { operand, subquery, negated } => Some(Expr::InSubquery {
+                    operand: Box::new(operand.rewrite_all(replace)?),
+                    subquery: Box::new(subquery.rewrite_expressions(replace)?),
+                    negated: *negated,
+                }),
+                _ => None,
+            })
+        })
+    }
+
+    /// Split a predicate into its AND-ed parts
+    pub fn conjuncts(&self) -> Vec<&Expr> {
+        match self {
+            Expr::Binary { left, operator: BinaryOperator::And, right } => {
+                let mut conjuncts = left.conjuncts();
+                conjuncts.extend(right.conjuncts());
+                conjuncts
+            }
+            _ => vec![self],
+        }
+    }
+
+    /// AND the given predicates together; `None` when there are none
+    pub fn conjunction(conjuncts: Vec<Expr>) -> Option<Expr> {
+        conjuncts.into_iter().reduce(|left, right| Expr::Binary {
+            left: Box::new(left),
+            operator: BinaryOperator::And,
+            right: Box::new(right),
+        })
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Binary { operator, .. } => operator.precedence(),
+            Expr::Unary { operator: UnaryOperator::Not, .. } => 2,
+            Expr::InSubquery { .. } | Expr::InList { .. } | Expr::Like { .. } | Expr::Regexp { .. }
+            | Expr::Between { .. } => 3,
+            Expr::Unary { operator: UnaryOperator::Minus, .. } => 6,
+            _ => 7,
+        }
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Parenthesize operands that bind more loosely than their parent
+        let operand = |f: &mut std::fmt::Formatter<'_>, expr: &Expr, precedence: u8| {
+            if expr.precedence() < precedence {
+                write!(f, "({})", expr)
+            } else {
+                write!(f, "{}", expr)
+            }
+        };
+
+        match self {
+            Expr::Column(name) => match name.split_once('.') {
+                Some((qualifier, column)) => write!(f, "{}.{}", quote_identifier(qualifier), quote_identifier(column)),
+                None => write!(f, "{}", quote_identifier(name)),
+            },
+            Expr::Literal(Value::Text(text)) => write!(f, "'{}'", text.replace('\'', "''")),
+            Expr::Literal(value) => write!(f, "{}", value),
+            Expr::Parameter(index) => write!(f, "${}", index),
+            Expr::Binary { left, operator, right } => {
+                operand(f, left, operator.precedence())?;
+                write!(f, " {} ", operator)?;
+                operand(f, right, operator.precedence() + 1)
+            }
+            Expr::Unary { operator: UnaryOperator::Not, operand: inner } => {
+                write!(f, "NOT ")?;
+                operand(f, inner, 3)
+            }
+            // Keep a negative operand from printing as a `--` comment
+            Expr::Unary { operator: UnaryOperator::Minus, operand: inner } if inner.to_string().starts_with('-') => {
+                write!(f, "-({})", inner)
+            }
+            Expr::Unary { operator: UnaryOperator::Minus, operand: inner } => {
+                write!(f, "-")?;
+                operand(f, inner, 6)
+            }
+            Expr::IsNull { operand: inner, negated } => {
+                operand(f, inner, 4)?;
+                write!(f, " IS {}NULL", if *negated { "NOT " } else { "" })
+            }
+            Expr::Aggregate { function, argument: Some(argument), distinct } => {
+                write!(f, "{}({}{})", function, if *distinct { "DISTINCT " } else { "" }, argument)
+            }
+            Expr::Aggregate { function, argument: None, .. } => write!(f, "{}(*)", function),
+            Expr::Subquery(subquery) => write!(f, "({})", subquery),
+            Expr::Exists(subquery) => write!(f, "EXISTS ({})", subquery),
+            Expr::InSubquery { operand: inner, subquery, negated } => {
+                operand(f, inner, 4)?;
+                write!(f, " {}IN ({})", if *negated { "NOT " } else { "" }, subquery)
+            }
+            Expr::Like { operand: inner, pattern, escape, case_insensitive, negated } => {
+                operand(f, inner, 4)?;
+                write!(f, " {}{} ", if *negated { "NOT " } else { "" }, if *case_insensitive { "ILIKE" } else { "LIKE" })?;
+                operand(f, pattern, 4)?;
+                match escape {
+                    Some(escape) => write!(f, " ESCAPE {}", Expr::Literal(Value::Text(escape.to_string()))),
+                    None => Ok(()),
+                }
+            }
+            Expr::Regexp { operand: inner, pattern, negated } => {
+                operand(f, inner, 4)?;
+                write!(f, " {}REGEXP ", if *negated { "NOT " } else { "" })?;
+                operand(f, pattern, 4)
+            }
+            Expr::InList { operand: inner, list, negated } => {
+                operand(f, inner, 4)?;
+                let items: Vec<String> = list.iter().map(|item| item.to_string()).collect();
+                write!(f, " {}IN ({})", if *negated { "NOT " } else { "" }, items.join(", "))
+            }
+            Expr::Between { operand: inner, low, high, negated } => {
+                operand(f, inner, 4)?;
+                write!(f, " {}BETWEEN ", if *negated { "NOT " } else { "" })?;
+                operand(f, low, 4)?;
+                write!(f, " AND ")?;
+                operand(f, high, 4)
+            }
+            Expr::Function { function, arguments } => {
+                let arguments: Vec<String> = arguments.iter().map(|argument| argument.to_string()).collect();
+                write!(f, "{}({})", function, arguments.join(", "))
+            }
+            Expr::Cast { operand, data_type } => write!(f, "CAST({} AS {})", operand, data_type),
+            Expr::Case { operand, branches, else_result } => {
+                write!(f, "CASE")?;
+                if let Some(operand) = operand {
+                    write!(f, " {}", operand)?;
+                }
+                for (when, result) in branches {
+                    write!(f, " WHEN {} THEN {}", when, result)?;
+                }
+                if let Some(else_result) = else_result {
+                    write!(f, " ELSE {}", else_result)?;
+                }
+                write!(f, " END")
+            }
+        }
+    }
+}
+
+/// Quote a name that would not read back as a plain identifier (e.g., `"first name"`)
+fn quote_identifier(name: &str) -> String {
+    let mut chars = name.chars();
+    let plain = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(Parser::is_identifier_char);
+    if plain {
+        name.to_string()
+    } else {
+        format!("\"{}\"", name.replace('"', "\"\""))
+    }
+}
+
+/// Words that end a table reference, so they are never taken as an alias
+const RESERVED_WORDS: &[&str] = &[
+    "WHERE", "GROUP", "HAVING", "ORDER", "LIMIT", "OFFSET", "FETCH", "JOIN", "INNER", "LEFT",
+    "OUTER", "CROSS", "ON", "AS", "RETURNING", "UNION",
+    "INTERSECT", "EXCEPT",
+];
+
+pub struct Parser;
+
+impl Parser {
+    /// Parses a name (e.g., `created_at`, `$total`) or a double-quoted name that may
+    /// hold spaces or keywords (e.g., `"first name"`, `"order"`). Quoted names cannot
+    /// contain `.`, which separates a qualifier from a column.
+    fn identifier(input: &str) -> IResult<&str, Identifier> {
+        map(
+            alt((
+                verify(Parser::quoted('"'), |name: &str| !name.is_empty() && !name.contains('.')),
+                map(Parser::bare_word, str::to_string),
+            )),
+            Identifier,
+        )(input)
+    }
+
+    /// Parses an unquoted name: a letter or `_`, then letters, digits, `_` or `$`
+    fn bare_word(input: &str) -> IResult<&str, &str> {
+        recognize(pair(
+            satisfy(|c: char| c.is_ascii_alphabetic() || c == '_'),
+            take_while(Parser::is_identifier_char),
+        ))(input)
+    }
+
+    fn is_identifier_char(c: char) -> bool {
+        c.is_ascii_alphanumeric() || c == '_' || c == '$'
+    }
+
+    /// Parses a possibly qualified column name (e.g., `id`, `u.id`, `u."first name"`)
+    fn column_reference(input: &str) -> IResult<&str, String> {
+        map(
+            pair(Parser::identifier, opt(preceded(char('.'), Parser::identifier))),
+            |(first, second)| match second {
+                Some(column) => format!("{}.{}", first.0, column.0),
+                None => first.0,
+            },
+        )(input)
+    }
+
+    /// Parses a table name with an optional alias (e.g., `users`, `users AS u`, `users u`)
+    fn table_reference(input: &str) -> IResult<&str, TableReference> {
+        let (input, name) = Parser::identifier(input)?;
+        let (input, alias) = opt(preceded(
+            pair(multispace1, opt(pair(Parser::keyword("AS"), multispace1))),
+            verify(Parser::identifier, |alias: &Identifier| {
+                !RESERVED_WORDS.iter().any(|word| word.eq_ignore_ascii_case(&alias.0))
+            }),
+        ))(input)?;
+
+        Ok((input, TableReference { name, alias }))
+    }
+
+    /// Parses `[INNER] JOIN t ON cond`, `LEFT [OUTER] JOIN t ON cond` or `CROSS JOIN t`
+    fn join_clause(input: &str) -> IResult<&str, Join> {
+        let join_keyword = |input| pair(Parser::keyword("JOIN"), multispace1)(input);
+        let on_condition = |input| preceded(
+            tuple((multispace1, Parser::keyword("ON"), multispace1)),
+            Parser::expression,
+        )(input);
+
+        alt((
+            map(
+                preceded(
+                    tuple((Parser::keyword("CROSS"), multispace1, join_keyword)),
+                    Parser::table_reference,
+                ),
+                |table| Join { kind: JoinKind::Cross, table, condition: None },
+            ),
+            map(
+                pair(
+                    preceded(
+                        tuple((
+                            Parser::keyword("LEFT"),
+                            multispace1,
+                            opt(pair(Parser::keyword("OUTER"), multispace1)),
+                            join_keyword,
+                        )),
+                        Parser::table_reference,
+                    ),
+                    on_condition,
+                ),
+                |(table, condition)| Join { kind: JoinKind::Left, table, condition: Some(condition) },
+            ),
+            map(
+                pair(
+                    preceded(
+                        pair(opt(pair(Parser::keyword("INNER"), multispace1)), join_keyword),
+                        Parser::table_reference,
+                    ),
+                    on_condition,
+                ),
+                |(table, condition)| Join { kind: JoinKind::Inner, table, condition: Some(condition) },
+            ),
+        ))(input)
+    }
+
+    /// Parses text between `quote` characters, where a doubled quote stands for one
+    /// (e.g., `'O''Brien'`)
+    fn quoted(quote: char) -> impl Fn(&str) -> IResult<&str, String> {
+        move |input| {
+            let (mut rest, _) = char(quote)(input)?;
+            let mut text = String::new();
+            loop {
+                let end = rest.find(quote).ok_or_else(|| {
+                    nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Char))
+                })?;
+                text.push_str(&rest[..end]);
+                rest = &rest[end + 1..];
+                match rest.strip_prefix(quote) {
+                    Some(after) => {
+                        text.push(quote);
+                        rest = after;
+                    }
+                    None => return Ok((rest, text)),
+                }
+            }
+        }
+    }
+
+    fn quoted_string(input: &str) -> IResult<&str, String> {
+        Parser::quoted('\'')(input)
+    }
+
+    /// Parses a hex blob literal (e.g., `X'CAFE'`)
+    fn blob(input: &str) -> IResult<&str, Value> {
+        map_opt(preceded(tag_no_case("X"), Parser::quoted_string), |hex| {
+            if hex.len() % 2 != 0 || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return None;
+            }
+            let bytes = (0..hex.len()).step_by(2)
+                .map(|i| u8::from_str_radix(&hex[i..i + 2], 16).ok())
+                .collect::<Option<Vec<u8>>>()?;
+            Some(Value::Blob(bytes))
+        })(input)
+    }
+
+    /// Parses the literals shared by expressions and value lists: strings, blobs,
+    /// numbers, `TRUE`, `FALSE` and `NULL`
+    fn literal(input: &str) -> IResult<&str, Value> {
+        alt((
+            map(Parser::quoted_string, Value::Text),
+            Parser::blob,
+            Parser::number,
+            map(Parser::keyword("NULL"), |_| Value::Null),
+            map(Parser::keyword("TRUE"), |_| Value::Boolean(true)),
+            map(Parser::keyword("FALSE"), |_| Value::Boolean(false)),
+        ))(input)
+    }
+
+    /// Parses a literal, an optionally signed number, or a bare word as text
+    fn value(input: &str) -> IResult<&str, Value> {
+        alt((
+            Parser::literal,
+            map(pair(one_of("+-"), consumed(Parser::number)), |(sign, number)| match sign {
+                '-' => Parser::negative(number),
+                _ => number.1,
+            }),
+            map(Parser::bare_word, Parser::bare_value),
+        ))(input)
+    }
+
+    /// Parses a `$n` placeholder, or a `?` that `Parser::parse` numbers afterwards
+    fn parameter(input: &str) -> IResult<&str, Expr> {
+        alt((
+            map(char('?'), |_| Expr::Parameter(0)),
+            map(
+                preceded(char('$'), verify(map_res(digit1, str::parse::<usize>), |index| *index > 0)),
+                Expr::Parameter,
+            ),
+        ))(input)
+    }
+
+    /// Parses an unsigned number (e.g., `42`, `2.5`, `.5`, `1e6`, `6.02E+23`); those
+    /// with a fractional part or exponent are REAL
+    fn number(input: &str) -> IResult<&str, Value> {
+        let exponent = |input| recognize(tuple((one_of("eE"), opt(one_of("+-")), digit1)))(input);
+        let (rest, text) = recognize(pair(
+            alt((recognize(pair(digit1, opt(pair(char('.'), digit0)))), recognize(pair(char('.'), digit1)))),
+            opt(exponent),
+        ))(input)?;
+        // `1abc` is neither a number nor a name
+        let (rest, _) = not(peek(satisfy(Parser::is_identifier_char)))(rest)?;
+
+        if text.bytes().all(|b| b.is_ascii_digit()) {
+            return Ok((rest, Parser::bare_value(text)));
+        }
+        match text.parse() {
+            Ok(real) => Ok((rest, Value::Real(real))),
+            Err(_) => Err(nom::Err::Error(nom::error::Error::new(input, nom::error::ErrorKind::Float))),
+        }
+    }
+
+    /// Negate a number literal from its text and value. `-9223372036854775808` is the
+    /// smallest INTEGER, although its digits alone are too large for one.
+    fn negative((text, number): (&str, Value)) -> Value {
+        match number {
+            Value::Integer(i) => Value::Integer(-i),
+            Value::Real(r) if text.bytes().all(|b| b.is_ascii_digit()) => {
+                format!("-{}", text).parse().map_or(Value::Real(-r), Value::Integer)
+            }
+            Value::Real(r) => Value::Real(-r),
+            number => number,
+        }
+    }
+
+    /// Unquoted values are numbers when they parse as one and text otherwise
+    fn bare_value(word: &str) -> Value {
+        if let Ok(integer) = word.parse::<i64>() {
+            Value::Integer(integer)
+        } else if word.bytes().all(|b| b.is_ascii_digit()) {
+            // Too large for a 64-bit integer
+            Value::Real(word.parse().unwrap_or(f64::INFINITY))
+        } else {
+            Value::Text(word.to_string())
+        }
+    }
+
+    /// Parses a list of projections (e.g., `col1, COUNT(DISTINCT col2), age + 1 AS next`),
+    /// any of which may be `*`
+    fn projection_list(input: &str) -> IResult<&str, Vec<SelectItem>> {
+        separated_list1(
+            delimited(multispace0, tag(","), multispace0),
+            alt((
+                map(tag("*"), |_| SelectItem::Wildcard),
+                map(
+                    pair(
+                        Parser::expression,
+                        opt(preceded(tuple((multispace1, Parser::keyword("AS"), multispace1)), Parser::identifier)),
+                    ),
+                    |(expr, alias)| SelectItem::Expr { expr, alias },
+                ),
+            )),
+        )(input)
+    }
+
+    /// Parses an optional `RETURNING *` or `RETURNING expr [AS alias], ...` after a write
+    fn returning_clause(input: &str) -> IResult<&str, Option<Vec<SelectItem>>> {
+        opt(preceded(
+            tuple((multispace1, Parser::keyword("RETURNING"), multispace1)),
+            Parser::projection_list,
+        ))(input)
+    }
+
+    fn select_statement(input: &str) -> IResult<&str, ASTNode> {
+        map(Parser::query, ASTNode::SelectStatement)(input)
+    }
+
+    /// Parses a SELECT that may start with a WITH clause; subqueries cannot
+    fn query(input: &str) -> IResult<&str, SelectStatement> {
+        let (input, with) = opt(terminated(Parser::with_clause, multispace1))(input)?;
+        let (input, select) = Parser::select(input)?;
+        Ok((input, SelectStatement { with, ..select }))
+    }
+
+    /// Parses `WITH [RECURSIVE] name [(column, ...)] AS (SELECT ...), ...`
+    fn with_clause(input: &str) -> IResult<&str, WithClause> {
+        let (input, _) = pair(Parser::keyword("WITH"), multispace1)(input)?;
+        let (input, recursive) = opt(terminated(Parser::keyword("RECURSIVE"), multispace1))(input)?;
+        let (input, queries) = separated_list1(
+            delimited(multispace0, char(','), multispace0),
+            |input| Parser::common_table_expression(input, recursive.is_some()),
+        )(input)?;
+
+        Ok((input, WithClause { recursive: recursive.is_some(), queries }))
+    }
+
+    /// Parses one named query of a WITH clause. In WITH RECURSIVE, a last `UNION [ALL]
+    /// SELECT ...` that reads the query itself is its recursive step.
+    fn common_table_expression(input: &str, recursive: bool) -> IResult<&str, CommonTableExpression> {
+        let (input, name) = Parser::identifier(input)?;
+        let (input, columns) = opt(preceded(
+            multispace0,
+            delimited(
+                pair(char('('), multispace0),
+                separated_list1(delimited(multispace0, char(','), multispace0), Parser::identifier),
+                pair(multispace0, char(')')),
+            ),
+        ))(input)?;
+        let (input, _) = tuple((multispace1, Parser::keyword("AS"), multispace0, char('('), multispace0))(input)?;
+        let (input, mut query) = Parser::select(input)?;
+        let (input, _) = pair(multispace0, char(')'))(input)?;
+
+        let recursive_step = match query.compound.last() {
+            Some(step) if recursive
+                && step.operator == SetOperator::Union
+                && step.query.read_tables().contains(&name.0) =>
+            {
+                query.compound.pop().map(|step| RecursiveStep { all: step.all, query: step.query })
+            }
+            _ => None,
+        };
+
+        Ok((input, CommonTableExpression {
+            name,
+            columns: columns.unwrap_or_default(),
+            query,
+            recursive_step,
+        }))
+    }
+
+    /// Parses a SELECT query, possibly combined with others by UNION, INTERSECT or
+    /// EXCEPT; subqueries nest through `Parser::expression`
+    fn select(input: &str) -> IResult<&str, SelectStatement> {
+        let (input, select) = Parser::select_core(input)?;
+        let (input, compound) = many0(preceded(multispace1, Parser::set_operation))(input)?;
+        let (input, order_by) = opt(preceded(
+            tuple((
+                multispace1,
+                Parser::keyword("ORDER"),
+                multispace1,
+                Parser::keyword("BY"),
+                multispace1,
+            )),
+            separated_list1(
+                delimited(multispace0, char(','), multispace0),
+                Parser::order_by_item,
+            ),
+        ))(input)?;
+
+        let (input, (limit, offset)) = Parser::limit_clause(input)?;
+
+        // ORDER BY may name an output column by its position (e.g., `ORDER BY 2`) or its
+        // alias. A compound SELECT sorts its combined rows, which only have the output
+        // columns, so its positions are left for the executor to resolve, as are those
+        // at or after a `*`, whose columns depend on the tables.
+        let order_by = order_by.unwrap_or_default().into_iter()
+            .map(|item| {
+                let selected = match &item.expr {
+                    _ if !compound.is_empty() => None,
+                    Expr::Literal(Value::Integer(position)) => usize::try_from(*position).ok()
+                        .and_then(|position| select.projection.get(..position))
+                        .filter(|selected| !selected.contains(&SelectItem::Wildcard))
+                        .and_then(|selected| selected.last()),
+                    Expr::Column(name) => select.projection.iter().find(|output| {
+                        matches!(output, SelectItem::Expr { alias: Some(alias), .. } if alias.0 == *name)
+                    }),
+                    _ => None,
+                };
+                match selected {
+                    Some(SelectItem::Expr { expr, .. }) => OrderByItem { expr: expr.clone(), ..item },
+                    _ => item,
+                }
+            })
+            .collect();
+
+        Ok((input, SelectStatement { compound, order_by, limit, offset, ..select }))
+    }
+
+    /// Parses `UNION [ALL | DISTINCT] SELECT ...`, or the same with INTERSECT or EXCEPT
+    fn set_operation(input: &str) -> IResult<&str, SetOperation> {
+        let (input, operator) = alt((
+            map(Parser::keyword("UNION"), |_| SetOperator::Union),
+            map(Parser::keyword("INTERSECT"), |_| SetOperator::Intersect),
+            map(Parser::keyword("EXCEPT"), |_| SetOperator::Except),
+        ))(input)?;
+        let (input, all) = opt(preceded(
+            multispace1,
+            alt((map(Parser::keyword("ALL"), |_| true), map(Parser::keyword("DISTINCT"), |_| false))),
+        ))(input)?;
+        let (input, query) = preceded(multispace1, Parser::select_core)(input)?;
+
+        Ok((input, SetOperation { operator, all: all.unwrap_or(false), query }))
+    }
+
+    /// Parses one SELECT up to its HAVING clause
+    fn select_core(input: &str) -> IResult<&str, SelectStatement> {
+        let (input, _) = Parser::keyword("SELECT")(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, distinct) = opt(terminated(Parser::keyword("DISTINCT"), multispace1))(input)?;
+        let (input, projection) = Parser::projection_list(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, _) = tag_no_case("FROM")(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, from) = Parser::table_reference(input)?;
+        let (input, joins) = many0(preceded(multispace1, Parser::join_clause))(input)?;
+        let (input, condition) = opt(preceded(
+            tuple((multispace1, tag_no_case("WHERE"), multispace1)),
+            Parser::expression,
+        ))(input)?;
+        let (input, group_by) = opt(preceded(
+            tuple((
+                multispace1,
+                Parser::keyword("GROUP"),
+                multispace1,
+                Parser::keyword("BY"),
+                multispace1,
+            )),
+            separated_list1(
+                delimited(multispace0, char(','), multispace0),
+                Parser::expression,
+            ),
+        ))(input)?;
+        let (input, having) = opt(preceded(
+            tuple((multispace1, Parser::keyword("HAVING"), multispace1)),
+            Parser::expression,
+        ))(input)?;
+
+        Ok((input, SelectStatement {
+            with: None,
+            distinct: distinct.is_some(),
+            projection,
+            from,
+            joins,
+            condition,
+            group_by: group_by.unwrap_or_default(),
+            having,
+            compound: Vec::new(),
+            order_by: Vec::new(),
+            limit: None,
+            offset: None,
+        }))
+    }
+
+    /// Parses `LIMIT n [OFFSET m]` or the standard `[OFFSET m ROWS] [FETCH FIRST n ROWS ONLY]`
+    fn limit_clause(input: &str) -> IResult<&str, (Option<usize>, Option<usize>)> {
+        let count = |input| map_res(digit1, |s: &str| s.parse::<usize>())(input);
+        let rows_keyword = |input| alt((Parser::keyword("ROWS"), Parser::keyword("ROW")))(input);
+
+        let limit_offset = map(
+            pair(
+                preceded(tuple((multispace1, Parser::keyword("LIMIT"), multispace1)), count),
+                opt(preceded(tuple((multispace1, Parser::keyword("OFFSET"), multispace1)), count)),
+            ),
+            |(limit, offset)| (Some(limit), offset),
+        );
+
+        let offset_fetch = map(
+            pair(
+                opt(delimited(
+                    tuple((multispace1, Parser::keyword("OFFSET"), multispace1)),
+                    count,
+                    opt(pair(multispace1, rows_keyword)),
+                )),
+                opt(delimited(
+                    tuple((
+                        multispace1,
+                        Parser::keyword("FETCH"),
+                        multispace1,
+                        alt((Parser::keyword("FIRST"), Parser::keyword("NEXT"))),
+                        multispace1,
+                    )),
+                    count,
+                    tuple((multispace1, rows_keyword, multispace1, Parser::keyword("ONLY"))),
+                )),
+            ),
+            |(offset, limit)| (limit, offset),
+        );
+
+        alt((limit_offset, offset_fetch))(input)
+    }
+
+    /// Parses a sort key (e.g., `age DESC NULLS LAST`)
+    fn order_by_item(input: &str) -> IResult<&str, OrderByItem> {
+        let (input, expr) = Parser::expression(input)?;
+        let (input, direction) = opt(preceded(
+            multispace1,
+            alt((
+                map(Parser::keyword("ASC"), |_| false),
+                map(Parser::keyword("DESC"), |_| true),
+            )),
+        ))(input)?;
+        let (input, nulls) = opt(preceded(
+            tuple((multispace1, Parser::keyword("NULLS"), multispace1)),
+            alt((
+                map(Parser::keyword("FIRST"), |_| true),
+                map(Parser::keyword("LAST"), |_| false),
+            )),
+        ))(input)?;
+
+        // NULLs sort as larger than any value unless told otherwise
+        let descending = direction.unwrap_or(false);
+        Ok((input, OrderByItem {
+            expr,
+            descending,
+            nulls_first: nulls.unwrap_or(descending),
+        }))
+    }
+
+    fn delete_statement(input: &str) -> IResult<&str, ASTNode> {
+        let (input, _) = tag_no_case("DELETE")(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, _) = tag_no_case("FROM")(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, table) = Parser::identifier(input)?;
+        let (input, condition) = opt(preceded(
+            tuple((multispace1, tag_no_case("WHERE"), multispace1)),
+            Parser::expression,
+        ))(input)?;
+        let (input, returning) = Parser::returning_clause(input)?;
+
+        Ok((input, ASTNode::DeleteStatement { table, condition, returning }))
+    }
+
+    fn update_statement(input: &str) -> IResult<&str, ASTNode> {
+        let (input, _) = tag_no_case("UPDATE")(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, table) = Parser::identifier(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, _) = tag_no_case("SET")(input)?;
+        let (input, _) = multispace1(input)?;
+
+        let (input, assignments) = Parser::assignments(input)?;
+        let (input, condition) = opt(preceded(
+            tuple((multispace1, tag_no_case("WHERE"), multispace1)),
+            Parser::expression,
+        ))(input)?;
+        let (input, returning) = Parser::returning_clause(input)?;
+
+        Ok((input, ASTNode::UpdateStatement {
+            table,
+            assignments,
+            condition,
+            returning,
+        }))
+    }
+
+    /// Parses `column = expr, ...`; each expression sees the row's old values
+    fn assignments(input: &str) -> IResult<&str, Vec<(Identifier, Expr)>> {
+        separated_list1(
+            delimited(multispace0, tag(","), multispace0),
+            separated_pair(
+                Parser::identifier,
+                delimited(multispace0, tag("="), multispace0),
+                Parser::expression,
+            ),
+        )(input)
+    }
+
+    /// Parses `ON CONFLICT [(column)] DO NOTHING` or `ON CONFLICT [(column)] DO UPDATE SET ...`
+    fn on_conflict_clause(input: &str) -> IResult<&str, OnConflict> {
+        let (input, _) = tuple((Parser::keyword("ON"), multispace1, Parser::keyword("CONFLICT")))(input)?;
+        let (input, target) = opt(preceded(
+            multispace0,
+            delimited(
+                pair(char('('), multispace0),
+                Parser::identifier,
+                pair(multispace0, char(')')),
+            ),
+        ))(input)?;
+        let (input, _) = tuple((multispace1, Parser::keyword("DO"), multispace1))(input)?;
+        let (input, action) = alt((
+            map(Parser::keyword("NOTHING"), |_| ConflictAction::Nothing),
+            map(
+                preceded(
+                    tuple((Parser::keyword("UPDATE"), multispace1, Parser::keyword("SET"), multispace1)),
+                    Parser::assignments,
+                ),
+                ConflictAction::Update,
+            ),
+        ))(input)?;
+
+        Ok((input, OnConflict { target, action }))
+    }
+
+    fn insert_statement(input: &str) -> IResult<&str, ASTNode> {
+        let (input, _) = tag_no_case("INSERT")(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, _) = tag_no_case("INTO")(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, table) = Parser::identifier(input)?;
+
+        // Parse optional column list
+        let (input, columns) = opt(delimited(
+            preceded(multispace0, char('(')),
+            separated_list0(
+                delimited(multispace0, char(','), multispace0),
+                Parser::identifier,
+            ),
+            preceded(multispace0, char(')')),
+        ))(input)?;
+
+        let (input, _) = multispace1(input)?;
+
+        // Parse one or more value tuples, or a query
+        let tuple_values = delimited(
+            char('('),
+            separated_list0(
+                delimited(multispace0, char(','), multispace0),
+                alt((Parser::parameter, map(Parser::value, Expr::Literal))),
+            ),
+            char(')'),
+        );
+        let (input, source) = alt((
+            map(
+                preceded(
+                    pair(tag_no_case("VALUES"), multispace0),
+                    separated_list1(delimited(multispace0, char(','), multispace0), tuple_values),
+                ),
+                InsertSource::Values,
+            ),
+            map(Parser::query, |select| InsertSource::Select(Box::new(select))),
+        ))(input)?;
+        let (input, on_conflict) = opt(preceded(multispace1, Parser::on_conflict_clause))(input)?;
+        let (input, returning) = Parser::returning_clause(input)?;
+
+        let columns = columns.unwrap_or_else(Vec::new);
+
+        Ok((input, ASTNode::InsertStatement {
+            table,
+            columns,
+            source,
+            on_conflict,
+            returning,
+        }))
+    }
+
+    fn create_table_statement(input: &str) -> IResult<&str, ASTNode> {
+        let (input, _) = tag_no_case("CREATE")(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, _) = tag_no_case("TABLE")(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, if_not_exists) = opt(tuple((
+            tag_no_case("IF"),
+            multispace1,
+            tag_no_case("NOT"),
+            multispace1,
+            tag_no_case("EXISTS"),
+            multispace1,
+        )))(input)?;
+        let (input, table) = Parser::identifier(input)?;
+        let (input, _) = multispace0(input)?;
+
+        // Parse column definitions and table constraints in declaration order
+        let (input, elements) = delimited(
+            char('('),
+            delimited(
+                multispace0,
+                separated_list1(
+                    delimited(multispace0, char(','), multispace0),
+                    alt((
+                        map(Parser::table_constraint, TableElement::Constraint),
+                        map(Parser::column_definition, TableElement::Column),
+                    )),
+                ),
+                multispace0,
+            ),
+            char(')'),
+        )(input)?;
 
-impl WhereCondition {
-    pub fn evaluate(&self, row: &Row) -> bool {
-        if let Some(row_value) = row.data.get(&self.column) {
-            match self.operator.as_str() {
-                "=" => row_value == &self.value,
-                ">" => {
-                    let row_num: i32 = row_value.parse().unwrap_or(0);
-                    let condition_num: i32 = self.value.parse().unwrap_or(0);
-                    row_num > condition_num
-                }
-                "<" => {
-                    let row_num: i32 = row_value.parse().unwrap_or(0);
-                    let condition_num: i32 = self.value.parse().unwrap_or(0);
-                    row_num < condition_num
-                }
-                ">=" => {
-                    let row_num: i32 = row_value.parse().unwrap_or(0);
-                    let condition_num: i32 = self.value.parse().unwrap_or(0);
-                    row_num >= condition_num
-                }
-                "<=" => {
-                    let row_num: i32 = row_value.parse().unwrap_or(0);
-                    let condition_num: i32 = self.value.parse().unwrap_or(0);
-                    row_num <= condition_num
-                }
-                "!=" | "<>" => row_value != &self.value,
-                _ => false,
+        let mut columns = Vec::new();
+        let mut constraints = Vec::new();
+        for element in elements {
+            match element {
+                TableElement::Column(column) => columns.push(column),
+                TableElement::Constraint(constraint) => constraints.push(constraint),
             }
-        } else {
-            false
         }
+
+        Ok((input, ASTNode::CreateTableStatement {
+            table,
+            columns,
+            constraints,
+            if_not_exists: if_not_exists.is_some(),
+        }))
     }
-}
 
-pub struct Parser {
-    tokens: Vec<Token>,
-    current: usize,
-}
+    fn column_definition(input: &str) -> IResult<&str, ColumnDefinition> {
+        let (input, name) = Parser::identifier(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, data_type) = Parser::data_type(input)?;
+        let (input, constraints) = many0(preceded(
+            multispace1,
+            map(
+                tuple((tag_no_case("PRIMARY"), multispace1, tag_no_case("KEY"))),
+                |_| ColumnConstraint::PrimaryKey,
+            ),
+        ))(input)?;
 
-impl Parser {
-    pub fn new(tokens: Vec<Token>) -> Self {
-        Parser { tokens, current: 0 }
+        Ok((input, ColumnDefinition { name, data_type, constraints }))
     }
 
-    fn identifier(input: &str) -> IResult<&str, Identifier> {
-        map(alphanumeric1, |s: &str| Identifier(s.to_string()))(input)
+    /// Parses a type name with optional size arguments (e.g., `INTEGER`, `VARCHAR(255)`)
+    fn data_type(input: &str) -> IResult<&str, String> {
+        let (input, name) = alphanumeric1(input)?;
+        let (input, arguments) = opt(delimited(
+            tuple((multispace0, char('('), multispace0)),
+            separated_list1(delimited(multispace0, char(','), multispace0), digit1),
+            tuple((multispace0, char(')'))),
+        ))(input)?;
+
+        let mut data_type = name.to_uppercase();
+        if let Some(arguments) = arguments {
+            data_type.push_str(&format!("({})", arguments.join(",")));
+        }
+        Ok((input, data_type))
     }
 
-    fn quoted_string(input: &str) -> IResult<&str, &str> {
-        delimited(char('\''), take_until("'"), char('\''))(input)
+    fn table_constraint(input: &str) -> IResult<&str, TableConstraint> {
+        let (input, _) = tag_no_case("PRIMARY")(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, _) = tag_no_case("KEY")(input)?;
+        let (input, _) = multispace0(input)?;
+        let (input, columns) = delimited(
+            char('('),
+            delimited(
+                multispace0,
+                separated_list1(
+                    delimited(multispace0, char(','), multispace0),
+                    Parser::identifier,
+                ),
+                multispace0,
+            ),
+            char(')'),
+        )(input)?;
+
+        Ok((input, TableConstraint::PrimaryKey(columns)))
     }
 
-    fn value(input: &str) -> IResult<&str, String> {
-        alt((
-            map(Self::quoted_string, |s| s.to_string()),
-            map(alphanumeric1, |s: &str| s.to_string()),
-        ))(input)
+    fn drop_table_statement(input: &str) -> IResult<&str, ASTNode> {
+        let (input, _) = tag_no_case("DROP")(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, _) = tag_no_case("TABLE")(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, if_exists) = opt(tuple((
+            tag_no_case("IF"),
+            multispace1,
+            tag_no_case("EXISTS"),
+            multispace1,
+        )))(input)?;
+        let (input, table) = Parser::identifier(input)?;
+
+        Ok((input, ASTNode::DropTableStatement {
+            table,
+            if_exists: if_exists.is_some(),
+        }))
     }
 
-    /// Parses a list of projections (e.g., `col1, col2`)
-    fn projection_list(input: &str) -> IResult<&str, Vec<Identifier>> {
-        separated_list0(
-            delimited(multispace0, tag(","), multispace0),
-            Parser::identifier,
-        )(input)
+    /// Parses `CREATE [OR REPLACE] VIEW name AS SELECT ...`
+    fn create_view_statement(input: &str) -> IResult<&str, ASTNode> {
+        let (input, _) = pair(Parser::keyword("CREATE"), multispace1)(input)?;
+        let (input, or_replace) = opt(tuple((
+            Parser::keyword("OR"),
+            multispace1,
+            Parser::keyword("REPLACE"),
+            multispace1,
+        )))(input)?;
+        let (input, _) = pair(Parser::keyword("VIEW"), multispace1)(input)?;
+        let (input, view) = Parser::identifier(input)?;
+        let (input, _) = tuple((multispace1, Parser::keyword("AS"), multispace1))(input)?;
+        let (input, (definition, query)) = consumed(Parser::query)(input)?;
+
+        Ok((input, ASTNode::CreateViewStatement {
+            view,
+            query: Box::new(query),
+            definition: definition.to_string(),
+            or_replace: or_replace.is_some(),
+        }))
     }
 
-    fn select_statement(input: &str) -> IResult<&str, ASTNode> {
-        let (input, _) = tag_no_case("SELECT")(input)?;
-        let (input, _) = multispace1(input)?;
-        let (input, projection) = alt((
-            map(tag("*"), |_| vec![Identifier("*".to_string())]),
-            Parser::projection_list,
-        ))(input)?;
+    /// Parses `DROP VIEW [IF EXISTS] name`
+    fn drop_view_statement(input: &str) -> IResult<&str, ASTNode> {
+        let (input, _) = tuple((Parser::keyword("DROP"), multispace1, Parser::keyword("VIEW"), multispace1))(input)?;
+        let (input, if_exists) = opt(tuple((
+            Parser::keyword("IF"),
+            multispace1,
+            Parser::keyword("EXISTS"),
+            multispace1,
+        )))(input)?;
+        let (input, view) = Parser::identifier(input)?;
+
+        Ok((input, ASTNode::DropViewStatement {
+            view,
+            if_exists: if_exists.is_some(),
+        }))
+    }
+
+    fn alter_table_statement(input: &str) -> IResult<&str, ASTNode> {
+        let (input, _) = tag_no_case("ALTER")(input)?;
         let (input, _) = multispace1(input)?;
-        let (input, _) = tag_no_case("FROM")(input)?;
+        let (input, _) = tag_no_case("TABLE")(input)?;
         let (input, _) = multispace1(input)?;
         let (input, table) = Parser::identifier(input)?;
-        let (input, condition) = opt(preceded(
-            tuple((multispace1, tag_no_case("WHERE"), multispace1)),
-            Parser::parse_where_condition,
+        let (input, _) = multispace1(input)?;
+        let (input, operation) = alt((
+            Parser::add_column_operation,
+            Parser::drop_column_operation,
+            Parser::rename_table_operation,
+            Parser::rename_column_operation,
         ))(input)?;
 
-        Ok((input, ASTNode::SelectStatement { projection, table, condition }))
+        Ok((input, ASTNode::AlterTableStatement { table, operation }))
     }
 
-    fn delete_statement(input: &str) -> IResult<&str, ASTNode> {
-        let (input, _) = tag_no_case("DELETE")(input)?;
+    fn add_column_operation(input: &str) -> IResult<&str, AlterTableOperation> {
+        let (input, _) = tag_no_case("ADD")(input)?;
         let (input, _) = multispace1(input)?;
-        let (input, _) = tag_no_case("FROM")(input)?;
+        let (input, _) = opt(tuple((tag_no_case("COLUMN"), multispace1)))(input)?;
+        let (input, column) = Parser::column_definition(input)?;
+
+        Ok((input, AlterTableOperation::AddColumn(column)))
+    }
+
+    fn drop_column_operation(input: &str) -> IResult<&str, AlterTableOperation> {
+        let (input, _) = tag_no_case("DROP")(input)?;
         let (input, _) = multispace1(input)?;
-        let (input, table) = Parser::identifier(input)?;
-        let (input, condition) = opt(preceded(
-            tuple((multispace1, tag_no_case("WHERE"), multispace1)),
-            Parser::parse_where_condition,
-        ))(input)?;
+        let (input, _) = opt(tuple((tag_no_case("COLUMN"), multispace1)))(input)?;
+        let (input, column) = Parser::identifier(input)?;
 
-        Ok((input, ASTNode::DeleteStatement { table, condition }))
+        Ok((input, AlterTableOperation::DropColumn(column)))
     }
 
-    fn update_statement(input: &str) -> IResult<&str, ASTNode> {
-        let (input, _) = tag_no_case("UPDATE")(input)?;
+    fn rename_table_operation(input: &str) -> IResult<&str, AlterTableOperation> {
+        let (input, _) = tag_no_case("RENAME")(input)?;
         let (input, _) = multispace1(input)?;
-        let (input, table) = Parser::identifier(input)?;
+        let (input, _) = tag_no_case("TO")(input)?;
         let (input, _) = multispace1(input)?;
-        let (input, _) = tag_no_case("SET")(input)?;
+        let (input, new_name) = Parser::identifier(input)?;
+
+        Ok((input, AlterTableOperation::RenameTable(new_name)))
+    }
+
+    fn rename_column_operation(input: &str) -> IResult<&str, AlterTableOperation> {
+        let (input, _) = tag_no_case("RENAME")(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, _) = opt(tuple((tag_no_case("COLUMN"), multispace1)))(input)?;
+        let (input, column) = Parser::identifier(input)?;
         let (input, _) = multispace1(input)?;
+        let (input, _) = tag_no_case("TO")(input)?;
+        let (input, _) = multispace1(input)?;
+        let (input, new_name) = Parser::identifier(input)?;
 
-        // Parse column-value assignments
-        let (input, assignments) = separated_list0(
-            delimited(multispace0, tag(","), multispace0),
-            separated_pair(
-                Parser::identifier,
-                delimited(multispace0, tag("="), multispace0),
-                Parser::value,
+        Ok((input, AlterTableOperation::RenameColumn { column, new_name }))
+    }
+
+    /// Matches a keyword that is not immediately followed by another identifier character
+    fn keyword<'a>(word: &'static str) -> impl FnMut(&'a str) -> IResult<&'a str, &'a str> {
+        terminated(
+            tag_no_case(word),
+            not(peek(satisfy(|c: char| c.is_alphanumeric() || c == '_' || c == '$'))),
+        )
+    }
+
+    /// Parses a boolean expression (e.g., `age > 25 AND (name = 'x' OR NOT id = 3)`)
+    fn expression(input: &str) -> IResult<&str, Expr> {
+        Parser::or_expression(input)
+    }
+
+    fn or_expression(input: &str) -> IResult<&str, Expr> {
+        let (input, first) = Parser::and_expression(input)?;
+        fold_many0(
+            preceded(
+                delimited(multispace0, Parser::keyword("OR"), multispace0),
+                Parser::and_expression,
             ),
-        )(input)?;
-        let (input, condition) = opt(preceded(
-            tuple((multispace1, tag_no_case("WHERE"), multispace1)),
-            Parser::parse_where_condition,
-        ))(input)?;
+            move || first.clone(),
+            |left, right| Expr::Binary {
+                left: Box::new(left),
+                operator: BinaryOperator::Or,
+                right: Box::new(right),
+            },
+        )(input)
+    }
 
-        let assignments = assignments
-            .into_iter()
-            .map(|(col, val)| (col, val))
-            .collect();
+    fn and_expression(input: &str) -> IResult<&str, Expr> {
+        let (input, first) = Parser::not_expression(input)?;
+        fold_many0(
+            preceded(
+                delimited(multispace0, Parser::keyword("AND"), multispace0),
+                Parser::not_expression,
+            ),
+            move || first.clone(),
+            |left, right| Expr::Binary {
+                left: Box::new(left),
+                operator: BinaryOperator::And,
+                right: Box::new(right),
+            },
+        )(input)
+    }
 
-        Ok((input, ASTNode::UpdateStatement {
-            table,
-            assignments,
-            condition,
-        }))
+    fn not_expression(input: &str) -> IResult<&str, Expr> {
+        alt((
+            map(
+                preceded(pair(Parser::keyword("NOT"), multispace0), Parser::not_expression),
+                |operand| Expr::Unary {
+                    operator: UnaryOperator::Not,
+                    operand: Box::new(operand),
+                },
+            ),
+            Parser::comparison,
+        ))(input)
     }
 
-    fn insert_statement(input: &str) -> IResult<&str, ASTNode> {
-        let (input, _) = tag_no_case("INSERT")(input)?;
-        let (input, _) = multispace1(input)?;
-        let (input, _) = tag_no_case("INTO")(input)?;
-        let (input, _) = multispace1(input)?;
-        let (input, table) = Parser::identifier(input)?;
+    fn comparison(input: &str) -> IResult<&str, Expr> {
+        let (input, left) = Parser::additive(input)?;
 
-        // Parse optional column list
-        let (input, columns) = opt(delimited(
-            preceded(multispace0, char('(')),
-            separated_list0(
-                delimited(multispace0, char(','), multispace0),
-                Parser::identifier,
+        // `expr IS [NOT] NULL`
+        let (input, is_null) = opt(tuple((
+            multispace1,
+            Parser::keyword("IS"),
+            multispace1,
+            opt(pair(Parser::keyword("NOT"), multispace1)),
+            Parser::keyword("NULL"),
+        )))(input)?;
+        if let Some((_, _, _, not, _)) = is_null {
+            return Ok((input, Expr::IsNull {
+                operand: Box::new(left),
+                negated: not.is_some(),
+            }));
+        }
+
+        // `expr [NOT] IN (SELECT ...)`
+        let (input, in_subquery) = opt(tuple((
+            multispace1,
+            opt(pair(Parser::keyword("NOT"), multispace1)),
+            Parser::keyword("IN"),
+            multispace0,
+            Parser::subquery,
+        )))(input)?;
+        if let Some((_, not, _, _, subquery)) = in_subquery {
+            return Ok((input, Expr::InSubquery {
+                operand: Box::new(left),
+                subquery: Box::new(subquery),
+                negated: not.is_some(),
+            }));
+        }
+
+        // `expr [NOT] IN (v1, v2, ...)`
+        let (input, in_list) = opt(tuple((
+            multispace1,
+            opt(pair(Parser::keyword("NOT"), multispace1)),
+            Parser::keyword("IN"),
+            multispace0,
+            delimited(
+                pair(char('('), multispace0),
+                separated_list1(delimited(multispace0, char(','), multispace0), Parser::expression),
+                pair(multispace0, char(')')),
             ),
-            preceded(multispace0, char(')')),
+        )))(input)?;
+        if let Some((_, not, _, _, list)) = in_list {
+            return Ok((input, Expr::InList {
+                operand: Box::new(left),
+                list,
+                negated: not.is_some(),
+            }));
+        }
+
+        // `expr [NOT] BETWEEN low AND high`
+        let (input, between) = opt(tuple((
+            multispace1,
+            opt(pair(Parser::keyword("NOT"), multispace1)),
+            Parser::keyword("BETWEEN"),
+            multispace1,
+            Parser::additive,
+            delimited(multispace1, Parser::keyword("AND"), multispace1),
+            Parser::additive,
+        )))(input)?;
+        if let Some((_, not, _, _, low, _, high)) = between {
+            return Ok((input, Expr::Between {
+                operand: Box::new(left),
+                low: Box::new(low),
+                high: Box::new(high),
+                negated: not.is_some(),
+            }));
+        }
+
+        // `expr [NOT] LIKE | ILIKE | REGEXP pattern`
+        let (input, pattern_match) = opt(Parser::pattern_match)(input)?;
+        if let Some((negated, operator, pattern, escape)) = pattern_match {
+            let (operand, pattern) = (Box::new(left), Box::new(pattern));
+            return Ok((input, match operator {
+                "REGEXP" => Expr::Regexp { operand, pattern, negated },
+                _ => Expr::Like { operand, pattern, escape, case_insensitive: operator == "ILIKE", negated },
+            }));
+        }
+
+        let (input, rest) = opt(pair(
+            delimited(multispace0, Parser::comparison_operator, multispace0),
+            Parser::additive,
         ))(input)?;
 
-        let (input, _) = multispace1(input)?;
-        let (input, _) = tag_no_case("VALUES")(input)?;
-        let (input, _) = multispace0(input)?;
+        match rest {
+            Some((operator, right)) => Ok((input, Expr::Binary {
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+            })),
+            None => Ok((input, left)),
+        }
+    }
 
-        // Parse values
-        let (input, values) = delimited(
-            char('('),
-            separated_list0(
-                delimited(multispace0, char(','), multispace0),
-                Parser::value,
+    /// Parses the rest of a pattern predicate: `[NOT] LIKE pattern [ESCAPE 'c']`,
+    /// `[NOT] ILIKE ...` or `[NOT] REGEXP pattern`
+    fn pattern_match(input: &str) -> IResult<&str, (bool, &'static str, Expr, Option<char>)> {
+        let (input, not) = preceded(multispace1, opt(pair(Parser::keyword("NOT"), multispace1)))(input)?;
+        let (input, operator) = alt((
+            map(Parser::keyword("LIKE"), |_| "LIKE"),
+            map(Parser::keyword("ILIKE"), |_| "ILIKE"),
+            map(Parser::keyword("REGEXP"), |_| "REGEXP"),
+        ))(input)?;
+        let (input, pattern) = preceded(multispace0, Parser::additive)(input)?;
+        let (input, escape) = if operator == "REGEXP" {
+            (input, None)
+        } else {
+            opt(preceded(
+                tuple((multispace1, Parser::keyword("ESCAPE"), multispace1)),
+                map(
+                    verify(Parser::quoted_string, |s: &String| s.chars().count() == 1),
+                    |s: String| s.chars().next().unwrap_or_default(),
+                ),
+            ))(input)?
+        };
+
+        Ok((input, (not.is_some(), operator, pattern, escape)))
+    }
+
+    fn comparison_operator(input: &str) -> IResult<&str, BinaryOperator> {
+        alt((
+            map(tag(">="), |_| BinaryOperator::GreaterThanOrEqual),
+            map(tag("<="), |_| BinaryOperator::LessThanOrEqual),
+            map(tag("!="), |_| BinaryOperator::NotEqual),
+            map(tag("<>"), |_| BinaryOperator::NotEqual),
+            map(tag("="), |_| BinaryOperator::Equal),
+            map(tag(">"), |_| BinaryOperator::GreaterThan),
+            map(tag("<"), |_| BinaryOperator::LessThan),
+        ))(input)
+    }
+
+    /// Parses `+`, `-` and `||` chains (e.g., `age + 1`, `name || ' <' || email || '>'`)
+    fn additive(input: &str) -> IResult<&str, Expr> {
+        let (input, first) = Parser::multiplicative(input)?;
+        fold_many0(
+            pair(
+                delimited(
+                    multispace0,
+                    alt((
+                        map(tag("||"), |_| BinaryOperator::Concat),
+                        map(char('+'), |_| BinaryOperator::Add),
+                        map(char('-'), |_| BinaryOperator::Subtract),
+                    )),
+                    multispace0,
+                ),
+                Parser::multiplicative,
             ),
-            char(')'),
+            move || first.clone(),
+            |left, (operator, right)| Expr::Binary {
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+            },
+        )(input)
+    }
+
+    fn multiplicative(input: &str) -> IResult<&str, Expr> {
+        let (input, first) = Parser::unary(input)?;
+        fold_many0(
+            pair(
+                delimited(
+                    multispace0,
+                    alt((
+                        map(char('*'), |_| BinaryOperator::Multiply),
+                        map(char('/'), |_| BinaryOperator::Divide),
+                        map(char('%'), |_| BinaryOperator::Modulo),
+                    )),
+                    multispace0,
+                ),
+                Parser::unary,
+            ),
+            move || first.clone(),
+            |left, (operator, right)| Expr::Binary {
+                left: Box::new(left),
+                operator,
+                right: Box::new(right),
+            },
+        )(input)
+    }
+
+    /// Parses a negation; a minus sign before a number makes a negative literal
+    fn unary(input: &str) -> IResult<&str, Expr> {
+        alt((
+            map(preceded(pair(char('-'), multispace0), consumed(Parser::number)), |number| {
+                Expr::Literal(Parser::negative(number))
+            }),
+            map(preceded(pair(char('-'), multispace0), Parser::unary), |operand| {
+                Expr::Unary { operator: UnaryOperator::Minus, operand: Box::new(operand) }
+            }),
+            preceded(pair(char('+'), multispace0), Parser::unary),
+            Parser::primary,
+        ))(input)
+    }
+
+    /// Parses a scalar function call (e.g., `UPPER(name)`, `SUBSTR(name, 1, 3)`) with
+    /// the number of arguments the function takes
+    fn function_call(input: &str) -> IResult<&str, Expr> {
+        let (input, function) = map_opt(alphanumeric1, ScalarFunction::from_name)(input)?;
+        let (input, arguments) = verify(
+            delimited(
+                tuple((multispace0, char('('), multispace0)),
+                separated_list1(delimited(multispace0, char(','), multispace0), Parser::expression),
+                pair(multispace0, char(')')),
+            ),
+            |arguments: &Vec<Expr>| {
+                let (min, max) = function.arity();
+                arguments.len() >= min && max.is_none_or(|max| arguments.len() <= max)
+            },
         )(input)?;
 
-        let columns = columns.unwrap_or_else(Vec::new);
+        Ok((input, Expr::Function { function, arguments }))
+    }
 
-        Ok((input, ASTNode::InsertStatement {
-            table,
-            columns,
-            values,
+    /// Parses `CAST(expr AS type)`
+    fn cast(input: &str) -> IResult<&str, Expr> {
+        let (input, _) = pair(Parser::keyword("CAST"), tuple((multispace0, char('('), multispace0)))(input)?;
+        let (input, operand) = Parser::expression(input)?;
+        let (input, _) = tuple((multispace1, Parser::keyword("AS"), multispace1))(input)?;
+        let (input, data_type) = map_opt(Parser::data_type, |name| DataType::from_sql_name(&name))(input)?;
+        let (input, _) = pair(multispace0, char(')'))(input)?;
+
+        Ok((input, Expr::Cast { operand: Box::new(operand), data_type }))
+    }
+
+    /// Parses `CASE WHEN condition THEN result ... [ELSE result] END`, or the simple form
+    /// `CASE operand WHEN value THEN result ... [ELSE result] END`
+    fn case(input: &str) -> IResult<&str, Expr> {
+        let (input, _) = Parser::keyword("CASE")(input)?;
+        let (input, operand) = opt(preceded(
+            pair(multispace1, not(Parser::keyword("WHEN"))),
+            Parser::expression,
+        ))(input)?;
+        let (input, branches) = many1(map(
+            tuple((
+                delimited(multispace1, Parser::keyword("WHEN"), multispace1),
+                Parser::expression,
+                delimited(multispace1, Parser::keyword("THEN"), multispace1),
+                Parser::expression,
+            )),
+            |(_, when, _, result)| (when, result),
+        ))(input)?;
+        let (input, else_result) = opt(preceded(
+            delimited(multispace1, Parser::keyword("ELSE"), multispace1),
+            Parser::expression,
+        ))(input)?;
+        let (input, _) = pair(multispace1, Parser::keyword("END"))(input)?;
+
+        Ok((input, Expr::Case {
+            operand: operand.map(Box::new),
+            branches,
+            else_result: else_result.map(Box::new),
         }))
     }
 
-    fn parse_where_condition(input: &str) -> IResult<&str, WhereCondition> {
-        let (input, column) = alphanumeric1(input)?;
-        let (input, _) = multispace0(input)?;
-        let (input, operator) = alt((
-            tag(">="),
-            tag("<="),
-            tag("!="),
-            tag("<>"),
-            tag("="),
-            tag(">"),
-            tag("<"),
+    /// Parses a parenthesized SELECT (e.g., `(SELECT id FROM users)`)
+    fn subquery(input: &str) -> IResult<&str, SelectStatement> {
+        delimited(
+            pair(char('('), multispace0),
+            Parser::select,
+            pair(multispace0, char(')')),
+        )(input)
+    }
+
+    fn primary(input: &str) -> IResult<&str, Expr> {
+        alt((
+            map(Parser::subquery, |subquery| Expr::Subquery(Box::new(subquery))),
+            map(
+                preceded(pair(Parser::keyword("EXISTS"), multispace0), Parser::subquery),
+                |subquery| Expr::Exists(Box::new(subquery)),
+            ),
+            delimited(
+                pair(char('('), multispace0),
+                Parser::expression,
+                pair(multispace0, char(')')),
+            ),
+            map(Parser::literal, Expr::Literal),
+            Parser::parameter,
+            Parser::aggregate_call,
+            Parser::cast,
+            Parser::case,
+            Parser::function_call,
+            map(Parser::column_reference, Expr::Column),
+        ))(input)
+    }
+
+    /// Parses an aggregate call (e.g., `COUNT(*)`, `SUM(age)`)
+    fn aggregate_call(input: &str) -> IResult<&str, Expr> {
+        let (input, function) = alt((
+            map(Parser::keyword("COUNT"), |_| AggregateFunction::Count),
+            map(Parser::keyword("SUM"), |_| AggregateFunction::Sum),
+            map(Parser::keyword("AVG"), |_| AggregateFunction::Avg),
+            map(Parser::keyword("MIN"), |_| AggregateFunction::Min),
+            map(Parser::keyword("MAX"), |_| AggregateFunction::Max),
         ))(input)?;
+        let (input, _) = pair(multispace0, char('('))(input)?;
         let (input, _) = multispace0(input)?;
-        let (input, value) = Parser::value(input)?;
+        let (input, distinct) = opt(terminated(Parser::keyword("DISTINCT"), multispace1))(input)?;
+
+        // Only COUNT accepts `*`, and not together with DISTINCT
+        let (input, argument) = if function == AggregateFunction::Count && distinct.is_none() {
+            alt((
+                map(char('*'), |_| None),
+                map(Parser::expression, |expr| Some(Box::new(expr))),
+            ))(input)?
+        } else {
+            map(Parser::expression, |expr| Some(Box::new(expr)))(input)?
+        };
+        let (input, _) = pair(multispace0, char(')'))(input)?;
 
-        Ok((input, WhereCondition {
-            column: column.to_string(),
-            operator: operator.to_string(),
-            value,
+        Ok((input, Expr::Aggregate {
+            function,
+            argument,
+            distinct: distinct.is_some(),
         }))
     }
 
@@ -255,18 +2725,28 @@ impl Parser {
         let delete_parser = |input| Parser::delete_statement(input);
         let update_parser = |input| Parser::update_statement(input);
         let insert_parser = |input| Parser::insert_statement(input);
+        let create_table_parser = |input| Parser::create_table_statement(input);
+        let drop_table_parser = |input| Parser::drop_table_statement(input);
+        let alter_table_parser = |input| Parser::alter_table_statement(input);
+        let create_view_parser = |input| Parser::create_view_statement(input);
+        let drop_view_parser = |input| Parser::drop_view_statement(input);
 
         let mut parsers = alt((
             select_parser,
             delete_parser,
             update_parser,
             insert_parser,
+            create_table_parser,
+            drop_table_parser,
+            alter_table_parser,
+            create_view_parser,
+            drop_view_parser,
         ));
 
         match parsers(input.trim()) {
             Ok((remaining, ast)) => {
                 if remaining.trim().is_empty() {
-                    Ok(ast)
+                    Parser::number_parameters(ast)
                 } else {
                     Err(format!("Unexpected input after query: '{}'", remaining))
                 }
@@ -277,4 +2757,375 @@ impl Parser {
             Err(nom::Err::Incomplete(_)) => Err("Incomplete input".to_string()),
         }
     }
+
+    /// Parse the SELECT that defines a view
+    pub fn parse_select(input: &str) -> Result<SelectStatement, String> {
+        match Parser::parse(input)? {
+            ASTNode::SelectStatement(select) => Ok(select),
+            _ => Err(format!("Expected a SELECT query: '{}'", input)),
+        }
+    }
+
+    /// Parse a script of `;`-separated statements, ignoring `--` and `/* */` comments
+    pub fn parse_script(script: &str) -> Result<Vec<ASTNode>, String> {
+        Parser::split_statements(script)?
+            .iter()
+            .enumerate()
+            .map(|(position, statement)| {
+                Parser::parse(statement).map_err(|e| format!("Statement {}: {}", position + 1, e))
+            })
+            .collect()
+    }
+
+    /// Split a script into statements on `;`, leaving out comments and empty statements.
+    /// Semicolons and comment markers inside quoted strings and identifiers are kept.
+    pub fn split_statements(script: &str) -> Result<Vec<String>, String> {
+        let mut statements = Vec::new();
+        let mut current = String::new();
+        let mut chars = script.chars().peekable();
+        let mut finish = |current: &mut String| {
+            let statement = current.trim();
+            if !statement.is_empty() {
+                statements.push(statement.to_string());
+            }
+            current.clear();
+        };
+
+        while let Some(c) = chars.next() {
+            match c {
+                '\'' | '"' => {
+                    // A doubled quote stands for the quote character itself
+                    current.push(c);
+                    loop {
+                        match chars.next() {
+                            Some(next) if next == c => {
+                                current.push(next);
+                                match chars.next_if_eq(&c) {
+                                    Some(escaped) => current.push(escaped),
+                                    None => break,
+                                }
+                            }
+                            Some(next) => current.push(next),
+                            None => return Err(format!("Unterminated {} quote", c)),
+                        }
+                    }
+                }
+                '-' if chars.peek() == Some(&'-') => {
+                    // The line break ending the comment is kept
+                    while chars.next_if(|&next| next != '\n').is_some() {}
+                }
+                '/' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    let mut previous = None;
+                    loop {
+                        match chars.next() {
+                            Some('/') if previous == Some('*') => break,
+                            Some(next) => previous = Some(next),
+                            None => return Err("Unterminated /* comment".to_string()),
+                        }
+                    }
+                    current.push(' ');
+                }
+                ';' => finish(&mut current),
+                _ => current.push(c),
+            }
+        }
+        finish(&mut current);
+        Ok(statements)
+    }
+
+    /// Number `?` placeholders left to right; they cannot be mixed with `$n` ones
+    fn number_parameters(ast: ASTNode) -> Result<ASTNode, String> {
+        let mut positional = 0;
+        let mut numbered = false;
+        let _: Result<_, std::convert::Infallible> = ast.rewrite_expressions(&mut |node| {
+            match node {
+                Expr::Parameter(0) => positional += 1,
+                Expr::Parameter(_) => numbered = true,
+                _ => {}
+            }
+            Ok(None)
+        });
+        if positional == 0 {
+            return Ok(ast);
+        } else if numbered {
+            return Err("Cannot mix ? and $n parameter placeholders".to_string());
+        }
+
+        let mut next = 0;
+        ast.rewrite_expressions(&mut |node| match node {
+            Expr::Parameter(0) => {
+                next += 1;
+                Ok(Some(Expr::Parameter(next)))
+            }
+            _ => Ok(None),
+        })
+    }
+
+    /// Parse a comma-separated list of literal values (e.g., `1, 'Ann', NULL`)
+    pub fn parse_values(input: &str) -> Result<Vec<Value>, String> {
+        let mut values = separated_list0(delimited(multispace0, char(','), multispace0), Parser::value);
+        match values(input.trim()) {
+            Ok(("", values)) => Ok(values),
+            Ok((remaining, _)) => Err(format!("Unexpected input after values: '{}'", remaining)),
+            Err(e) => Err(format!("Parse error: {:?}", e)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn expr(input: &str) -> Expr {
+        let (rest, expr) = Parser::expression(input).unwrap();
+        assert!(rest.is_empty(), "unparsed input: {:?}", rest);
+        expr
+    }
+
+    fn person(age: i64, name: &str) -> Row {
+        Row { data: HashMap::from([
+            ("age".to_string(), Value::Integer(age)),
+            ("name".to_string(), Value::Text(name.to_string())),
+        ]) }
+    }
+
+    #[test]
+    fn and_binds_tighter_than_or() {
+        let condition = expr("name = 'Bo' OR age > 25 AND name = 'Ann'");
+        assert!(condition.evaluate(&person(20, "Bo")));
+        assert!(condition.evaluate(&person(30, "Ann")));
+        assert!(!condition.evaluate(&person(30, "Cy")));
+    }
+
+    #[test]
+    fn parentheses_and_not_group_predicates() {
+        let condition = expr("NOT (name = 'Bo' OR age > 25) AND age >= 18");
+        assert!(condition.evaluate(&person(20, "Ann")));
+        assert!(!condition.evaluate(&person(20, "Bo")));
+        assert!(!condition.evaluate(&person(30, "Ann")));
+        assert!(!condition.evaluate(&person(10, "Ann")));
+    }
+
+    #[test]
+    fn create_table_reads_column_and_table_constraints() {
+        let ast = Parser::parse("CREATE TABLE IF NOT EXISTS orders (id INTEGER PRIMARY KEY, total REAL, PRIMARY KEY (id))");
+        let Ok(ASTNode::CreateTableStatement { table, columns, constraints, if_not_exists }) = ast else {
+            panic!("expected CREATE TABLE, got {:?}", ast);
+        };
+        assert_eq!(table.0, "orders");
+        assert!(if_not_exists);
+        assert_eq!(columns.len(), 2);
+        assert_eq!(columns[0].constraints, vec![ColumnConstraint::PrimaryKey]);
+        assert_eq!(columns[1].data_type, "REAL");
+        assert!(matches!(&constraints[..], [TableConstraint::PrimaryKey(key)] if key[0].0 == "id"));
+    }
+
+    #[test]
+    fn create_table_needs_at_least_one_column() {
+        assert!(Parser::parse("CREATE TABLE empty ()").is_err());
+    }
+
+    #[test]
+    fn comparisons_with_null_are_unknown() {
+        let row = person(30, "Ann");
+        assert_eq!(expr("missing = 1").eval(&row), Value::Null);
+        assert_eq!(expr("NULL = NULL").eval(&row), Value::Null);
+        assert_eq!(expr("age <> NULL").eval(&row), Value::Null);
+        assert!(!expr("NOT (missing = 1)").evaluate(&row));
+        assert!(expr("missing IS NULL AND age IS NOT NULL").evaluate(&row));
+    }
+
+    #[test]
+    fn and_or_not_follow_three_valued_logic() {
+        let row = person(30, "Ann");
+        let truth = |input: &str| expr(input).eval(&row);
+        // `age = 30` is TRUE, `age = 1` is FALSE and `missing = 1` is unknown
+        assert_eq!(truth("missing = 1 AND age = 1"), Value::Boolean(false));
+        assert_eq!(truth("missing = 1 AND age = 30"), Value::Null);
+        assert_eq!(truth("missing = 1 OR age = 30"), Value::Boolean(true));
+        assert_eq!(truth("missing = 1 OR age = 1"), Value::Null);
+        assert_eq!(truth("NOT missing = 1"), Value::Null);
+        assert_eq!(truth("NOT (age = 30 AND missing = 1)"), Value::Null);
+    }
+
+    fn order_by(sql: &str) -> Vec<OrderByItem> {
+        match Parser::parse(sql) {
+            Ok(ASTNode::SelectStatement(select)) => select.order_by,
+            other => panic!("expected a SELECT, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn descending_keys_put_nulls_first_unless_told_otherwise() {
+        let keys = order_by("SELECT * FROM users ORDER BY age DESC, name, id DESC NULLS LAST");
+        let flags: Vec<(bool, bool)> = keys.iter().map(|key| (key.descending, key.nulls_first)).collect();
+        assert_eq!(flags, vec![(true, true), (false, false), (true, false)]);
+    }
+
+    #[test]
+    fn order_by_position_names_a_selected_column() {
+        let keys = order_by("SELECT id, name FROM users ORDER BY 2 DESC, 1");
+        assert_eq!(keys[0].expr, Expr::Column("name".to_string()));
+        assert!(keys[0].descending);
+        assert_eq!(keys[1].expr, Expr::Column("id".to_string()));
+
+        // Positions outside the select list are left for validation to reject
+        let keys = order_by("SELECT id FROM users ORDER BY 2");
+        assert_eq!(keys[0].expr, Expr::Literal(Value::Integer(2)));
+
+        // So are positions at or after a `*`, which the executor counts once it expands
+        let keys = order_by("SELECT id, * FROM users ORDER BY 1, 2");
+        assert_eq!(keys[0].expr, Expr::Column("id".to_string()));
+        assert_eq!(keys[1].expr, Expr::Literal(Value::Integer(2)));
+    }
+
+    #[test]
+    fn between_includes_both_bounds_and_is_unknown_only_when_undecided() {
+        let row = person(30, "Ann");
+        let truth = |input: &str| expr(input).eval(&row);
+        assert_eq!(truth("age BETWEEN 30 AND 40"), Value::Boolean(true));
+        assert_eq!(truth("age BETWEEN 20 AND 30"), Value::Boolean(true));
+        assert_eq!(truth("age NOT BETWEEN 31 AND 40"), Value::Boolean(true));
+        assert_eq!(truth("name BETWEEN 'A' AND 'B' AND age BETWEEN 18 AND 65"), Value::Boolean(true));
+        // One failing bound decides the answer even when the other is NULL
+        assert_eq!(truth("age BETWEEN 40 AND NULL"), Value::Boolean(false));
+        assert_eq!(truth("age BETWEEN NULL AND 40"), Value::Null);
+        assert_eq!(truth("missing BETWEEN 1 AND 2"), Value::Null);
+    }
+
+    #[test]
+    fn in_lists_match_any_item_and_treat_null_items_as_unknown() {
+        let row = person(30, "Ann");
+        let truth = |input: &str| expr(input).eval(&row);
+        assert_eq!(truth("age IN (10, 30.0)"), Value::Boolean(true));
+        assert_eq!(truth("name NOT IN ('Bo', 'Cy')"), Value::Boolean(true));
+        assert_eq!(truth("age IN (10, 20)"), Value::Boolean(false));
+        assert_eq!(truth("age IN (30, NULL)"), Value::Boolean(true));
+        assert_eq!(truth("age IN (10, NULL)"), Value::Null);
+        assert_eq!(truth("age NOT IN (10, NULL)"), Value::Null);
+        assert_eq!(truth("missing IN (1)"), Value::Null);
+    }
+
+    #[test]
+    fn order_by_alias_names_the_aliased_expression() {
+        let keys = order_by("SELECT UPPER(name) AS shout, age FROM users ORDER BY shout, 2");
+        assert_eq!(keys[0].expr, expr("UPPER(name)"));
+        assert_eq!(keys[1].expr, Expr::Column("age".to_string()));
+    }
+
+    fn condition(sql: &str) -> Option<Expr> {
+        match Parser::parse(sql) {
+            Ok(ASTNode::SelectStatement(select)) => select.condition,
+            other => panic!("expected a SELECT, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn question_marks_are_numbered_left_to_right() {
+        let numbered = condition("SELECT * FROM users WHERE age > ? AND name IN (?, ?)");
+        assert_eq!(numbered, Some(expr("age > $1 AND name IN ($2, $3)")));
+        assert!(Parser::parse("SELECT * FROM users WHERE age > ? AND name = $1").is_err());
+    }
+
+    #[test]
+    fn binding_replaces_each_placeholder_with_its_value() {
+        let ast = Parser::parse("SELECT * FROM users WHERE age > $2 AND name = $1 OR age = $2").unwrap();
+        assert_eq!(ast.parameter_count(), 2);
+        let bound = ast.bind_parameters(&[Value::Text("Ann".to_string()), Value::Integer(30)]);
+        assert_eq!(bound.parameter_count(), 0);
+        let ASTNode::SelectStatement(select) = bound else {
+            panic!("expected a SELECT");
+        };
+        assert_eq!(select.condition, Some(expr("age > 30 AND name = 'Ann' OR age = 30")));
+    }
+
+    #[test]
+    fn scripts_split_on_semicolons_outside_strings_and_comments() {
+        let script = "INSERT INTO notes VALUES ('a;b', 'it''s; fine');\n\
+                      -- a comment; not a statement\n\
+                      SELECT \"odd;name\" FROM notes /* also; skipped */ WHERE id = 1;;\n\
+                      SELECT 1 -- trailing";
+        assert_eq!(Parser::split_statements(script).unwrap(), vec![
+            "INSERT INTO notes VALUES ('a;b', 'it''s; fine')".to_string(),
+            "SELECT \"odd;name\" FROM notes   WHERE id = 1".to_string(),
+            "SELECT 1".to_string(),
+        ]);
+    }
+
+    #[test]
+    fn scripts_with_unterminated_strings_or_comments_are_rejected() {
+        assert!(Parser::split_statements("SELECT 'open; SELECT 1").is_err());
+        assert!(Parser::split_statements("SELECT 1; /* never closed").is_err());
+        assert_eq!(Parser::split_statements("  ;\n-- only a comment\n").unwrap(), Vec::<String>::new());
+    }
+
+    #[test]
+    fn literals_cover_escaped_quotes_blobs_exponents_and_booleans() {
+        let literal = |input: &str| match expr(input) {
+            Expr::Literal(value) => value,
+            other => panic!("expected a literal, got {:?}", other),
+        };
+        assert_eq!(literal("'it''s'"), Value::Text("it's".to_string()));
+        assert_eq!(literal("x'0aFF'"), Value::Blob(vec![0x0a, 0xff]));
+        assert_eq!(literal("1e3"), Value::Real(1000.0));
+        assert_eq!(literal("2.5E-1"), Value::Real(0.25));
+        assert_eq!(literal(".5"), Value::Real(0.5));
+        assert_eq!(literal("- 7"), Value::Integer(-7));
+        assert_eq!(literal("TRUE"), Value::Boolean(true));
+        assert!(Parser::expression("X'ABC'").map_or(true, |(rest, _)| !rest.is_empty()));
+        assert!(Parser::expression("1abc").map_or(true, |(rest, _)| !rest.is_empty()));
+    }
+
+    #[test]
+    fn the_smallest_integer_parses_as_an_integer() {
+        assert_eq!(expr("-9223372036854775808"), Expr::Literal(Value::Integer(i64::MIN)));
+        assert_eq!(expr("-9223372036854775809"), Expr::Literal(Value::Real(-9223372036854775809.0)));
+        assert_eq!(Parser::parse_values("-9223372036854775808, +7, -2.5").unwrap(), vec![
+            Value::Integer(i64::MIN),
+            Value::Integer(7),
+            Value::Real(-2.5),
+        ]);
+        // Negating it again leaves evaluation to widen the result
+        let double = expr("- -9223372036854775808");
+        assert_eq!(double.eval(&Row { data: HashMap::new() }), Value::Real(9223372036854775808.0));
+    }
+
+    #[test]
+    fn identifiers_may_be_quoted_or_use_underscores_and_dollars() {
+        let ast = Parser::parse(r#"SELECT "first name", _tmp$1 FROM "order items""#);
+        let Ok(ASTNode::SelectStatement(select)) = ast else {
+            panic!("expected a SELECT, got {:?}", ast);
+        };
+        assert_eq!(select.from.name.0, "order items");
+        let columns: Vec<Option<&Expr>> = select.projection.iter().map(SelectItem::expr).collect();
+        assert_eq!(columns, vec![
+            Some(&Expr::Column("first name".to_string())),
+            Some(&Expr::Column("_tmp$1".to_string())),
+        ]);
+        assert!(Parser::parse(r#"SELECT "a.b" FROM t"#).is_err());
+    }
+
+    #[test]
+    fn searched_case_takes_the_first_true_branch() {
+        let row = person(30, "Ann");
+        let text = |text: &str| Value::Text(text.to_string());
+        let case = |input: &str| expr(input).eval(&row);
+        assert_eq!(case("CASE WHEN age < 18 THEN 'minor' WHEN age < 65 THEN 'adult' ELSE 'senior' END"), text("adult"));
+        assert_eq!(case("CASE WHEN age > 40 THEN 'old' END"), Value::Null);
+        // An unknown condition is not true, so it falls through
+        assert_eq!(case("CASE WHEN missing > 1 THEN 'yes' ELSE 'no' END"), text("no"));
+    }
+
+    #[test]
+    fn simple_case_never_matches_a_null_operand_or_value() {
+        let row = person(30, "Ann");
+        let text = |text: &str| Value::Text(text.to_string());
+        let case = |input: &str| expr(input).eval(&row);
+        assert_eq!(case("CASE age WHEN 20 THEN 'a' WHEN 30.0 THEN 'b' END"), text("b"));
+        assert_eq!(case("CASE missing WHEN NULL THEN 'null' ELSE 'other' END"), text("other"));
+        assert_eq!(case("CASE name WHEN NULL THEN 'null' WHEN 'Ann' THEN 'Ann' END"), text("Ann"));
+        assert_eq!(case("CASE missing WHEN 1 THEN 'one' END"), Value::Null);
+    }
 }
@@ -0,0 +1,661 @@
+//! Pattern matching for the LIKE, ILIKE and REGEXP predicates.
+
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::rc::Rc;
+
+/// How many compiled patterns of each kind a thread keeps for reuse
+const CACHE_SIZE: usize = 64;
+
+/// A LIKE pattern with its escape character and whether it ignores case
+type LikeKey = (String, Option<char>, bool);
+
+thread_local! {
+    static COMPILED_LIKE: RefCell<HashMap<LikeKey, Option<Rc<LikePattern>>>> = RefCell::new(HashMap::new());
+}
+
+/// A compiled LIKE pattern: `%` matches any run of characters, `_` exactly one
+#[derive(Debug, Clone, PartialEq)]
+pub struct LikePattern {
+    tokens: Vec<LikeToken>,
+    case_insensitive: bool,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum LikeToken {
+    Char(char),
+    One,
+    Many,
+}
+
+impl LikePattern {
+    /// Compile a pattern; the escape character makes the next character literal
+    pub fn new(pattern: &str, escape: Option<char>, case_insensitive: bool) -> Result<Self, String> {
+        let mut tokens = Vec::new();
+        let mut chars = pattern.chars();
+        while let Some(c) = chars.next() {
+            tokens.push(match c {
+                c if Some(c) == escape => match chars.next() {
+                    Some(escaped) => LikeToken::Char(escaped),
+                    None => return Err("LIKE pattern must not end with the escape character".to_string()),
+                },
+                '%' => LikeToken::Many,
+                '_' => LikeToken::One,
+                c => LikeToken::Char(c),
+            });
+        }
+
+        if case_insensitive {
+            for token in tokens.iter_mut() {
+                if let LikeToken::Char(c) = token {
+                    *c = fold_case(*c);
+                }
+            }
+        }
+        Ok(LikePattern { tokens, case_insensitive })
+    }
+
+    /// Compile a pattern once per thread and reuse it; `None` when the pattern is invalid
+    pub fn cached(pattern: &str, escape: Option<char>, case_insensitive: bool) -> Option<Rc<LikePattern>> {
+        COMPILED_LIKE.with(|compiled| {
+            let mut compiled = compiled.borrow_mut();
+            let key = (pattern.to_string(), escape, case_insensitive);
+            if let Some(like) = compiled.get(&key) {
+                return like.clone();
+            }
+            if compiled.len() >= CACHE_SIZE {
+                compiled.clear();
+            }
+            let like = LikePattern::new(pattern, escape, case_insensitive).ok().map(Rc::new);
+            compiled.insert(key, like.clone());
+            like
+        })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = if self.case_insensitive {
+            text.chars().map(fold_case).collect()
+        } else {
+            text.chars().collect()
+        };
+
+        // Greedy matching that backtracks to the most recent `%` on a mismatch
+        let (mut t, mut p) = (0, 0);
+        let mut backtrack: Option<(usize, usize)> = None;
+        while t < text.len() {
+            match self.tokens.get(p) {
+                Some(LikeToken::Many) => {
+                    p += 1;
+                    backtrack = Some((p, t));
+                }
+                Some(LikeToken::One) => {
+                    t += 1;
+                    p += 1;
+                }
+                Some(LikeToken::Char(c)) if *c == text[t] => {
+                    t += 1;
+                    p += 1;
+                }
+                _ => match backtrack {
+                    // Let the `%` absorb one more character and retry
+                    Some((after_many, start)) => {
+                        p = after_many;
+                        t = start + 1;
+                        backtrack = Some((after_many, start + 1));
+                    }
+                    None => return false,
+                },
+            }
+        }
+        self.tokens[p..].iter().all(|token| *token == LikeToken::Many)
+    }
+
+    /// The literal text every match starts with, and whether the pattern is only that text
+    pub fn prefix(&self) -> (String, bool) {
+        let prefix: String = self.tokens.iter()
+            .map_while(|token| match token {
+                LikeToken::Char(c) => Some(*c),
+                _ => None,
+            })
+            .collect();
+        let exact = prefix.chars().count() == self.tokens.len();
+        (prefix, exact)
+    }
+}
+
+fn fold_case(c: char) -> char {
+    c.to_lowercase().next().unwrap_or(c)
+}
+
+/// A regular expression compiled for a Pike VM, which advances all candidate matches
+/// in lockstep so matching takes time linear in the input for any pattern.
+///
+/// Supports literals, `.`, classes (`[a-z]`, `[^0-9]`), the escapes `\d \w \s \D \W \S`,
+/// anchors `^ $`, groups, `|` and the quantifiers `* + ? {n} {n,} {n,m}`. Matching
+/// searches anywhere in the text unless anchored.
+#[derive(Debug)]
+pub struct Regex {
+    program: Vec<Instruction>,
+}
+
+#[derive(Debug, Clone)]
+enum Instruction {
+    Char(char),
+    Any,
+    Class(Class),
+    Split(usize, usize),
+    Jump(usize),
+    AssertStart,
+    AssertEnd,
+    Match,
+}
+
+#[derive(Debug, Clone)]
+struct Class {
+    ranges: Vec<(char, char)>,
+    negated: bool,
+}
+
+impl Class {
+    fn contains(&self, c: char) -> bool {
+        self.ranges.iter().any(|(low, high)| (*low..=*high).contains(&c)) != self.negated
+    }
+
+    /// The class of a `\d`, `\w` or `\s` escape (uppercase negates it)
+    fn escape(c: char) -> Option<Class> {
+        let ranges = match c.to_ascii_lowercase() {
+            'd' => vec![('0', '9')],
+            'w' => vec![('a', 'z'), ('A', 'Z'), ('0', '9'), ('_', '_')],
+            's' => vec![(' ', ' '), ('\t', '\r')],
+            _ => return None,
+        };
+        Some(Class { ranges, negated: c.is_ascii_uppercase() })
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Char(char),
+    Any,
+    Class(Class),
+    Start,
+    End,
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat { node: Box<Node>, min: usize, max: Option<usize> },
+}
+
+/// Upper bound on counted repetition, which compiles to copies of the repeated node
+const MAX_REPEAT: usize = 1000;
+
+/// Upper bound on the nodes compiled for a pattern, counting every copy; nested counted
+/// repetitions multiply their copies
+const MAX_COMPILED_NODES: usize = 100_000;
+
+thread_local! {
+    static COMPILED_REGEX: RefCell<HashMap<String, Option<Rc<Regex>>>> = RefCell::new(HashMap::new());
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Self, String> {
+        let mut parser = RegexParser { chars: pattern.chars().collect(), position: 0 };
+        let node = parser.alternation()?;
+        if parser.position < parser.chars.len() {
+            return Err(format!("unmatched ')' at position {}", parser.position));
+        }
+
+        let (mut program, mut budget) = (Vec::new(), MAX_COMPILED_NODES);
+        compile(&node, &mut program, &mut budget)?;
+        program.push(Instruction::Match);
+        Ok(Regex { program })
+    }
+
+    /// Compile a pattern once per thread and reuse it; `None` when the pattern is invalid
+    pub fn cached(pattern: &str) -> Option<Rc<Regex>> {
+        COMPILED_REGEX.with(|compiled| {
+            let mut compiled = compiled.borrow_mut();
+            if let Some(regex) = compiled.get(pattern) {
+                return regex.clone();
+            }
+            if compiled.len() >= CACHE_SIZE {
+                compiled.clear();
+            }
+            let regex = Regex::new(pattern).ok().map(Rc::new);
+            compiled.insert(pattern.to_string(), regex.clone());
+            regex
+        })
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        let text: Vec<char> = text.chars().collect();
+        let mut current = Threads::new(self.program.len());
+        let mut next = Threads::new(self.program.len());
+
+        for position in 0..=text.len() {
+            // Start a new attempt at every position to search the whole text
+            self.add_thread(&mut current, 0, position, text.len());
+
+            for index in 0..current.list.len() {
+                let pc = current.list[index];
+                let matched = match self.program[pc] {
+                    Instruction::Match => return true,
+                    Instruction::Char(c) => text.get(position) == Some(&c),
+                    Instruction::Any => position < text.len(),
+                    Instruction::Class(ref class) => text.get(position).is_some_and(|c| class.contains(*c)),
+                    _ => false,
+                };
+                if matched {
+                    self.add_thread(&mut next, pc + 1, position + 1, text.len());
+                }
+            }
+
+            std::mem::swap(&mut current, &mut next);
+            next.clear();
+        }
+        false
+    }
+
+    /// Add a thread, following jumps, splits and anchors that consume no input
+    fn add_thread(&self, threads: &mut Threads, pc: usize, position: usize, length: usize) {
+        if !threads.insert(pc) {
+            return;
+        }
+
+        match self.program[pc] {
+            Instruction::Jump(target) => self.add_thread(threads, target, position, length),
+            Instruction::Split(first, second) => {
+                self.add_thread(threads, first, position, length);
+                self.add_thread(threads, second, position, length);
+            }
+            Instruction::AssertStart if position == 0 => self.add_thread(threads, pc + 1, position, length),
+            Instruction::AssertEnd if position == length => self.add_thread(threads, pc + 1, position, length),
+            Instruction::AssertStart | Instruction::AssertEnd => {}
+            _ => threads.list.push(pc),
+        }
+    }
+}
+
+/// The set of program counters live at one input position
+struct Threads {
+    list: Vec<usize>,
+    seen: Vec<bool>,
+}
+
+impl Threads {
+    fn new(size: usize) -> Self {
+        Threads { list: Vec::new(), seen: vec![false; size] }
+    }
+
+    fn insert(&mut self, pc: usize) -> bool {
+        !std::mem::replace(&mut self.seen[pc], true)
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.seen.iter_mut().for_each(|seen| *seen = false);
+    }
+}
+
+/// Append the instructions of a node, counting each node compiled against `budget`
+fn compile(node: &Node, program: &mut Vec<Instruction>, budget: &mut usize) -> Result<(), String> {
+    *budget = budget.checked_sub(1).ok_or_else(|| {
+        format!("pattern is too large; it compiles more than {} nodes", MAX_COMPILED_NODES)
+    })?;
+    match node {
+        Node::Empty => {}
+        Node::Char(c) => program.push(Instruction::Char(*c)),
+        Node::Any => program.push(Instruction::Any),
+        Node::Class(class) => program.push(Instruction::Class(class.clone())),
+        Node::Start => program.push(Instruction::AssertStart),
+        Node::End => program.push(Instruction::AssertEnd),
+        Node::Concat(nodes) => nodes.iter().try_for_each(|node| compile(node, program, budget))?,
+        Node::Alternate(branches) => {
+            // split L1, next; L1: first; jump end; next: split L2, ...
+            let mut jumps = Vec::new();
+            for (index, branch) in branches.iter().enumerate() {
+                if index + 1 < branches.len() {
+                    let split = program.len();
+                    program.push(Instruction::Split(split + 1, 0));
+                    compile(branch, program, budget)?;
+                    jumps.push(program.len());
+                    program.push(Instruction::Jump(0));
+                    let next = program.len();
+                    program[split] = Instruction::Split(split + 1, next);
+                } else {
+                    compile(branch, program, budget)?;
+                }
+            }
+            let end = program.len();
+            for jump in jumps {
+                program[jump] = Instruction::Jump(end);
+            }
+        }
+        Node::Repeat { node, min, max } => {
+            for _ in 0..*min {
+                compile(node, program, budget)?;
+            }
+            match max {
+                // L1: split L2, end; L2: node; jump L1
+                None => {
+                    let split = program.len();
+                    program.push(Instruction::Split(split + 1, 0));
+                    compile(node, program, budget)?;
+                    program.push(Instruction::Jump(split));
+                    let end = program.len();
+                    program[split] = Instruction::Split(split + 1, end);
+                }
+                // Each optional copy may be skipped to the end
+                Some(max) => {
+                    let mut splits = Vec::new();
+                    for _ in *min..*max {
+                        splits.push(program.len());
+                        program.push(Instruction::Split(program.len() + 1, 0));
+                        compile(node, program, budget)?;
+                    }
+                    let end = program.len();
+                    for split in splits {
+                        program[split] = Instruction::Split(split + 1, end);
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Recursive descent over the pattern: alternation, then concatenation, then
+/// quantified atoms
+struct RegexParser {
+    chars: Vec<char>,
+    position: usize,
+}
+
+impl RegexParser {
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.position).copied()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek();
+        self.position += 1;
+        c
+    }
+
+    fn alternation(&mut self) -> Result<Node, String> {
+        let mut branches = vec![self.concatenation()?];
+        while self.peek() == Some('|') {
+            self.position += 1;
+            branches.push(self.concatenation()?);
+        }
+        Ok(if branches.len() == 1 { branches.remove(0) } else { Node::Alternate(branches) })
+    }
+
+    fn concatenation(&mut self) -> Result<Node, String> {
+        let mut nodes = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            let atom = self.atom()?;
+            nodes.push(self.quantified(atom)?);
+        }
+        Ok(match nodes.len() {
+            0 => Node::Empty,
+            1 => nodes.remove(0),
+            _ => Node::Concat(nodes),
+        })
+    }
+
+    fn atom(&mut self) -> Result<Node, String> {
+        let position = self.position;
+        match self.next() {
+            Some('(') => {
+                let node = self.alternation()?;
+                match self.next() {
+                    Some(')') => Ok(node),
+                    _ => Err(format!("unclosed group at position {}", position)),
+                }
+            }
+            Some('[') => self.class(position),
+            Some('.') => Ok(Node::Any),
+            Some('^') => Ok(Node::Start),
+            Some('$') => Ok(Node::End),
+            Some('\\') => match self.next() {
+                Some(c) => Ok(Class::escape(c).map_or(Node::Char(c), Node::Class)),
+                None => Err("pattern ends with '\\'".to_string()),
+            },
+            Some(c @ ('*' | '+' | '?')) => {
+                Err(format!("quantifier '{}' at position {} has nothing to repeat", c, position))
+            }
+            Some(c) => Ok(Node::Char(c)),
+            None => Err("unexpected end of pattern".to_string()),
+        }
+    }
+
+    fn class(&mut self, start: usize) -> Result<Node, String> {
+        let negated = self.peek() == Some('^');
+        if negated {
+            self.position += 1;
+        }
+
+        let mut ranges = Vec::new();
+        let mut first = true;
+        loop {
+            let low = match self.next() {
+                None => return Err(format!("unclosed character class at position {}", start)),
+                // A leading `]` is a literal
+                Some(']') if !first => break,
+                Some('\\') => match self.next() {
+                    Some(c) => match Class::escape(c) {
+                        Some(Class { ranges: escaped, negated: false }) => {
+                            ranges.extend(escaped);
+                            first = false;
+                            continue;
+                        }
+                        Some(_) => return Err(format!("negated escape '\\{}' inside a character class", c)),
+                        None => c,
+                    },
+                    None => return Err("pattern ends with '\\'".to_string()),
+                },
+                Some(c) => c,
+            };
+            first = false;
+
+            // `a-z` is a range unless the `-` ends the class
+            if self.peek() == Some('-') && self.chars.get(self.position + 1).is_some_and(|c| *c != ']') {
+                self.position += 1;
+                let high = match self.next() {
+                    Some('\\') => self.next().ok_or("pattern ends with '\\'")?,
+                    Some(c) => c,
+                    None => return Err(format!("unclosed character class at position {}", start)),
+                };
+                if high < low {
+                    return Err(format!("invalid range '{}-{}' in character class", low, high));
+                }
+                ranges.push((low, high));
+            } else {
+                ranges.push((low, low));
+            }
+        }
+        Ok(Node::Class(Class { ranges, negated }))
+    }
+
+    fn quantified(&mut self, atom: Node) -> Result<Node, String> {
+        let (min, max) = match self.peek() {
+            Some('{') => match self.counted()? {
+                Some(bounds) => bounds,
+                None => return Ok(atom),
+            },
+            Some(c @ ('*' | '+' | '?')) => {
+                self.position += 1;
+                match c {
+                    '*' => (0, None),
+                    '+' => (1, None),
+                    _ => (0, Some(1)),
+                }
+            }
+            _ => return Ok(atom),
+        };
+
+        if matches!(atom, Node::Start | Node::End) {
+            return Err("anchors cannot be repeated".to_string());
+        }
+        Ok(Node::Repeat { node: Box::new(atom), min, max })
+    }
+
+    /// Parses `{n}`, `{n,}` or `{n,m}`; anything else leaves the `{` as a literal
+    fn counted(&mut self) -> Result<Option<(usize, Option<usize>)>, String> {
+        let start = self.position;
+        let rest: String = self.chars[start..].iter().collect();
+        let Some(end) = rest.find('}') else {
+            return Ok(None);
+        };
+
+        let body = &rest[1..end];
+        let number = |s: &str| s.trim().parse::<usize>().ok();
+        let bounds = match body.split_once(',') {
+            None => number(body).map(|n| (n, Some(n))),
+            Some((min, "")) => number(min).map(|min| (min, None)),
+            Some((min, max)) => number(min).zip(number(max)).map(|(min, max)| (min, Some(max))),
+        };
+        let Some((min, max)) = bounds else {
+            return Ok(None);
+        };
+
+        if max.is_some_and(|max| max < min) {
+            return Err(format!("invalid repetition '{{{}}}'", body));
+        }
+        if min.max(max.unwrap_or(0)) > MAX_REPEAT {
+            return Err(format!("repetition count in '{{{}}}' exceeds {}", body, MAX_REPEAT));
+        }
+        self.position = start + rest[..=end].chars().count();
+        Ok(Some((min, max)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn like(pattern: &str, text: &str) -> bool {
+        LikePattern::new(pattern, None, false).unwrap().is_match(text)
+    }
+
+    fn regex(pattern: &str, text: &str) -> bool {
+        Regex::new(pattern).unwrap().is_match(text)
+    }
+
+    #[test]
+    fn like_wildcards_match_the_whole_text() {
+        assert!(like("abc", "abc"));
+        assert!(!like("abc", "abcd"));
+        assert!(like("a%", "abc"));
+        assert!(like("%c", "abc"));
+        assert!(like("%", ""));
+        assert!(like("a_c", "abc"));
+        assert!(!like("a_c", "ac"));
+        assert!(like("%b%", "abc"));
+        assert!(!like("%x%", "abc"));
+    }
+
+    #[test]
+    fn like_escape_makes_wildcards_literal() {
+        let percent = LikePattern::new("100!%", Some('!'), false).unwrap();
+        assert!(percent.is_match("100%"));
+        assert!(!percent.is_match("1000"));
+
+        let underscore = LikePattern::new("a\\_%", Some('\\'), false).unwrap();
+        assert!(underscore.is_match("a_b"));
+        assert!(!underscore.is_match("ab"));
+
+        assert!(LikePattern::new("ab!", Some('!'), false).is_err());
+    }
+
+    #[test]
+    fn ilike_folds_case() {
+        let pattern = LikePattern::new("ÉCOLE%", None, true).unwrap();
+        assert!(pattern.is_match("école primaire"));
+        assert!(!LikePattern::new("ÉCOLE%", None, false).unwrap().is_match("école"));
+    }
+
+    #[test]
+    fn like_prefix_stops_at_the_first_wildcard() {
+        assert_eq!(LikePattern::new("ab%c", None, false).unwrap().prefix(), ("ab".to_string(), false));
+        assert_eq!(LikePattern::new("a!%", Some('!'), false).unwrap().prefix(), ("a%".to_string(), true));
+    }
+
+    #[test]
+    fn cached_patterns_match_like_fresh_ones() {
+        let pattern = LikePattern::cached("a%", None, false).unwrap();
+        assert!(Rc::ptr_eq(&pattern, &LikePattern::cached("a%", None, false).unwrap()));
+        assert!(pattern.is_match("abc"));
+        assert!(LikePattern::cached("a!", Some('!'), false).is_none());
+        assert!(Regex::cached("(").is_none());
+    }
+
+    #[test]
+    fn regex_anchors() {
+        assert!(regex("b", "abc"));
+        assert!(!regex("^b", "abc"));
+        assert!(regex("^a", "abc"));
+        assert!(regex("c$", "abc"));
+        assert!(!regex("b$", "abc"));
+        assert!(regex("^$", ""));
+        assert!(Regex::new("^*").is_err());
+    }
+
+    #[test]
+    fn regex_character_classes() {
+        assert!(regex("^[a-c]+$", "abcab"));
+        assert!(!regex("^[a-c]+$", "abd"));
+        assert!(regex("^[^0-9]+$", "abc"));
+        assert!(!regex("[^0-9]", "123"));
+        assert!(regex("^[]a]+$", "]a"));
+        assert!(regex("^[a-]+$", "a-"));
+        assert!(regex("^[\\d_]+$", "1_2"));
+        assert!(Regex::new("[z-a]").is_err());
+        assert!(Regex::new("[abc").is_err());
+    }
+
+    #[test]
+    fn regex_escapes() {
+        assert!(regex("^\\d{3}-\\d{4}$", "555-1234"));
+        assert!(!regex("^\\d+$", "12a"));
+        assert!(regex("^\\w+\\s\\w+$", "hello world"));
+        assert!(regex("^\\S+$", "no_spaces"));
+        assert!(regex("^a\\.b$", "a.b"));
+        assert!(!regex("^a\\.b$", "axb"));
+        assert!(Regex::new("a\\").is_err());
+    }
+
+    #[test]
+    fn regex_quantifiers_and_alternation() {
+        assert!(regex("^(ab|cd)+$", "abcdab"));
+        assert!(!regex("^(ab|cd)+$", "abc"));
+        assert!(regex("^a{2,3}$", "aaa"));
+        assert!(!regex("^a{2,3}$", "aaaa"));
+        assert!(regex("^colou?r$", "color"));
+        assert!(regex("^a{x}$", "a{x}"));
+        assert!(Regex::new("a{3,1}").is_err());
+        assert!(Regex::new("a{1001}").is_err());
+    }
+
+    #[test]
+    fn nested_counted_repetition_is_bounded_in_total() {
+        assert!(Regex::new("((((){1000}){1000}){1000}){1000}").is_err());
+        assert!(Regex::new("(a{100}){100}").is_ok());
+        assert!(Regex::new("(a{1000}){1000}").is_err());
+        assert!(Regex::new("((a{1000}){1000}){1000}").is_err());
+        assert!(Regex::new("(((a{1,1000}){1000}){1000}){1000}").is_err());
+    }
+
+    #[test]
+    fn backtracking_worst_cases_finish() {
+        let text = "a".repeat(5_000);
+        assert!(!like("%a%a%a%a%a%b", &text));
+        assert!(like("%a%a%a%a%a", &text));
+        assert!(!regex("^(a|aa)*(a*)*b$", &text));
+        assert!(regex("^(a*)*$", &text));
+    }
+}
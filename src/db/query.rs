@@ -1,6 +1,14 @@
-use super::parser::{ASTNode, WhereCondition};
-use super::schema::Row;
-use std::collections::HashMap;
+use super::parser::{
+    ASTNode, AggregateFunction, AlterTableOperation, BinaryOperator, ColumnConstraint,
+    ColumnDefinition, CommonTableExpression, ConflictAction, Expr, InsertSource, Join, JoinKind,
+    OnConflict, OrderByItem, Parser, SelectItem, SelectStatement, SetOperator, TableConstraint,
+    TableReference, UnaryOperator, WithClause,
+};
+use super::pattern::{LikePattern, Regex};
+use super::functions::ScalarFunction;
+use super::schema::{Column, DataType, Table, Value, View};
+use super::storage_engine::{ColumnStatistics, StorageEngine, TableStatistics};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, PartialEq, Eq, Hash, Clone)]
 pub struct Identifier(pub String);
@@ -29,20 +37,82 @@ pub enum QueryType {
     Insert,
     Update,
     Delete,
+    CreateTable,
+    DropTable,
+    AlterTable,
+    CreateView,
+    DropView,
 }
 
 #[derive(Debug, Clone)]
 pub struct QueryPlan {
     pub query_type: QueryType,
     pub table: Identifier,
-    pub projection: Option<Vec<Identifier>>,
-    pub condition: Option<WhereCondition>,
-    pub assignments: Option<Vec<(Identifier, String)>>,
-    pub insert_data: Option<(Vec<Identifier>, Vec<String>)>,
+    pub table_alias: Option<Identifier>,
+    pub joins: Option<Vec<Join>>,
+    pub distinct: bool,
+    pub projection: Option<Vec<SelectItem>>,
+    pub condition: Option<Expr>,
+    pub group_by: Option<Vec<Expr>>,
+    pub aggregates: Option<Vec<Expr>>,
+    pub having: Option<Expr>,
+    pub order_by: Option<Vec<OrderByItem>>,
+    pub limit: Option<usize>,
+    pub offset: Option<usize>,
+    pub assignments: Option<Vec<(Identifier, Expr)>>,
+    pub insert_data: Option<(Vec<Identifier>, Vec<Vec<Expr>>)>,
+    /// The query feeding INSERT ... SELECT, or defining a view
+    pub source: Option<Box<QueryPlan>>,
+    /// Plans of the views the query reads, materialized before it runs; a view
+    /// comes after the views it reads
+    pub views: Vec<(Identifier, QueryPlan)>,
+    /// Plans of the WITH queries that run before the query, in order
+    pub ctes: Vec<CtePlan>,
+    /// Plans of the SELECTs combined with the query by UNION, INTERSECT or EXCEPT
+    pub set_operations: Vec<SetOperationPlan>,
     pub estimated_cost: f64,
     pub execution_steps: Vec<ExecutionStep>,
 }
 
+/// A WITH query the planner chose to materialize rather than inline
+#[derive(Debug, Clone)]
+pub struct CtePlan {
+    pub name: Identifier,
+    /// Names given to the query's output columns, if any
+    pub columns: Vec<Identifier>,
+    pub plan: QueryPlan,
+    /// The plan of a recursive query's step, run once per iteration
+    pub recursive_step: Option<QueryPlan>,
+}
+
+/// A SELECT combined with the rows of the SELECTs before it
+#[derive(Debug, Clone)]
+pub struct SetOperationPlan {
+    pub operator: SetOperator,
+    pub all: bool,
+    pub plan: QueryPlan,
+}
+
+impl QueryPlan {
+    /// Every table the plan reads as `(table, qualifier)`, in join order
+    pub fn tables(&self) -> Vec<(&str, &str)> {
+        let mut tables = vec![(
+            self.table.0.as_str(),
+            self.table_alias.as_ref().unwrap_or(&self.table).0.as_str(),
+        )];
+        for join in self.joins.iter().flatten() {
+            tables.push((join.table.name.0.as_str(), join.table.qualifier()));
+        }
+        tables
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoinAlgorithm {
+    NestedLoop,
+    HashJoin,
+}
+
 #[derive(Debug, Clone)]
 pub enum ExecutionStep {
     TableScan {
@@ -50,25 +120,125 @@ pub enum ExecutionStep {
         estimated_rows: usize,
     },
     FilterRows {
-        condition: WhereCondition,
+        condition: Expr,
         estimated_selectivity: f64,
     },
+    NestedLoopJoin {
+        table: String,
+        kind: JoinKind,
+        condition: Option<Expr>,
+        outer_rows: usize,
+        inner_rows: usize,
+        estimated_rows: usize,
+    },
+    HashJoin {
+        table: String,
+        kind: JoinKind,
+        condition: Option<Expr>,
+        probe_rows: usize,
+        build_rows: usize,
+        estimated_rows: usize,
+    },
+    /// Run a subquery once, or once per outer row when it is correlated
+    Subquery {
+        query: String,
+        correlated: bool,
+        executions: usize,
+        estimated_rows: usize,
+        estimated_cost: f64,
+    },
+    /// Keep the rows whose operand is among the hashed results of an IN subquery
+    SemiJoin {
+        condition: Expr,
+        probe_rows: usize,
+        build_rows: usize,
+        estimated_rows: usize,
+    },
     ProjectColumns {
         columns: Vec<String>,
     },
-    InsertRow {
+    Aggregate {
+        group_by: Vec<Expr>,
+        aggregates: Vec<Expr>,
+        estimated_rows: usize,
+        estimated_groups: usize,
+    },
+    Distinct {
+        columns: Vec<String>,
+        estimated_rows: usize,
+        estimated_distinct: usize,
+    },
+    Sort {
+        keys: Vec<OrderByItem>,
+        estimated_rows: usize,
+    },
+    Limit {
+        limit: Option<usize>,
+        offset: usize,
+    },
+    InsertRows {
         table: String,
         columns: Vec<String>,
-        values: Vec<String>,
+        rows: usize,
+        on_conflict: Option<OnConflict>,
     },
     UpdateRows {
         table: String,
-        assignments: Vec<(String, String)>,
-        condition: Option<WhereCondition>,
+        assignments: Vec<(String, Expr)>,
+        condition: Option<Expr>,
     },
     DeleteRows {
         table: String,
-        condition: Option<WhereCondition>,
+        condition: Option<Expr>,
+    },
+    CreateTable {
+        table: String,
+        columns: Vec<String>,
+        primary_key: Option<String>,
+    },
+    DropTable {
+        table: String,
+    },
+    AlterTable {
+        table: String,
+        operation: AlterTableOperation,
+    },
+    /// Run a view's query and keep its rows for the statement that reads it
+    MaterializeView {
+        view: String,
+        estimated_rows: usize,
+        estimated_cost: f64,
+    },
+    CreateView {
+        view: String,
+    },
+    DropView {
+        view: String,
+    },
+    /// Run a WITH query once and keep its rows for the statement that reads it
+    MaterializeCte {
+        name: String,
+        estimated_rows: usize,
+        estimated_cost: f64,
+    },
+    /// Run a recursive WITH query: its first SELECT, then its step on the rows the
+    /// previous iteration added, until an iteration adds none
+    RecursiveCte {
+        name: String,
+        union_all: bool,
+        estimated_iterations: usize,
+        estimated_rows: usize,
+        estimated_cost: f64,
+    },
+    /// Run a combined SELECT and merge its rows with the rows so far, hashing them
+    /// unless UNION ALL only appends them
+    SetOperation {
+        operator: SetOperator,
+        all: bool,
+        left_rows: usize,
+        right_rows: usize,
+        estimated_rows: usize,
+        estimated_cost: f64,
     },
 }
 
@@ -79,6 +249,7 @@ pub struct QueryStatistics {
     pub insert_queries: u64,
     pub update_queries: u64,
     pub delete_queries: u64,
+    pub ddl_queries: u64,
     pub failed_queries: u64,
     pub average_execution_time: f64,
 }
@@ -91,6 +262,7 @@ impl Default for QueryStatistics {
             insert_queries: 0,
             update_queries: 0,
             delete_queries: 0,
+            ddl_queries: 0,
             failed_queries: 0,
             average_execution_time: 0.0,
         }
@@ -101,6 +273,8 @@ impl Default for QueryStatistics {
 pub struct QueryOptimizer {
     pub enable_optimizations: bool,
     pub statistics: QueryStatistics,
+    pub table_statistics: HashMap<String, TableStatistics>,
+    statistics_version: Option<u64>,
 }
 
 impl Default for QueryOptimizer {
@@ -108,6 +282,8 @@ impl Default for QueryOptimizer {
         QueryOptimizer {
             enable_optimizations: true,
             statistics: QueryStatistics::default(),
+            table_statistics: HashMap::new(),
+            statistics_version: None,
         }
     }
 }
@@ -123,66 +299,813 @@ impl QueryOptimizer {
         }
 
         // Simple optimization rules
+        self.optimize_joins(plan);
         self.optimize_where_clause(plan);
-        self.optimize_projection(plan);
+        self.optimize_aggregation(plan);
+        self.optimize_distinct(plan);
+        if plan.set_operations.is_empty() {
+            self.optimize_sort(plan);
+            self.optimize_limit(plan);
+            self.optimize_projection(plan);
+        } else {
+            // A compound SELECT sorts and limits the combined rows
+            self.optimize_projection(plan);
+            self.optimize_set_operations(plan);
+            self.optimize_sort(plan);
+            self.optimize_limit(plan);
+        }
         self.estimate_cost(plan);
     }
 
+    /// Rows in a table according to the latest statistics
+    fn table_rows(&self, table: &str) -> usize {
+        self.table_statistics
+            .get(table)
+            .map_or(1000, |stats| stats.row_count) // Default estimate without statistics
+    }
+
+    /// Pick the join operator: a hash join pays off once the inputs are large enough to
+    /// amortize building the hash table, but needs equality keys to probe with. The
+    /// executor applies the same rule to the actual input sizes.
+    pub fn choose_join_algorithm(left_rows: usize, right_rows: usize, has_equality_keys: bool) -> JoinAlgorithm {
+        const HASH_JOIN_MIN_PAIRS: usize = 100;
+
+        if has_equality_keys && left_rows.saturating_mul(right_rows) > HASH_JOIN_MIN_PAIRS {
+            JoinAlgorithm::HashJoin
+        } else {
+            JoinAlgorithm::NestedLoop
+        }
+    }
+
+    /// A WHERE conjunct `expr IN (SELECT ...)` whose subquery does not reference the outer
+    /// query runs as a semi-join: the subquery executes once and every row probes its
+    /// hashed results. The executor applies the same rule. `has_column` tells whether
+    /// a table defines a column.
+    pub fn semi_join_subquery<'e>(
+        conjunct: &'e Expr,
+        has_column: &dyn Fn(&TableReference, &str) -> bool,
+    ) -> Option<(&'e Expr, &'e SelectStatement)> {
+        match conjunct {
+            Expr::InSubquery { operand, subquery, negated: false }
+                if !operand.has_subqueries() && subquery.outer_references(has_column).is_empty() =>
+            {
+                Some((operand, subquery))
+            }
+            _ => None,
+        }
+    }
+
+    /// Inline the WITH queries that are cheaper to read in place than to materialize: a
+    /// query that only filters and picks columns of one table, read once as the only
+    /// table of a SELECT without subqueries, merges into that SELECT. The other WITH
+    /// queries run once before the statement. The executor applies the same rule.
+    pub fn inline_ctes(select: &SelectStatement) -> SelectStatement {
+        let mut select = select.clone();
+        loop {
+            let Some(mut with) = select.with.take() else {
+                return select;
+            };
+            let Some(position) = (0..with.queries.len()).find(|&position| Self::can_inline(&with, position, &select)) else {
+                select.with = Some(with);
+                return select;
+            };
+
+            let cte = with.queries.remove(position);
+            select.with = if with.queries.is_empty() { None } else { Some(with) };
+            select = Self::inline_cte(select, cte);
+        }
+    }
+
+    fn can_inline(with: &WithClause, position: usize, select: &SelectStatement) -> bool {
+        let cte = &with.queries[position];
+        let query = &cte.query;
+        let reads = |query: &SelectStatement, name: &str| query.read_tables().iter().any(|table| table == name);
+
+        // A plain filter over one table, whose columns keep their names
+        let simple = cte.recursive_step.is_none()
+            && cte.columns.is_empty()
+            && query.joins.is_empty()
+            && query.compound.is_empty()
+            && !query.distinct
+            && !query.is_aggregate()
+            && query.order_by.is_empty()
+            && query.limit.is_none()
+            && query.offset.is_none()
+            && query.expressions().all(|expr| !expr.has_subqueries());
+        let mut outputs = Vec::new();
+        for item in &query.projection {
+            match item {
+                SelectItem::Wildcard if query.projection.len() == 1 => {}
+                SelectItem::Expr { expr: Expr::Column(name), .. }
+                    if item.view_column_name().as_deref() == name.rsplit('.').next() =>
+                {
+                    outputs.push(item.view_column_name().unwrap_or_default());
+                }
+                _ => return false,
+            }
+        }
+        if !simple || outputs.iter().enumerate().any(|(i, name)| outputs[..i].contains(name)) {
+            return false;
+        }
+
+        // Its table must mean the same thing where the query is read
+        let table = &query.from.name.0;
+        let redefined = with.queries[position + 1..].iter().any(|other| other.name.0 == *table);
+        if redefined || (with.recursive && *table == cte.name.0) {
+            return false;
+        }
+
+        // Read once, as the only table of the SELECT, and only by columns it outputs
+        let read_elsewhere = with.queries[position + 1..].iter().any(|other| {
+            reads(&other.query, &cte.name.0)
+                || other.recursive_step.as_ref().is_some_and(|step| reads(&step.query, &cte.name.0))
+        });
+        let qualifier = format!("{}.", select.from.qualifier());
+        select.from.name == cte.name
+            && select.joins.is_empty()
+            && select.compound.is_empty()
+            && !read_elsewhere
+            && select.expressions().all(|expr| !expr.has_subqueries())
+            && (outputs.is_empty() || select.expressions().flat_map(Expr::referenced_columns).all(|name| {
+                outputs.iter().any(|output| name.strip_prefix(&qualifier).unwrap_or(name) == output)
+            }))
+    }
+
+    /// Read the WITH query's table in place of the query, under the same qualifier,
+    /// with the query's condition added to the SELECT's
+    fn inline_cte(select: SelectStatement, cte: CommonTableExpression) -> SelectStatement {
+        let unqualified = |expr: &Expr| -> Expr {
+            let rewritten: Result<Expr, std::convert::Infallible> = expr.rewrite(&mut |node| match node {
+                Expr::Column(name) => Ok(Some(Expr::Column(name.rsplit('.').next().unwrap_or(name).to_string()))),
+                _ => Ok(None),
+            });
+            match rewritten {
+                Ok(expr) => expr,
+                Err(never) => match never {},
+            }
+        };
+
+        let qualifier = Identifier::from(select.from.qualifier());
+        let table = cte.query.from.name;
+        let outputs: Vec<SelectItem> = cte.query.projection.iter()
+            .map(|item| match item {
+                SelectItem::Expr { expr, .. } => SelectItem::Expr { expr: unqualified(expr), alias: None },
+                SelectItem::Wildcard => SelectItem::Wildcard,
+            })
+            .collect();
+        let projection = select.projection.iter()
+            .flat_map(|item| match item {
+                SelectItem::Wildcard => outputs.clone(),
+                item => vec![item.clone()],
+            })
+            .collect();
+        let condition = cte.query.condition.as_ref().map(unqualified).into_iter().chain(select.condition.clone());
+
+        SelectStatement {
+            projection,
+            from: TableReference { alias: (qualifier != table).then_some(qualifier), name: table },
+            condition: Expr::conjunction(condition.collect()),
+            ..select
+        }
+    }
+
+    /// Whether the statistics know a column of the referenced table
+    fn has_column(&self, table: &TableReference, column: &str) -> bool {
+        self.table_statistics
+            .get(&table.name.0)
+            .is_some_and(|stats| stats.column_stats.contains_key(column))
+    }
+
+    /// Build the unoptimized plan of a SELECT
+    fn select_plan(&self, select: &SelectStatement) -> QueryPlan {
+        let steps = vec![
+            ExecutionStep::TableScan {
+                table: select.from.name.0.clone(),
+                estimated_rows: self.table_rows(&select.from.name.0),
+            }
+        ];
+        let is_aggregate = select.is_aggregate();
+        let set_operations = select.compound.iter()
+            .map(|operation| {
+                let mut plan = self.select_plan(&operation.query);
+                self.optimize_plan(&mut plan);
+                SetOperationPlan { operator: operation.operator, all: operation.all, plan }
+            })
+            .collect();
+
+        QueryPlan {
+            query_type: QueryType::Select,
+            table: select.from.name.clone(),
+            table_alias: select.from.alias.clone(),
+            joins: if select.joins.is_empty() { None } else { Some(select.joins.clone()) },
+            distinct: select.distinct,
+            projection: Some(select.projection.clone()),
+            condition: select.condition.clone(),
+            group_by: is_aggregate.then(|| select.group_by.clone()),
+            aggregates: is_aggregate.then(|| select.aggregates().into_iter().cloned().collect()),
+            having: select.having.clone(),
+            order_by: if select.order_by.is_empty() { None } else { Some(select.order_by.clone()) },
+            limit: select.limit,
+            offset: select.offset,
+            assignments: None,
+            insert_data: None,
+            source: None,
+            views: Vec::new(),
+            ctes: Vec::new(),
+            set_operations,
+            estimated_cost: 0.0,
+            execution_steps: steps,
+        }
+    }
+
+    /// Plan the subqueries of an expression evaluated over the rows produced so far.
+    /// Correlated subqueries run once per row.
+    fn optimize_subqueries(&self, plan: &mut QueryPlan, expr: &Expr) {
+        for subquery in expr.subqueries() {
+            let correlated = !subquery.outer_references(&|table, column| self.has_column(table, column)).is_empty();
+            let executions = match plan.query_type {
+                _ if !correlated => 1,
+                QueryType::Select => self.estimate_output_rows(plan),
+                _ => self.table_rows(&plan.table.0), // UPDATE and DELETE test every row
+            };
+            let (estimated_rows, cost) = self.estimate_subquery(subquery);
+            plan.execution_steps.push(ExecutionStep::Subquery {
+                query: subquery.to_string(),
+                correlated,
+                executions,
+                estimated_rows,
+                estimated_cost: cost * executions as f64,
+            });
+        }
+    }
+
+    /// Estimated result rows and cost of one execution of a subquery
+    fn estimate_subquery(&self, subquery: &SelectStatement) -> (usize, f64) {
+        let mut plan = self.select_plan(subquery);
+        self.optimize_plan(&mut plan);
+        (self.estimate_output_rows(&plan), plan.estimated_cost)
+    }
+
+    fn optimize_joins(&self, plan: &mut QueryPlan) {
+        // Joins run left to right on the output of the previous join
+        let Some(joins) = plan.joins.clone() else {
+            return;
+        };
+
+        for join in joins {
+            let left_rows = self.estimate_output_rows(plan);
+            let right_rows = self.table_rows(&join.table.name.0);
+            let estimated_rows = self.estimate_join_rows(plan, &join, left_rows, right_rows);
+            let has_equality_keys = join.condition.as_ref().is_some_and(|condition| {
+                condition.conjuncts().iter().any(|conjunct| matches!(
+                    conjunct,
+                    Expr::Binary { left, operator: BinaryOperator::Equal, right }
+                        if matches!((left.as_ref(), right.as_ref()), (Expr::Column(_), Expr::Column(_)))
+                ))
+            });
+
+            let table = join.table.name.0.clone();
+            plan.execution_steps.push(match Self::choose_join_algorithm(left_rows, right_rows, has_equality_keys) {
+                JoinAlgorithm::HashJoin => ExecutionStep::HashJoin {
+                    table,
+                    kind: join.kind,
+                    condition: join.condition,
+                    probe_rows: left_rows,
+                    build_rows: right_rows,
+                    estimated_rows,
+                },
+                JoinAlgorithm::NestedLoop => ExecutionStep::NestedLoopJoin {
+                    table,
+                    kind: join.kind,
+                    condition: join.condition,
+                    outer_rows: left_rows,
+                    inner_rows: right_rows,
+                    estimated_rows,
+                },
+            });
+        }
+    }
+
+    /// Estimate join output from the matching fraction of all row pairs; LEFT joins
+    /// keep every left row
+    fn estimate_join_rows(&self, plan: &QueryPlan, join: &Join, left_rows: usize, right_rows: usize) -> usize {
+        let pairs = left_rows as f64 * right_rows as f64;
+        let matched = match join.condition {
+            Some(ref condition) => pairs * self.join_selectivity(plan, condition),
+            None => pairs,
+        };
+
+        match join.kind {
+            JoinKind::Left => matched.max(left_rows as f64).ceil() as usize,
+            JoinKind::Inner | JoinKind::Cross => matched.ceil() as usize,
+        }
+    }
+
+    /// An equality between two columns matches one row in as many as the larger number
+    /// of distinct values; other join conditions use the filter estimates
+    fn join_selectivity(&self, plan: &QueryPlan, condition: &Expr) -> f64 {
+        condition.conjuncts().into_iter()
+            .map(|conjunct| match conjunct {
+                Expr::Binary { left, operator: BinaryOperator::Equal, right } => {
+                    match (left.as_ref(), right.as_ref()) {
+                        (Expr::Column(left), Expr::Column(right)) => {
+                            let distinct = [left, right].iter()
+                                .filter_map(|name| self.column_statistics(plan, name))
+                                .map(|(_, column)| column.unique_values)
+                                .max()
+                                .unwrap_or(10);
+                            1.0 / distinct.max(1) as f64
+                        }
+                        _ => self.estimate_selectivity(plan, conjunct),
+                    }
+                }
+                _ => self.estimate_selectivity(plan, conjunct),
+            })
+            .product()
+    }
+
+    /// Statistics of a (possibly qualified) column reference of the plan's tables
+    fn column_statistics(&self, plan: &QueryPlan, name: &str) -> Option<(&TableStatistics, &ColumnStatistics)> {
+        let (qualifier, column) = match name.split_once('.') {
+            Some((qualifier, column)) => (Some(qualifier), column),
+            None => (None, name),
+        };
+
+        plan.tables().into_iter()
+            .filter(|(_, table_qualifier)| qualifier.is_none_or(|qualifier| qualifier == *table_qualifier))
+            .find_map(|(table, _)| {
+                let stats = self.table_statistics.get(table)?;
+                Some((stats, stats.column_stats.get(column)?))
+            })
+    }
+
+    /// Statistics of a view estimated from its plan, so that queries reading the view
+    /// are planned like queries reading a table
+    fn view_statistics(&self, plan: &QueryPlan) -> TableStatistics {
+        let row_count = self.estimate_output_rows(plan);
+        let mut column_stats = HashMap::new();
+        for item in plan.projection.iter().flatten() {
+            match item {
+                SelectItem::Wildcard => {
+                    for (table, _) in plan.tables() {
+                        if let Some(stats) = self.table_statistics.get(table) {
+                            column_stats.extend(stats.column_stats.clone());
+                        }
+                    }
+                }
+                SelectItem::Expr { expr, .. } => {
+                    let stats = match expr {
+                        Expr::Column(name) => self.column_statistics(plan, name).map(|(_, stats)| stats.clone()),
+                        _ => None,
+                    };
+                    let stats = stats.unwrap_or(ColumnStatistics {
+                        unique_values: row_count,
+                        total_values: row_count,
+                        selectivity: 1.0,
+                    });
+                    column_stats.insert(item.view_column_name().unwrap_or_default(), stats);
+                }
+            }
+        }
+
+        TableStatistics { row_count, column_stats, last_updated: 0 }
+    }
+
+    /// Statistics of a WITH query of `row_count` rows, estimated like those of a view;
+    /// `columns` renames the outputs in order
+    fn cte_statistics(&self, plan: &QueryPlan, columns: &[Identifier], row_count: usize) -> TableStatistics {
+        let mut statistics = self.view_statistics(plan);
+        statistics.row_count = row_count;
+        if !columns.is_empty() {
+            let outputs: Option<Vec<String>> = plan.projection.iter().flatten().map(SelectItem::view_column_name).collect();
+            let outputs = outputs.unwrap_or_default();
+            statistics.column_stats = columns.iter()
+                .enumerate()
+                .map(|(position, column)| {
+                    let stats = outputs.get(position)
+                        .and_then(|output| statistics.column_stats.get(output).cloned())
+                        .unwrap_or(ColumnStatistics { unique_values: row_count, total_values: row_count, selectivity: 1.0 });
+                    (column.0.clone(), stats)
+                })
+                .collect();
+        }
+        statistics
+    }
+
     fn optimize_where_clause(&self, plan: &mut QueryPlan) {
         // Future: Add WHERE clause optimization logic
         // For now, just ensure the condition is properly structured
-        if let Some(ref condition) = plan.condition {
-            // Add index usage hints or condition reordering here
-            plan.execution_steps.push(ExecutionStep::FilterRows {
-                condition: condition.clone(),
-                estimated_selectivity: self.estimate_selectivity(condition),
+        if let Some(condition) = plan.condition.clone() {
+            // Uncorrelated IN subqueries become semi-joins ahead of the remaining filter
+            let mut remaining = Vec::new();
+            for conjunct in condition.conjuncts() {
+                let Some((operand, subquery)) = Self::semi_join_subquery(
+                    conjunct,
+                    &|table, column| self.has_column(table, column),
+                ) else {
+                    remaining.push(conjunct.clone());
+                    continue;
+                };
+
+                self.optimize_subqueries(plan, conjunct);
+                let probe_rows = self.estimate_output_rows(plan);
+                let build_rows = self.estimate_subquery(subquery).0;
+                // Each distinct subquery value matches its share of the operand's values
+                let selectivity = match operand {
+                    Expr::Column(name) => match self.column_statistics(plan, name) {
+                        Some((_, column)) if column.unique_values > 0 => {
+                            (build_rows as f64 / column.unique_values as f64).min(1.0)
+                        }
+                        _ => 0.5,
+                    },
+                    _ => 0.5,
+                };
+                plan.execution_steps.push(ExecutionStep::SemiJoin {
+                    condition: conjunct.clone(),
+                    probe_rows,
+                    build_rows,
+                    estimated_rows: (probe_rows as f64 * selectivity).ceil() as usize,
+                });
+            }
+
+            if let Some(condition) = Expr::conjunction(remaining) {
+                // Add index usage hints or condition reordering here
+                self.optimize_subqueries(plan, &condition);
+                let estimated_selectivity = self.estimate_selectivity(plan, &condition);
+                plan.execution_steps.push(ExecutionStep::FilterRows {
+                    condition,
+                    estimated_selectivity,
+                });
+            }
+        }
+    }
+
+    fn optimize_aggregation(&self, plan: &mut QueryPlan) {
+        // Hash aggregation runs on the filtered rows; HAVING filters the groups
+        if let (Some(group_by), Some(aggregates)) = (plan.group_by.clone(), plan.aggregates.clone()) {
+            let estimated_rows = self.estimate_output_rows(plan);
+            let estimated_groups = self.estimate_distinct(plan, &group_by, estimated_rows);
+            plan.execution_steps.push(ExecutionStep::Aggregate {
+                group_by,
+                aggregates,
+                estimated_rows,
+                estimated_groups,
+            });
+
+            if let Some(having) = plan.having.clone() {
+                self.optimize_subqueries(plan, &having);
+                let estimated_selectivity = self.estimate_selectivity(plan, &having);
+                plan.execution_steps.push(ExecutionStep::FilterRows {
+                    condition: having,
+                    estimated_selectivity,
+                });
+            }
+        }
+    }
+
+    fn optimize_distinct(&self, plan: &mut QueryPlan) {
+        // Deduplicate the projected rows before they are sorted and limited
+        if !plan.distinct {
+            return;
+        }
+        let Some(projection) = plan.projection.clone() else {
+            return;
+        };
+
+        let estimated_rows = self.estimate_output_rows(plan);
+        let expressions: Option<Vec<Expr>> = projection.iter()
+            .map(|item| item.expr().cloned())
+            .collect();
+        let estimated_distinct = match expressions {
+            Some(expressions) => self.estimate_distinct(plan, &expressions, estimated_rows),
+            None => estimated_rows, // Whole rows are rarely duplicated
+        };
+
+        plan.execution_steps.push(ExecutionStep::Distinct {
+            columns: projection.iter().map(|item| item.to_string()).collect(),
+            estimated_rows,
+            estimated_distinct,
+        });
+    }
+
+    /// Estimate the number of distinct combinations of the given expressions as the
+    /// product of each column's distinct values, bounded by the number of input rows
+    fn estimate_distinct(&self, plan: &QueryPlan, expressions: &[Expr], input_rows: usize) -> usize {
+        if expressions.is_empty() {
+            return 1;
+        }
+
+        let mut groups = 1.0;
+        for expr in expressions {
+            groups *= match expr {
+                Expr::Column(name) => match self.column_statistics(plan, name) {
+                    // NULLs form one extra group
+                    Some((stats, column)) if column.total_values < stats.row_count => column.unique_values as f64 + 1.0,
+                    Some((_, column)) => column.unique_values as f64,
+                    None => input_rows as f64,
+                },
+                _ => input_rows as f64 * 0.1, // Assume expressions repeat often
+            };
+        }
+        (groups.ceil() as usize).max(1).min(input_rows)
+    }
+
+    fn optimize_sort(&self, plan: &mut QueryPlan) {
+        // Sort the filtered rows before projecting so keys may use any column
+        if let Some(ref keys) = plan.order_by {
+            let estimated_rows = self.estimate_output_rows(plan);
+            plan.execution_steps.push(ExecutionStep::Sort {
+                keys: keys.clone(),
+                estimated_rows,
+            });
+        }
+    }
+
+    /// Merge the rows of each combined SELECT in turn. Without statistics on how the
+    /// SELECTs overlap, distinct rows are assumed to be a fixed share of their input.
+    fn optimize_set_operations(&self, plan: &mut QueryPlan) {
+        for operation in plan.set_operations.clone() {
+            let left_rows = self.estimate_output_rows(plan);
+            let right_rows = self.estimate_output_rows(&operation.plan);
+            let estimated_rows = match (operation.operator, operation.all) {
+                (SetOperator::Union, true) => left_rows + right_rows,
+                (SetOperator::Union, false) => left_rows + right_rows - left_rows.min(right_rows) / 2,
+                (SetOperator::Intersect, _) => left_rows.min(right_rows) / 2,
+                (SetOperator::Except, _) => left_rows / 2,
+            };
+            let hashed = match (operation.operator, operation.all) {
+                (SetOperator::Union, true) => 0.0,
+                _ => (left_rows + right_rows) as f64 * 0.05, // Hash every row once
+            };
+            plan.execution_steps.push(ExecutionStep::SetOperation {
+                operator: operation.operator,
+                all: operation.all,
+                left_rows,
+                right_rows,
+                estimated_rows,
+                estimated_cost: operation.plan.estimated_cost + hashed,
+            });
+        }
+    }
+
+    fn optimize_limit(&self, plan: &mut QueryPlan) {
+        if plan.limit.is_some() || plan.offset.is_some() {
+            plan.execution_steps.push(ExecutionStep::Limit {
+                limit: plan.limit,
+                offset: plan.offset.unwrap_or(0),
             });
         }
     }
 
+    /// Estimate how many rows reach the end of the steps planned so far
+    fn estimate_output_rows(&self, plan: &QueryPlan) -> usize {
+        let mut rows = 0.0;
+        for step in &plan.execution_steps {
+            match step {
+                ExecutionStep::TableScan { estimated_rows, .. } => rows = *estimated_rows as f64,
+                ExecutionStep::FilterRows { estimated_selectivity, .. } => rows *= estimated_selectivity,
+                ExecutionStep::NestedLoopJoin { estimated_rows, .. }
+                | ExecutionStep::HashJoin { estimated_rows, .. } => rows = *estimated_rows as f64,
+                ExecutionStep::Aggregate { estimated_groups, .. } => rows = *estimated_groups as f64,
+                ExecutionStep::Distinct { estimated_distinct, .. } => rows = *estimated_distinct as f64,
+                ExecutionStep::SemiJoin { estimated_rows, .. }
+                | ExecutionStep::SetOperation { estimated_rows, .. } => rows = *estimated_rows as f64,
+                ExecutionStep::Limit { limit: Some(limit), offset } => {
+                    rows = (rows - *offset as f64).max(0.0).min(*limit as f64)
+                }
+                _ => {}
+            }
+        }
+        rows.ceil() as usize
+    }
+
     fn optimize_projection(&self, plan: &mut QueryPlan) {
         // Optimize column projection
-        if let Some(ref projection) = plan.projection {
-            if projection.len() == 1 && projection[0].0 == "*" {
+        if let Some(projection) = plan.projection.clone() {
+            if projection.len() == 1 && projection[0] == SelectItem::Wildcard {
                 // SELECT * - no optimization needed
                 return;
             }
-            
+
+            for expr in projection.iter().filter_map(SelectItem::expr) {
+                self.optimize_subqueries(plan, expr);
+            }
             plan.execution_steps.push(ExecutionStep::ProjectColumns {
-                columns: projection.iter().map(|id| id.0.clone()).collect(),
+                columns: projection.iter().map(|item| item.to_string()).collect(),
             });
         }
     }
 
-    fn estimate_selectivity(&self, _condition: &WhereCondition) -> f64 {
-        // Simple selectivity estimation
-        // In a real database, this would use statistics
-        match _condition.operator.as_str() {
-            "=" => 0.1,    // Equality is usually selective
-            ">" | "<" => 0.3,  // Range queries are less selective
-            ">=" | "<=" => 0.4,
-            "!=" | "<>" => 0.9, // Not equal is usually not very selective
+    fn estimate_selectivity(&self, plan: &QueryPlan, condition: &Expr) -> f64 {
+        // Use column statistics where available, fixed guesses otherwise
+        if let Some(selectivity) = self.column_selectivity(plan, condition) {
+            return selectivity;
+        }
+
+        match condition {
+            Expr::Binary { left, operator: BinaryOperator::And, right } => {
+                // Assume independent predicates
+                self.estimate_selectivity(plan, left) * self.estimate_selectivity(plan, right)
+            }
+            Expr::Binary { left, operator: BinaryOperator::Or, right } => {
+                let left = self.estimate_selectivity(plan, left);
+                let right = self.estimate_selectivity(plan, right);
+                left + right - left * right
+            }
+            Expr::Unary { operator: UnaryOperator::Not, operand } => {
+                1.0 - self.estimate_selectivity(plan, operand)
+            }
+            Expr::IsNull { negated: false, .. } => 0.1, // Most columns are mostly populated
+            Expr::IsNull { negated: true, .. } => 0.9,
+            // Patterns usually match a small share of the values
+            Expr::Like { negated: false, .. } | Expr::Regexp { negated: false, .. } => 0.2,
+            Expr::Like { negated: true, .. } | Expr::Regexp { negated: true, .. } => 0.8,
+            // BETWEEN is one range rather than two independent comparisons
+            Expr::Between { operand, negated, .. } => {
+                let selectivity = self.range_selectivity(plan, operand, true);
+                if *negated { 1.0 - selectivity } else { selectivity }
+            }
+            Expr::InList { list, negated, .. } => {
+                let selectivity = (0.1 * list.len() as f64).min(0.9);
+                if *negated { 1.0 - selectivity } else { selectivity }
+            }
+            Expr::Binary { operator, .. } => match operator {
+                BinaryOperator::Equal => 0.1,    // Equality is usually selective
+                BinaryOperator::GreaterThan | BinaryOperator::LessThan => 0.3,  // Range queries are less selective
+                BinaryOperator::GreaterThanOrEqual | BinaryOperator::LessThanOrEqual => 0.4,
+                BinaryOperator::NotEqual => 0.9, // Not equal is usually not very selective
+                _ => 0.5, // Arithmetic used as a truth value
+            },
             _ => 0.5,
         }
     }
 
+    /// Fraction of rows holding any one value of a column: its non-NULL share spread over
+    /// its distinct values
+    fn equality_selectivity(&self, plan: &QueryPlan, name: &str) -> Option<f64> {
+        let (stats, column) = self.column_statistics(plan, name)
+            .filter(|(stats, column)| stats.row_count > 0 && column.unique_values > 0)?;
+        Some(column.total_values as f64 / stats.row_count as f64 / column.unique_values as f64)
+    }
+
+    /// Selectivity of a range over a column. Without value histograms a range bounded
+    /// on both sides counts as both one-sided guesses together, but never as more
+    /// selective than a single value.
+    fn range_selectivity(&self, plan: &QueryPlan, operand: &Expr, bounded: bool) -> f64 {
+        const ONE_SIDED: f64 = 0.3;
+
+        let estimate = if bounded { ONE_SIDED * ONE_SIDED } else { ONE_SIDED };
+        let equality = match operand {
+            Expr::Column(name) => self.equality_selectivity(plan, name),
+            _ => None,
+        };
+        equality.map_or(estimate, |equality| estimate.max(equality))
+    }
+
+    /// Selectivity of `column = literal`, `column IN (literals)`, `column IS [NOT] NULL`
+    /// and `column LIKE 'text'` from table statistics
+    fn column_selectivity(&self, plan: &QueryPlan, condition: &Expr) -> Option<f64> {
+        let column_stats = |name: &str| {
+            self.column_statistics(plan, name).filter(|(stats, _)| stats.row_count > 0)
+        };
+        let non_null = |stats: &TableStatistics, column: &ColumnStatistics| {
+            column.total_values as f64 / stats.row_count as f64
+        };
+
+        match condition {
+            Expr::Binary { left, operator: BinaryOperator::Equal, right } => {
+                match (left.as_ref(), right.as_ref()) {
+                    (Expr::Column(name), Expr::Literal(_) | Expr::Parameter(_))
+                    | (Expr::Literal(_) | Expr::Parameter(_), Expr::Column(name)) => {
+                        self.equality_selectivity(plan, name)
+                    }
+                    _ => None,
+                }
+            }
+            Expr::Like { operand, pattern, escape, case_insensitive: false, negated } => {
+                let (Expr::Column(name), Expr::Literal(Value::Text(pattern))) = (operand.as_ref(), pattern.as_ref()) else {
+                    return None;
+                };
+                // Without wildcards LIKE is an equality; a literal prefix is a range, as
+                // 'abc%' covers 'abc' <= value < 'abd'
+                let (prefix, exact) = LikePattern::new(pattern, *escape, false).ok()?.prefix();
+                let selectivity = if exact {
+                    self.equality_selectivity(plan, name)?
+                } else if !prefix.is_empty() {
+                    self.range_selectivity(plan, operand, true)
+                } else {
+                    return None;
+                };
+                Some(if *negated { 1.0 - selectivity } else { selectivity })
+            }
+            Expr::InList { operand, list, negated } => {
+                let Expr::Column(name) = operand.as_ref() else {
+                    return None;
+                };
+                let mut values = HashSet::new();
+                for item in list {
+                    match item {
+                        // NOT IN over a list holding NULL is never true
+                        Expr::Literal(Value::Null) if *negated => return Some(0.0),
+                        Expr::Literal(Value::Null) => {}
+                        Expr::Literal(value) => {
+                            values.insert(value.to_string());
+                        }
+                        _ => return None,
+                    }
+                }
+
+                // Each distinct listed value selects one value's share of the rows
+                let (stats, column) = column_stats(name)?;
+                let non_null = non_null(stats, column);
+                let selectivity = (self.equality_selectivity(plan, name)? * values.len() as f64).min(non_null);
+                Some(if *negated { non_null - selectivity } else { selectivity })
+            }
+            Expr::IsNull { operand, negated } => match operand.as_ref() {
+                Expr::Column(name) => {
+                    let (stats, column) = column_stats(name)?;
+                    let non_null = non_null(stats, column);
+                    Some(if *negated { non_null } else { 1.0 - non_null })
+                }
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
     fn estimate_cost(&self, plan: &mut QueryPlan) {
         let mut cost = 0.0;
 
+        // Without a sort the scan stops as soon as the limit is satisfied; a compound
+        // SELECT limits the combined rows, so it reads all of them
+        let has_sort = plan.execution_steps.iter()
+            .any(|step| matches!(step, ExecutionStep::Sort { .. } | ExecutionStep::SetOperation { .. }));
+        let scan_fraction = match plan.execution_steps.iter().find_map(|step| match step {
+            ExecutionStep::Limit { limit: Some(limit), offset } if !has_sort => Some(limit + offset),
+            _ => None,
+        }) {
+            Some(needed) => {
+                let matching = self.estimate_output_rows(&QueryPlan {
+                    execution_steps: plan.execution_steps.iter()
+                        .filter(|step| !matches!(step, ExecutionStep::Limit { .. }))
+                        .cloned()
+                        .collect(),
+                    ..plan.clone()
+                });
+                (needed as f64 / matching.max(1) as f64).min(1.0)
+            }
+            None => 1.0,
+        };
+
         for step in &plan.execution_steps {
             cost += match step {
                 ExecutionStep::TableScan { estimated_rows, .. } => {
-                    *estimated_rows as f64 * 0.1 // Base cost per row scan
+                    *estimated_rows as f64 * 0.1 * scan_fraction // Base cost per row scan
                 }
                 ExecutionStep::FilterRows { estimated_selectivity, .. } => {
                     100.0 * (1.0 - estimated_selectivity) // Cost increases with lower selectivity
                 }
+                ExecutionStep::NestedLoopJoin { outer_rows, inner_rows, .. } => {
+                    // Compare every outer row with every inner row
+                    *inner_rows as f64 * 0.1 + *outer_rows as f64 * *inner_rows as f64 * 0.01
+                }
+                ExecutionStep::HashJoin { probe_rows, build_rows, estimated_rows, .. } => {
+                    // Build a hash table once, then probe it with every outer row
+                    *build_rows as f64 * 0.15 + *probe_rows as f64 * 0.05 + *estimated_rows as f64 * 0.02
+                }
+                ExecutionStep::Subquery { estimated_cost, .. }
+                | ExecutionStep::MaterializeView { estimated_cost, .. }
+                | ExecutionStep::MaterializeCte { estimated_cost, .. }
+                | ExecutionStep::RecursiveCte { estimated_cost, .. }
+                | ExecutionStep::SetOperation { estimated_cost, .. } => *estimated_cost,
+                ExecutionStep::SemiJoin { probe_rows, build_rows, .. } => {
+                    // Hash the subquery results once, then probe with every row
+                    *build_rows as f64 * 0.15 + *probe_rows as f64 * 0.05
+                }
                 ExecutionStep::ProjectColumns { columns } => {
                     columns.len() as f64 * 0.5 // Cost per column projection
                 }
-                ExecutionStep::InsertRow { .. } => 50.0, // Fixed cost for insert
+                ExecutionStep::Aggregate { aggregates, estimated_rows, estimated_groups, .. } => {
+                    // Hash every input row, then update each aggregate of its group
+                    *estimated_rows as f64 * (0.05 + aggregates.len() as f64 * 0.02)
+                        + *estimated_groups as f64 * 0.1
+                }
+                ExecutionStep::Distinct { estimated_rows, .. } => {
+                    *estimated_rows as f64 * 0.05 // Hash every projected row once
+                }
+                ExecutionStep::Sort { keys, estimated_rows } => {
+                    // Comparison sort: n log n comparisons, each evaluating every key
+                    let rows = (*estimated_rows).max(2) as f64;
+                    rows * rows.log2() * keys.len() as f64 * 0.01
+                }
+                ExecutionStep::Limit { .. } => 1.0, // Counting rows is nearly free
+                // Fixed cost for the single write, plus validating each row
+                ExecutionStep::InsertRows { rows, .. } => 50.0 + *rows as f64,
                 ExecutionStep::UpdateRows { .. } => 75.0, // Fixed cost for update
                 ExecutionStep::DeleteRows { .. } => 25.0, // Fixed cost for delete
+                ExecutionStep::CreateTable { .. } => 10.0, // Fixed cost for schema changes
+                ExecutionStep::DropTable { .. } | ExecutionStep::CreateView { .. } | ExecutionStep::DropView { .. } => 10.0,
+                ExecutionStep::AlterTable { .. } => 75.0, // Column rewrites touch every row
             };
         }
 
@@ -202,6 +1125,9 @@ impl QueryOptimizer {
             QueryType::Insert => self.statistics.insert_queries += 1,
             QueryType::Update => self.statistics.update_queries += 1,
             QueryType::Delete => self.statistics.delete_queries += 1,
+            QueryType::CreateTable | QueryType::DropTable | QueryType::AlterTable | QueryType::CreateView | QueryType::DropView => {
+                self.statistics.ddl_queries += 1
+            }
         }
 
         // Update average execution time
@@ -214,79 +1140,328 @@ impl QueryOptimizer {
 
 pub struct QueryPlanner {
     pub optimizer: QueryOptimizer,
+    /// Validated plans of prepared statements by statement text, for the current statistics
+    cache: QueryCache,
+    /// View definitions as of the last statistics refresh
+    views: HashMap<String, View>,
 }
 
 impl QueryPlanner {
     pub fn new() -> Self {
         QueryPlanner {
             optimizer: QueryOptimizer::new(),
+            cache: QueryCache::new(100),
+            views: HashMap::new(),
         }
     }
 
-    pub fn plan(&mut self, ast: &ASTNode) -> Result<QueryPlan, PlanningError> {
-        let mut plan = match ast {
-            ASTNode::SelectStatement { projection, table, condition } => {
-                let mut steps = vec![
-                    ExecutionStep::TableScan {
-                        table: table.0.clone(),
-                        estimated_rows: 1000, // Default estimate
-                    }
-                ];
-
-                QueryPlan {
-                    query_type: QueryType::Select,
-                    table: table.clone(),
-                    projection: Some(projection.clone()),
-                    condition: condition.clone(),
-                    assignments: None,
-                    insert_data: None,
-                    estimated_cost: 0.0,
-                    execution_steps: steps,
-                }
-            }
-            ASTNode::InsertStatement { table, columns, values } => {
-                let steps = vec![
-                    ExecutionStep::InsertRow {
-                        table: table.0.clone(),
-                        columns: columns.iter().map(|id| id.0.clone()).collect(),
-                        values: values.clone(),
-                    }
-                ];
+    /// Parse, plan and validate a statement with `?` or `$n` placeholders once, so it can
+    /// be executed many times. Preparing the same text again reuses the cached plan until
+    /// the database changes; only plans that validate are cached.
+    pub fn prepare(&mut self, sql: &str, storage: &StorageEngine) -> Result<PreparedStatement, PlanningError> {
+        let sql = sql.trim();
+        let ast = Parser::parse(sql).map_err(PlanningError::InvalidQuery)?;
+        self.refresh_statistics(storage);
+        let (plan, cached) = match self.cache.get(sql) {
+            Some(plan) => (plan.clone(), true),
+            None => (self.plan(&ast)?, false),
+        };
 
-                QueryPlan {
-                    query_type: QueryType::Insert,
-                    table: table.clone(),
-                    projection: None,
-                    condition: None,
-                    assignments: None,
-                    insert_data: Some((columns.clone(), values.clone())),
-                    estimated_cost: 0.0,
-                    execution_steps: steps,
-                }
-            }
-            ASTNode::UpdateStatement { table, assignments, condition } => {
-                let steps = vec![
-                    ExecutionStep::UpdateRows {
-                        table: table.0.clone(),
-                        assignments: assignments.iter()
-                            .map(|(id, val)| (id.0.clone(), val.clone()))
-                            .collect(),
-                        condition: condition.clone(),
-                    }
-                ];
+        // Queries must name existing tables; schema changes may create them
+        let reads_table = matches!(
+            plan.query_type,
+            QueryType::Select | QueryType::Insert | QueryType::Update | QueryType::Delete | QueryType::CreateView
+        );
+        let exists = storage.tables.contains_key(&plan.table.0) || storage.views.contains_key(&plan.table.0);
+        let columns = if reads_table || exists {
+            self.validate(&plan, &storage.tables)?
+        } else {
+            Vec::new()
+        };
+        if !cached {
+            self.cache.put(sql.to_string(), plan.clone());
+        }
+        let parameter_types = parameter_types(&ast, &columns);
+        let version = storage.metadata.total_operations;
+        Ok(PreparedStatement { sql: sql.to_string(), ast, plan, parameter_types, version })
+    }
+
+    /// Prepare a statement again if the storage changed since it was prepared, as the
+    /// tables it uses may have been altered or dropped. Fails if it no longer validates.
+    pub fn refresh_prepared(&mut self, statement: &mut PreparedStatement, storage: &StorageEngine) -> Result<(), PlanningError> {
+        if statement.version != storage.metadata.total_operations {
+            *statement = self.prepare(&statement.sql, storage)?;
+        }
+        Ok(())
+    }
+
+    /// Refresh the table statistics used for cardinality estimates, and the view
+    /// definitions, if the database changed. Cached plans were chosen with the old
+    /// statistics, so they are dropped.
+    pub fn refresh_statistics(&mut self, storage: &StorageEngine) {
+        let version = storage.metadata.total_operations;
+        if self.optimizer.statistics_version == Some(version) {
+            return;
+        }
+        self.cache.clear();
+
+        self.optimizer.table_statistics = storage.tables.keys()
+            .filter_map(|name| storage.get_table_stats(name).map(|stats| (name.clone(), stats)))
+            .collect();
+        self.views = storage.views.clone();
+        self.optimizer.statistics_version = Some(version);
+    }
+
+    /// Plan the views a statement reads, directly or through other views, so that each
+    /// view is planned after the views it reads. Their statistics are estimated from
+    /// the plans.
+    fn plan_views(&mut self, ast: &ASTNode) -> Result<Vec<(Identifier, QueryPlan)>, PlanningError> {
+        let mut planned = Vec::new();
+        for name in ast.read_tables() {
+            self.plan_view(&name, &mut Vec::new(), &mut planned)?;
+        }
+        Ok(planned)
+    }
+
+    fn plan_view(
+        &mut self,
+        name: &str,
+        reading: &mut Vec<String>,
+        planned: &mut Vec<(Identifier, QueryPlan)>,
+    ) -> Result<(), PlanningError> {
+        let Some(view) = self.views.get(name) else {
+            return Ok(());
+        };
+        if planned.iter().any(|(view, _)| view.0 == name) {
+            return Ok(());
+        }
+        if reading.iter().any(|view| view == name) {
+            return Err(PlanningError::InvalidQuery(format!("View '{}' reads itself", name)));
+        }
+
+        let select = Parser::parse_select(&view.definition)
+            .map_err(|e| PlanningError::InvalidQuery(format!("View '{}' cannot be read: {}", name, e)))?;
+        reading.push(name.to_string());
+        for table in ASTNode::SelectStatement(select.clone()).read_tables() {
+            self.plan_view(&table, reading, planned)?;
+        }
+        reading.pop();
+
+        let mut plan = self.plan_select(&select)?;
+        self.optimizer.optimize_plan(&mut plan);
+        let statistics = self.optimizer.view_statistics(&plan);
+        self.optimizer.table_statistics.insert(name.to_string(), statistics);
+        planned.push((Identifier::from(name), plan));
+        Ok(())
+    }
+
+    /// Plan a SELECT and the WITH queries it does not inline
+    fn plan_select(&mut self, select: &SelectStatement) -> Result<QueryPlan, PlanningError> {
+        let queries = select.with.iter().flat_map(|with| with.queries.iter()).collect::<Vec<_>>();
+        for (position, cte) in queries.iter().enumerate() {
+            if queries[..position].iter().any(|other| other.name == cte.name) {
+                return Err(PlanningError::InvalidQuery(format!("WITH query name '{}' is used more than once", cte.name)));
+            }
+        }
+
+        let select = QueryOptimizer::inline_ctes(select);
+        let (ctes, steps) = match select.with {
+            Some(ref with) => self.plan_ctes(with)?,
+            None => (Vec::new(), Vec::new()),
+        };
+        let mut plan = self.optimizer.select_plan(&select);
+        plan.execution_steps.splice(0..0, steps);
+        plan.ctes = ctes;
+        Ok(plan)
+    }
+
+    /// Plan the WITH queries in order, each reading those before it. Their statistics
+    /// are estimated from their plans for the rest of the statement; a recursive query
+    /// is assumed to iterate a fixed number of times.
+    fn plan_ctes(&mut self, with: &WithClause) -> Result<(Vec<CtePlan>, Vec<ExecutionStep>), PlanningError> {
+        const ESTIMATED_ITERATIONS: usize = 10;
+
+        let mut planned: Vec<CtePlan> = Vec::new();
+        let mut steps = Vec::new();
+        for cte in &with.queries {
+            let name = &cte.name.0;
+            let reads_itself = |query: &SelectStatement| query.read_tables().contains(name);
+            match cte.recursive_step {
+                Some(_) if reads_itself(&cte.query) => {
+                    return Err(PlanningError::InvalidQuery(
+                        format!("Recursive query '{}' cannot read itself before UNION", name)
+                    ));
+                }
+                Some(ref step) => {
+                    let references = step.query.tables().filter(|table| &table.name.0 == name).count();
+                    let in_subqueries = step.query.expressions()
+                        .flat_map(Expr::subqueries)
+                        .any(&reads_itself);
+                    if references != 1 || in_subqueries {
+                        return Err(PlanningError::InvalidQuery(
+                            format!("The step of recursive query '{}' must read it exactly once, in FROM or JOIN", name)
+                        ));
+                    }
+                    if step.query.is_aggregate() {
+                        return Err(PlanningError::InvalidQuery(
+                            format!("The step of recursive query '{}' cannot use aggregate functions", name)
+                        ));
+                    }
+                    // These would apply to the first SELECT alone; the statement reading
+                    // the query can sort or limit all of its rows instead
+                    if !cte.query.order_by.is_empty() || cte.query.limit.is_some() || cte.query.offset.is_some() {
+                        return Err(PlanningError::InvalidQuery(
+                            format!("Recursive query '{}' cannot use ORDER BY, LIMIT or OFFSET", name)
+                        ));
+                    }
+                }
+                None if with.recursive && reads_itself(&cte.query) => {
+                    return Err(PlanningError::InvalidQuery(
+                        format!("Recursive query '{}' needs a UNION [ALL] step to read itself", name)
+                    ));
+                }
+                None => {}
+            }
+
+            let mut plan = self.optimizer.select_plan(&cte.query);
+            self.optimizer.optimize_plan(&mut plan);
+            let mut estimated_rows = self.optimizer.estimate_output_rows(&plan);
+            let mut estimated_cost = plan.estimated_cost;
+            let statistics = self.optimizer.cte_statistics(&plan, &cte.columns, estimated_rows);
+            self.optimizer.table_statistics.insert(name.clone(), statistics);
+
+            // Each iteration of the step reads about as many rows as the first SELECT returned
+            let recursive_step = match cte.recursive_step {
+                Some(ref step) => {
+                    let mut step_plan = self.optimizer.select_plan(&step.query);
+                    self.optimizer.optimize_plan(&mut step_plan);
+                    estimated_rows += self.optimizer.estimate_output_rows(&step_plan) * ESTIMATED_ITERATIONS;
+                    estimated_cost += step_plan.estimated_cost * ESTIMATED_ITERATIONS as f64;
+                    let statistics = self.optimizer.cte_statistics(&plan, &cte.columns, estimated_rows);
+                    self.optimizer.table_statistics.insert(name.clone(), statistics);
+                    steps.push(ExecutionStep::RecursiveCte {
+                        name: name.clone(),
+                        union_all: step.all,
+                        estimated_iterations: ESTIMATED_ITERATIONS,
+                        estimated_rows,
+                        estimated_cost,
+                    });
+                    Some(step_plan)
+                }
+                None => {
+                    steps.push(ExecutionStep::MaterializeCte { name: name.clone(), estimated_rows, estimated_cost });
+                    None
+                }
+            };
+            planned.push(CtePlan { name: cte.name.clone(), columns: cte.columns.clone(), plan, recursive_step });
+        }
+        Ok((planned, steps))
+    }
+
+    /// Plan a statement. WITH queries only exist for the statement, so the statistics
+    /// estimated for them are dropped again afterwards.
+    pub fn plan(&mut self, ast: &ASTNode) -> Result<QueryPlan, PlanningError> {
+        let statistics = self.optimizer.table_statistics.clone();
+        let plan = self.plan_statement(ast);
+        self.optimizer.table_statistics = statistics;
+        plan
+    }
+
+    fn plan_statement(&mut self, ast: &ASTNode) -> Result<QueryPlan, PlanningError> {
+        // Schema changes make cached plans stale
+        if matches!(
+            ast,
+            ASTNode::CreateTableStatement { .. } | ASTNode::DropTableStatement { .. } | ASTNode::AlterTableStatement { .. }
+                | ASTNode::CreateViewStatement { .. } | ASTNode::DropViewStatement { .. }
+        ) {
+            self.cache.clear();
+        }
+
+        let views = self.plan_views(ast)?;
+        let mut plan = match ast {
+            ASTNode::SelectStatement(select) => self.plan_select(select)?,
+            ASTNode::InsertStatement { table, columns, source, on_conflict, returning } => {
+                // INSERT ... SELECT runs the query's plan first
+                let (values, source) = match source {
+                    InsertSource::Values(values) => (values.clone(), None),
+                    InsertSource::Select(select) => {
+                        let mut source = self.plan_select(select)?;
+                        self.optimizer.optimize_plan(&mut source);
+                        (Vec::new(), Some(Box::new(source)))
+                    }
+                };
+                let mut steps = source.as_ref().map_or_else(Vec::new, |source| source.execution_steps.clone());
+                steps.push(ExecutionStep::InsertRows {
+                    table: table.0.clone(),
+                    columns: columns.iter().map(|id| id.0.clone()).collect(),
+                    rows: source.as_ref().map_or(values.len(), |source| self.optimizer.estimate_output_rows(source)),
+                    on_conflict: on_conflict.clone(),
+                });
+                let assignments = on_conflict.as_ref().and_then(|on_conflict| match on_conflict.action {
+                    ConflictAction::Update(ref assignments) => Some(assignments.clone()),
+                    ConflictAction::Nothing => None,
+                });
+
+                QueryPlan {
+                    query_type: QueryType::Insert,
+                    table: table.clone(),
+                    table_alias: None,
+                    joins: None,
+                    distinct: false,
+                    projection: returning.clone(),
+                    condition: None,
+                    group_by: None,
+                    aggregates: None,
+                    having: None,
+                    order_by: None,
+                    limit: None,
+                    offset: None,
+                    assignments,
+                    insert_data: Some((columns.clone(), values)),
+                    source,
+                    views: Vec::new(),
+                    ctes: Vec::new(),
+                    set_operations: Vec::new(),
+                    estimated_cost: 0.0,
+                    execution_steps: steps,
+                }
+            }
+            ASTNode::UpdateStatement { table, assignments, condition, returning } => {
+                let steps = vec![
+                    ExecutionStep::UpdateRows {
+                        table: table.0.clone(),
+                        assignments: assignments.iter()
+                            .map(|(id, expr)| (id.0.clone(), expr.clone()))
+                            .collect(),
+                        condition: condition.clone(),
+                    }
+                ];
 
                 QueryPlan {
                     query_type: QueryType::Update,
                     table: table.clone(),
-                    projection: None,
+                    table_alias: None,
+                    joins: None,
+                    distinct: false,
+                    projection: returning.clone(),
                     condition: condition.clone(),
+                    group_by: None,
+                    aggregates: None,
+                    having: None,
+                    order_by: None,
+                    limit: None,
+                    offset: None,
                     assignments: Some(assignments.clone()),
                     insert_data: None,
+                    source: None,
+                    views: Vec::new(),
+                    ctes: Vec::new(),
+                    set_operations: Vec::new(),
                     estimated_cost: 0.0,
                     execution_steps: steps,
                 }
             }
-            ASTNode::DeleteStatement { table, condition } => {
+            ASTNode::DeleteStatement { table, condition, returning } => {
                 let steps = vec![
                     ExecutionStep::DeleteRows {
                         table: table.0.clone(),
@@ -297,10 +1472,191 @@ impl QueryPlanner {
                 QueryPlan {
                     query_type: QueryType::Delete,
                     table: table.clone(),
-                    projection: None,
+                    table_alias: None,
+                    joins: None,
+                    distinct: false,
+                    projection: returning.clone(),
                     condition: condition.clone(),
+                    group_by: None,
+                    aggregates: None,
+                    having: None,
+                    order_by: None,
+                    limit: None,
+                    offset: None,
                     assignments: None,
                     insert_data: None,
+                    source: None,
+                    views: Vec::new(),
+                    ctes: Vec::new(),
+                    set_operations: Vec::new(),
+                    estimated_cost: 0.0,
+                    execution_steps: steps,
+                }
+            }
+            ASTNode::CreateTableStatement { table, columns, constraints, .. } => {
+                column_schema(columns)?;
+                let primary_key = primary_key_column(columns, constraints)?;
+                let steps = vec![
+                    ExecutionStep::CreateTable {
+                        table: table.0.clone(),
+                        columns: columns.iter().map(|column| column.name.0.clone()).collect(),
+                        primary_key: primary_key.map(|id| id.0.clone()),
+                    }
+                ];
+
+                QueryPlan {
+                    query_type: QueryType::CreateTable,
+                    table: table.clone(),
+                    table_alias: None,
+                    joins: None,
+                    distinct: false,
+                    projection: None,
+                    condition: None,
+                    group_by: None,
+                    aggregates: None,
+                    having: None,
+                    order_by: None,
+                    limit: None,
+                    offset: None,
+                    assignments: None,
+                    insert_data: None,
+                    source: None,
+                    views: Vec::new(),
+                    ctes: Vec::new(),
+                    set_operations: Vec::new(),
+                    estimated_cost: 0.0,
+                    execution_steps: steps,
+                }
+            }
+            ASTNode::DropTableStatement { table, .. } => {
+                let steps = vec![
+                    ExecutionStep::DropTable {
+                        table: table.0.clone(),
+                    }
+                ];
+
+                QueryPlan {
+                    query_type: QueryType::DropTable,
+                    table: table.clone(),
+                    table_alias: None,
+                    joins: None,
+                    distinct: false,
+                    projection: None,
+                    condition: None,
+                    group_by: None,
+                    aggregates: None,
+                    having: None,
+                    order_by: None,
+                    limit: None,
+                    offset: None,
+                    assignments: None,
+                    insert_data: None,
+                    source: None,
+                    views: Vec::new(),
+                    ctes: Vec::new(),
+                    set_operations: Vec::new(),
+                    estimated_cost: 0.0,
+                    execution_steps: steps,
+                }
+            }
+            ASTNode::AlterTableStatement { table, operation } => {
+                let steps = vec![
+                    ExecutionStep::AlterTable {
+                        table: table.0.clone(),
+                        operation: operation.clone(),
+                    }
+                ];
+
+                QueryPlan {
+                    query_type: QueryType::AlterTable,
+                    table: table.clone(),
+                    table_alias: None,
+                    joins: None,
+                    distinct: false,
+                    projection: None,
+                    condition: None,
+                    group_by: None,
+                    aggregates: None,
+                    having: None,
+                    order_by: None,
+                    limit: None,
+                    offset: None,
+                    assignments: None,
+                    insert_data: None,
+                    source: None,
+                    views: Vec::new(),
+                    ctes: Vec::new(),
+                    set_operations: Vec::new(),
+                    estimated_cost: 0.0,
+                    execution_steps: steps,
+                }
+            }
+            ASTNode::CreateViewStatement { view, query, .. } => {
+                if ast.parameter_count() > 0 {
+                    return Err(PlanningError::InvalidQuery("View definitions cannot use parameters".to_string()));
+                }
+                if views.iter().any(|(name, _)| name == view) {
+                    return Err(PlanningError::InvalidQuery(format!("View '{}' cannot read itself", view)));
+                }
+                let mut source = self.plan_select(query)?;
+                self.optimizer.optimize_plan(&mut source);
+                let steps = vec![
+                    ExecutionStep::CreateView {
+                        view: view.0.clone(),
+                    }
+                ];
+
+                QueryPlan {
+                    query_type: QueryType::CreateView,
+                    table: view.clone(),
+                    table_alias: None,
+                    joins: None,
+                    distinct: false,
+                    projection: None,
+                    condition: None,
+                    group_by: None,
+                    aggregates: None,
+                    having: None,
+                    order_by: None,
+                    limit: None,
+                    offset: None,
+                    assignments: None,
+                    insert_data: None,
+                    source: Some(Box::new(source)),
+                    views: Vec::new(),
+                    ctes: Vec::new(),
+                    set_operations: Vec::new(),
+                    estimated_cost: 0.0,
+                    execution_steps: steps,
+                }
+            }
+            ASTNode::DropViewStatement { view, .. } => {
+                let steps = vec![
+                    ExecutionStep::DropView {
+                        view: view.0.clone(),
+                    }
+                ];
+
+                QueryPlan {
+                    query_type: QueryType::DropView,
+                    table: view.clone(),
+                    table_alias: None,
+                    joins: None,
+                    distinct: false,
+                    projection: None,
+                    condition: None,
+                    group_by: None,
+                    aggregates: None,
+                    having: None,
+                    order_by: None,
+                    limit: None,
+                    offset: None,
+                    assignments: None,
+                    insert_data: None,
+                    source: None,
+                    views: Vec::new(),
+                    ctes: Vec::new(),
+                    set_operations: Vec::new(),
                     estimated_cost: 0.0,
                     execution_steps: steps,
                 }
@@ -310,55 +1666,364 @@ impl QueryPlanner {
             }
         };
 
+        // Each view the statement reads is materialized once, before the statement runs
+        let materialize = views.iter().map(|(view, view_plan)| ExecutionStep::MaterializeView {
+            view: view.0.clone(),
+            estimated_rows: self.optimizer.estimate_output_rows(view_plan),
+            estimated_cost: view_plan.estimated_cost,
+        });
+        plan.execution_steps.splice(0..0, materialize);
+        plan.views = views;
+
         // Apply optimizations
         self.optimizer.optimize_plan(&mut plan);
 
         Ok(plan)
     }
 
-    pub fn validate_plan(&self, plan: &QueryPlan, table_exists: bool, columns: &[String]) -> Result<(), PlanningError> {
+    /// Validate a plan against the database schema, including the query feeding INSERT ...
+    /// SELECT or defining a view, and return the columns the plan can reference. The
+    /// views the plan reads are validated first and resolve to the columns they output.
+    pub fn validate(&self, plan: &QueryPlan, tables: &HashMap<String, Table>) -> Result<Vec<Column>, PlanningError> {
+        let mut views = HashMap::new();
+        for (name, view_plan) in &plan.views {
+            let columns = self.validate_with_views(view_plan, tables, &views)?;
+            let columns = query_columns(view_plan, &columns);
+            check_unique_columns(&columns, "View")?;
+            views.insert(name.0.clone(), Table { columns, rows: HashMap::new(), primary_key: None });
+        }
+        self.validate_with_views(plan, tables, &views)
+    }
+
+    /// Validate the WITH queries of a plan in order, and return the views together with
+    /// the columns of the WITH queries, which hide tables and views of the same name
+    fn validate_ctes(
+        &self,
+        plan: &QueryPlan,
+        tables: &HashMap<String, Table>,
+        views: &HashMap<String, Table>,
+    ) -> Result<HashMap<String, Table>, PlanningError> {
+        let mut scope = views.clone();
+        for cte in &plan.ctes {
+            let columns = self.validate_with_views(&cte.plan, tables, &scope)?;
+            let mut columns = query_columns(&cte.plan, &columns);
+            if !cte.columns.is_empty() {
+                if cte.columns.len() != columns.len() {
+                    return Err(PlanningError::InvalidQuery(format!(
+                        "WITH query '{}' names {} columns but its query returns {}",
+                        cte.name, cte.columns.len(), columns.len()
+                    )));
+                }
+                for (column, name) in columns.iter_mut().zip(&cte.columns) {
+                    column.name = name.0.clone();
+                }
+            }
+            check_unique_columns(&columns, &format!("WITH query '{}'", cte.name))?;
+            let width = columns.len();
+            scope.insert(cte.name.0.clone(), Table { columns, rows: HashMap::new(), primary_key: None });
+
+            if let Some(ref step) = cte.recursive_step {
+                let columns = self.validate_with_views(step, tables, &scope)?;
+                let step_width = query_columns(step, &columns).len();
+                if step_width != width {
+                    return Err(PlanningError::InvalidQuery(format!(
+                        "The step of recursive query '{}' returns {} columns but its first SELECT returns {}",
+                        cte.name, step_width, width
+                    )));
+                }
+            }
+        }
+        Ok(scope)
+    }
+
+    fn validate_with_views(
+        &self,
+        plan: &QueryPlan,
+        tables: &HashMap<String, Table>,
+        views: &HashMap<String, Table>,
+    ) -> Result<Vec<Column>, PlanningError> {
+        let scope;
+        let views = if plan.ctes.is_empty() {
+            views
+        } else {
+            scope = self.validate_ctes(plan, tables, views)?;
+            &scope
+        };
+
+        match plan.query_type {
+            QueryType::CreateView => {
+                // The query must be valid and name each of its columns once
+                let source = plan.source.as_deref()
+                    .ok_or_else(|| PlanningError::InvalidQuery("View has no query".to_string()))?;
+                let columns = self.validate_with_views(source, tables, views)?;
+                check_unique_columns(&query_columns(source, &columns), "View")?;
+                return Ok(Vec::new());
+            }
+            QueryType::DropView => return Ok(Vec::new()),
+            // Table DDL on a view's name is left for the storage engine to reject
+            QueryType::CreateTable | QueryType::DropTable | QueryType::AlterTable
+                if !tables.contains_key(&plan.table.0) => return Ok(Vec::new()),
+            QueryType::Insert | QueryType::Update | QueryType::Delete if self.views.contains_key(&plan.table.0) => {
+                return Err(PlanningError::InvalidQuery(format!("Cannot modify view '{}'", plan.table)));
+            }
+            _ => {}
+        }
+
+        let table_exists = tables.contains_key(&plan.table.0) || views.contains_key(&plan.table.0);
+        let columns = if table_exists { scope_columns(plan, tables, views)? } else { Vec::new() };
+        // Each combined SELECT reads its own tables
+        let mut combined = Vec::new();
+        for operation in &plan.set_operations {
+            let columns = self.validate_with_views(&operation.plan, tables, views)?;
+            combined.push(query_outputs(&operation.plan, &columns));
+        }
+        self.validate_plan(plan, table_exists, &columns, &combined)?;
+
+        if let Some(ref source) = plan.source {
+            self.validate_with_views(source, tables, views)?;
+        }
+        Ok(columns)
+    }
+
+    /// Validate a plan against the columns it can reference. `combined` holds the output
+    /// columns of the SELECTs combined with a compound SELECT, in order.
+    pub fn validate_plan(
+        &self,
+        plan: &QueryPlan,
+        table_exists: bool,
+        columns: &[Column],
+        combined: &[Vec<(String, Option<DataType>)>],
+    ) -> Result<(), PlanningError> {
         // Validate table exists
         if !table_exists {
             return Err(PlanningError::TableNotFound(plan.table.0.clone()));
         }
 
-        // Validate columns exist for SELECT queries
+        let find_column = |name: &str| {
+            // Writes may qualify columns with their target table, in RETURNING too
+            let key = match name.split_once('.') {
+                Some((qualifier, column)) if qualifier == plan.table.0
+                    && matches!(plan.query_type, QueryType::Insert | QueryType::Update | QueryType::Delete) => column,
+                _ => name,
+            };
+            columns.iter()
+                .find(|column| column.name == key)
+                .ok_or_else(|| {
+                    // Bare names shared by joined tables must be qualified
+                    let suffix = format!(".{}", name);
+                    if columns.iter().filter(|column| column.name.ends_with(&suffix)).count() > 1 {
+                        PlanningError::InvalidQuery(format!("Column reference '{}' is ambiguous", name))
+                    } else {
+                        PlanningError::ColumnNotFound(name.to_string())
+                    }
+                })
+        };
+
+        // Validate JOIN conditions
+        for join in plan.joins.iter().flatten() {
+            if let Some(ref condition) = join.condition {
+                for column in condition.referenced_columns() {
+                    find_column(column)?;
+                }
+                check_comparison_types(condition, columns)?;
+            }
+        }
+
+        // Validate columns exist for SELECT queries and RETURNING lists
         if let Some(ref projection) = plan.projection {
-            for column in projection {
-                if column.0 != "*" && !columns.contains(&column.0) {
-                    return Err(PlanningError::ColumnNotFound(column.0.clone()));
+            for expr in projection.iter().filter_map(SelectItem::expr) {
+                for column in expr.referenced_columns() {
+                    find_column(column)?;
+                }
+                if !matches!(plan.query_type, QueryType::Select) && !expr.aggregates().is_empty() {
+                    return Err(PlanningError::InvalidQuery(
+                        "Aggregate functions are not allowed in RETURNING".to_string()
+                    ));
                 }
             }
         }
 
-        // Validate WHERE clause columns
+        // Validate WHERE clause columns and literal types
         if let Some(ref condition) = plan.condition {
-            if !columns.contains(&condition.column) {
-                return Err(PlanningError::ColumnNotFound(condition.column.clone()));
+            for column in condition.referenced_columns() {
+                find_column(column)?;
+            }
+            if !condition.aggregates().is_empty() {
+                return Err(PlanningError::InvalidQuery(
+                    "Aggregate functions are not allowed in WHERE; use HAVING".to_string()
+                ));
+            }
+            check_comparison_types(condition, columns)?;
+        }
+
+        // Validate GROUP BY, HAVING and aggregate arguments
+        if let (Some(group_by), Some(aggregates)) = (&plan.group_by, &plan.aggregates) {
+            for expr in group_by {
+                for column in expr.referenced_columns() {
+                    find_column(column)?;
+                }
+                if !expr.aggregates().is_empty() {
+                    return Err(PlanningError::InvalidQuery(
+                        "Aggregate functions are not allowed in GROUP BY".to_string()
+                    ));
+                }
+            }
+
+            for aggregate in aggregates {
+                check_aggregate(aggregate, columns)?;
+            }
+
+            if let Some(ref having) = plan.having {
+                for column in having.referenced_columns() {
+                    find_column(column)?;
+                }
+                check_grouped(having, group_by)?;
+            }
+
+            for item in plan.projection.iter().flatten() {
+                match item {
+                    SelectItem::Wildcard => {
+                        return Err(PlanningError::InvalidQuery(
+                            "SELECT * cannot be used with GROUP BY or aggregate functions".to_string()
+                        ));
+                    }
+                    SelectItem::Expr { expr, .. } => check_grouped(expr, group_by)?,
+                }
+            }
+
+            for item in plan.order_by.iter().flatten().filter(|_| plan.set_operations.is_empty()) {
+                check_grouped(&item.expr, group_by)?;
+            }
+        }
+
+        // The SELECTs of a compound SELECT return as many columns, of compatible types
+        let outputs = query_outputs(plan, columns);
+        for (operation, combined) in plan.set_operations.iter().zip(combined) {
+            if combined.len() != outputs.len() {
+                return Err(PlanningError::InvalidQuery(format!(
+                    "Each SELECT of {} must return the same number of columns, but they return {} and {}",
+                    operation.operator, outputs.len(), combined.len()
+                )));
+            }
+            let numeric = |data_type| matches!(data_type, DataType::Integer | DataType::Real);
+            for (position, ((name, left), (_, right))) in outputs.iter().zip(combined).enumerate() {
+                if let (Some(left), Some(right)) = (*left, *right) {
+                    if left != right && !(numeric(left) && numeric(right)) {
+                        return Err(PlanningError::InvalidQuery(format!(
+                            "{} column {} ('{}') is {} in one SELECT and {} in another",
+                            operation.operator, position + 1, name, left, right
+                        )));
+                    }
+                }
+            }
+        }
+
+        // Validate ORDER BY columns; a compound SELECT sorts by its output columns. A
+        // position still left as a number must name an output column, counting each
+        // column `*` expands to.
+        if let Some(ref order_by) = plan.order_by {
+            for item in order_by {
+                if let Expr::Literal(Value::Integer(position)) = item.expr {
+                    if !usize::try_from(position).is_ok_and(|position| (1..=outputs.len()).contains(&position)) {
+                        return Err(PlanningError::InvalidQuery(
+                            format!("ORDER BY position {} does not name a selected column", position)
+                        ));
+                    }
+                }
+                for column in item.expr.referenced_columns() {
+                    if plan.set_operations.is_empty() {
+                        find_column(column)?;
+                    } else if !outputs.iter().any(|(name, _)| column.rsplit('.').next() == Some(name)) {
+                        return Err(PlanningError::InvalidQuery(format!(
+                            "ORDER BY of a compound SELECT can only use its output columns, not '{}'", column
+                        )));
+                    }
+                }
+                if !plan.set_operations.is_empty() && !item.expr.aggregates().is_empty() {
+                    return Err(PlanningError::InvalidQuery(
+                        "Aggregate functions are not allowed in the ORDER BY of a compound SELECT".to_string()
+                    ));
+                }
             }
         }
 
-        // Validate UPDATE assignments
+        // SELECT DISTINCT can only sort by what it outputs
+        let distinct = plan.distinct && plan.set_operations.is_empty();
+        if let (true, Some(projection), Some(order_by)) = (distinct, &plan.projection, &plan.order_by) {
+            for item in order_by {
+                let projected = matches!(item.expr, Expr::Literal(Value::Integer(_)))
+                    || projection.iter().any(|output| match output {
+                        SelectItem::Expr { expr, .. } => expr == &item.expr,
+                        SelectItem::Wildcard => matches!(item.expr, Expr::Column(_)),
+                    });
+                if !projected {
+                    return Err(PlanningError::InvalidQuery(
+                        format!("For SELECT DISTINCT, ORDER BY expression '{}' must appear in the select list", item.expr)
+                    ));
+                }
+            }
+        }
+
+        // Validate UPDATE and ON CONFLICT DO UPDATE assignments
         if let Some(ref assignments) = plan.assignments {
-            for (column, _) in assignments {
-                if !columns.contains(&column.0) {
-                    return Err(PlanningError::ColumnNotFound(column.0.clone()));
+            for (column, expr) in assignments {
+                let target = find_column(&column.0)?;
+                for column in expr.referenced_columns() {
+                    // An upsert also reads the proposed row as `excluded`
+                    match column.strip_prefix("excluded.") {
+                        Some(column) if matches!(plan.query_type, QueryType::Insert) => find_column(column)?,
+                        _ => find_column(column)?,
+                    };
+                }
+                if !expr.aggregates().is_empty() {
+                    return Err(PlanningError::InvalidQuery(
+                        "Aggregate functions are not allowed in UPDATE assignments".to_string()
+                    ));
+                }
+                check_comparison_types(expr, columns)?;
+                if let Expr::Literal(value) = expr {
+                    check_value_type(target, value)?;
                 }
             }
         }
 
         // Validate INSERT columns
-        if let Some((ref insert_columns, ref values)) = plan.insert_data {
-            if !insert_columns.is_empty() {
-                for column in insert_columns {
-                    if !columns.contains(&column.0) {
-                        return Err(PlanningError::ColumnNotFound(column.0.clone()));
+        if let Some((ref insert_columns, ref tuples)) = plan.insert_data {
+            for column in insert_columns {
+                find_column(&column.0)?;
+            }
+
+            // Values map to the listed columns, or to the table columns in order
+            let targets: Vec<&str> = if insert_columns.is_empty() {
+                columns.iter().map(|column| column.name.as_str()).collect()
+            } else {
+                insert_columns.iter().map(|column| column.0.as_str()).collect()
+            };
+            for values in tuples {
+                if !insert_columns.is_empty() && insert_columns.len() != values.len() {
+                    return Err(PlanningError::InvalidQuery(
+                        "Column count doesn't match value count".to_string()
+                    ));
+                } else if values.len() > columns.len() {
+                    return Err(PlanningError::InvalidQuery(
+                        format!("Table has {} columns but {} values were supplied", columns.len(), values.len())
+                    ));
+                }
+                for (name, value) in targets.iter().zip(values) {
+                    let column = find_column(name)?;
+                    if let Expr::Literal(value) = value {
+                        check_value_type(column, value)?;
                     }
                 }
-                
-                if insert_columns.len() != values.len() {
+            }
+
+            // The query must return one value per target column; the width of `*` is
+            // only known once the query runs
+            let outputs = plan.source.iter().flat_map(|source| source.projection.iter().flatten());
+            if let Some(count) = outputs.map(|item| item.expr().map(|_| 1)).sum::<Option<usize>>() {
+                if plan.source.is_some() && count != targets.len() {
                     return Err(PlanningError::InvalidQuery(
-                        "Column count doesn't match value count".to_string()
+                        format!("INSERT has {} target columns but the query returns {}", targets.len(), count)
                     ));
                 }
             }
@@ -388,6 +2053,8 @@ pub enum PlanningError {
     ColumnNotFound(String),
     InvalidQuery(String),
     OptimizationFailed(String),
+    TypeMismatch { column: String, expected: DataType, value: String },
+    ParameterMismatch { index: usize, expected: DataType, value: String },
 }
 
 impl std::fmt::Display for PlanningError {
@@ -397,12 +2064,352 @@ impl std::fmt::Display for PlanningError {
             PlanningError::ColumnNotFound(column) => write!(f, "Column '{}' not found", column),
             PlanningError::InvalidQuery(msg) => write!(f, "Invalid query: {}", msg),
             PlanningError::OptimizationFailed(msg) => write!(f, "Optimization failed: {}", msg),
+            PlanningError::TypeMismatch { column, expected, value } => {
+                write!(f, "Type mismatch: value '{}' is not a valid {} for column '{}'", value, expected, column)
+            }
+            PlanningError::ParameterMismatch { index, expected, value } => {
+                write!(f, "Type mismatch: value '{}' is not a valid {} for parameter ${}", value, expected, index)
+            }
         }
     }
 }
 
 impl std::error::Error for PlanningError {}
 
+/// Reject literals that can never be stored in (or compared with) a typed column
+fn check_value_type(column: &Column, value: &Value) -> Result<(), PlanningError> {
+    match value.coerce_to(column.data_type) {
+        Some(_) => Ok(()),
+        None => Err(PlanningError::TypeMismatch {
+            column: column.name.clone(),
+            expected: column.data_type,
+            value: value.to_string(),
+        }),
+    }
+}
+
+fn check_comparison_types(condition: &Expr, columns: &[Column]) -> Result<(), PlanningError> {
+    match condition {
+        Expr::Binary { left, operator, right } if !operator.is_comparison() => {
+            check_comparison_types(left, columns)?;
+            check_comparison_types(right, columns)
+        }
+        Expr::Unary { operand, .. } | Expr::IsNull { operand, .. } | Expr::Cast { operand, .. } => {
+            check_comparison_types(operand, columns)
+        }
+        Expr::Function { arguments, .. } => {
+            arguments.iter().try_for_each(|argument| check_comparison_types(argument, columns))
+        }
+        Expr::Binary { left, right, .. } => match (left.as_ref(), right.as_ref()) {
+            (Expr::Column(name), Expr::Literal(value)) | (Expr::Literal(value), Expr::Column(name)) => {
+                match columns.iter().find(|column| &column.name == name) {
+                    Some(column) => check_value_type(column, value),
+                    None => Ok(()),
+                }
+            }
+            _ => Ok(()),
+        },
+        Expr::Like { pattern, escape, case_insensitive, .. } => match pattern.as_ref() {
+            Expr::Literal(Value::Text(pattern)) => match LikePattern::new(pattern, *escape, *case_insensitive) {
+                Ok(_) => Ok(()),
+                Err(e) => Err(PlanningError::InvalidQuery(format!("Invalid LIKE pattern '{}': {}", pattern, e))),
+            },
+            _ => Ok(()),
+        },
+        Expr::Regexp { pattern, .. } => match pattern.as_ref() {
+            Expr::Literal(Value::Text(pattern)) => match Regex::new(pattern) {
+                Ok(_) => Ok(()),
+                Err(e) => Err(PlanningError::InvalidQuery(format!("Invalid regular expression '{}': {}", pattern, e))),
+            },
+            _ => Ok(()),
+        },
+        Expr::InList { operand, list, .. } => {
+            // Every listed literal must be comparable with the column
+            if let Expr::Column(name) = operand.as_ref() {
+                if let Some(column) = columns.iter().find(|column| &column.name == name) {
+                    for item in list {
+                        if let Expr::Literal(value) = item {
+                            check_value_type(column, value)?;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+        Expr::Between { operand, low, high, .. } => {
+            // Both bounds must be comparable with the column
+            if let Expr::Column(name) = operand.as_ref() {
+                if let Some(column) = columns.iter().find(|column| &column.name == name) {
+                    for bound in [low, high] {
+                        if let Expr::Literal(value) = bound.as_ref() {
+                            check_value_type(column, value)?;
+                        }
+                    }
+                }
+            }
+            Ok(())
+        }
+        Expr::Case { operand, branches, .. } => {
+            // The WHEN values of a simple CASE must be comparable with its operand
+            if let Some(Expr::Column(name)) = operand.as_deref() {
+                if let Some(column) = columns.iter().find(|column| &column.name == name) {
+                    for (when, _) in branches {
+                        if let Expr::Literal(value) = when {
+                            check_value_type(column, value)?;
+                        }
+                    }
+                }
+            }
+            condition.case_children().into_iter().try_for_each(|child| check_comparison_types(child, columns))
+        }
+        Expr::Column(_) | Expr::Literal(_) | Expr::Parameter(_) | Expr::Aggregate { .. } | Expr::Subquery(_)
+        | Expr::Exists(_) | Expr::InSubquery { .. } => Ok(()),
+    }
+}
+
+/// Reject nested aggregates and SUM/AVG over non-numeric columns
+fn check_aggregate(aggregate: &Expr, columns: &[Column]) -> Result<(), PlanningError> {
+    let Expr::Aggregate { function, argument: Some(argument), .. } = aggregate else {
+        return Ok(());
+    };
+
+    if !argument.aggregates().is_empty() {
+        return Err(PlanningError::InvalidQuery(
+            format!("Aggregate calls cannot be nested in {}", aggregate)
+        ));
+    }
+
+    if let (AggregateFunction::Sum | AggregateFunction::Avg, Expr::Column(name)) = (function, argument.as_ref()) {
+        if let Some(column) = columns.iter().find(|column| &column.name == name) {
+            if !matches!(column.data_type, DataType::Integer | DataType::Real) {
+                return Err(PlanningError::InvalidQuery(
+                    format!("{} requires a numeric column, but '{}' is {}", function, name, column.data_type)
+                ));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Ensure an expression over groups only uses grouping expressions outside aggregate calls
+fn check_grouped(expr: &Expr, group_by: &[Expr]) -> Result<(), PlanningError> {
+    if group_by.contains(expr) {
+        return Ok(());
+    }
+
+    match expr {
+        Expr::Column(name) => Err(PlanningError::InvalidQuery(
+            format!("Column '{}' must appear in the GROUP BY clause or be used in an aggregate function", name)
+        )),
+        // Subqueries run in their own scope
+        Expr::Literal(_) | Expr::Parameter(_) | Expr::Aggregate { .. } | Expr::Subquery(_) | Expr::Exists(_) => Ok(()),
+        Expr::Binary { left, right, .. } => {
+            check_grouped(left, group_by)?;
+            check_grouped(right, group_by)
+        }
+        Expr::Unary { operand, .. } | Expr::IsNull { operand, .. } | Expr::InSubquery { operand, .. } => {
+            check_grouped(operand, group_by)
+        }
+        Expr::Like { operand, pattern, .. } | Expr::Regexp { operand, pattern, .. } => {
+            check_grouped(operand, group_by)?;
+            check_grouped(pattern, group_by)
+        }
+        Expr::InList { operand, list, .. } => {
+            check_grouped(operand, group_by)?;
+            list.iter().try_for_each(|item| check_grouped(item, group_by))
+        }
+        Expr::Between { operand, low, high, .. } => {
+            check_grouped(operand, group_by)?;
+            check_grouped(low, group_by)?;
+            check_grouped(high, group_by)
+        }
+        Expr::Function { arguments, .. } => {
+            arguments.iter().try_for_each(|argument| check_grouped(argument, group_by))
+        }
+        Expr::Cast { operand, .. } => check_grouped(operand, group_by),
+        Expr::Case { .. } => expr.case_children().into_iter().try_for_each(|child| check_grouped(child, group_by)),
+    }
+}
+
+/// Columns a query can reference. SELECT may qualify a column with its table name or
+/// alias; a bare name is usable unless several of the query's tables have it. `views`
+/// holds the columns of the views and WITH queries the query reads.
+pub fn scope_columns(
+    plan: &QueryPlan,
+    tables: &HashMap<String, Table>,
+    views: &HashMap<String, Table>,
+) -> Result<Vec<Column>, PlanningError> {
+    let tables = |name: &str| views.get(name).or_else(|| tables.get(name));
+    let references = plan.tables();
+    let mut scope = Vec::new();
+
+    for (position, (name, qualifier)) in references.iter().enumerate() {
+        if references[..position].iter().any(|(_, other)| other == qualifier) {
+            return Err(PlanningError::InvalidQuery(
+                format!("Table name '{}' is specified more than once; use an alias", qualifier)
+            ));
+        }
+        let table = tables(name).ok_or_else(|| PlanningError::TableNotFound(name.to_string()))?;
+        for column in &table.columns {
+            let sharing_tables = references.iter()
+                .filter(|(other, _)| tables(other).is_some_and(|other| other.has_column(&column.name)))
+                .count();
+            if sharing_tables == 1 {
+                scope.push(column.clone());
+            }
+            if matches!(plan.query_type, QueryType::Select) {
+                scope.push(Column::new(&format!("{}.{}", qualifier, column.name), column.data_type));
+            }
+        }
+    }
+
+    Ok(scope)
+}
+
+/// The columns a view's or WITH query's query outputs, named as
+/// `SelectItem::view_column_name` names them; `*` stands for the columns of every
+/// table the query reads
+fn query_columns(plan: &QueryPlan, scope: &[Column]) -> Vec<Column> {
+    query_outputs(plan, scope)
+        .into_iter()
+        .map(|(name, data_type)| Column::new(&name, data_type.unwrap_or(DataType::Text)))
+        .collect()
+}
+
+/// The names and types of a query's output columns, as `query_columns`; the type is
+/// `None` where it depends on the data
+fn query_outputs(plan: &QueryPlan, scope: &[Column]) -> Vec<(String, Option<DataType>)> {
+    let mut columns = Vec::new();
+    for item in plan.projection.iter().flatten() {
+        match item {
+            SelectItem::Wildcard => columns.extend(plan.tables().iter().flat_map(|(_, qualifier)| {
+                let prefix = format!("{}.", qualifier);
+                scope.iter().filter_map(move |column| {
+                    column.name.strip_prefix(&prefix).map(|name| (name.to_string(), Some(column.data_type)))
+                })
+            })),
+            SelectItem::Expr { expr, .. } => {
+                columns.push((item.view_column_name().unwrap_or_default(), expression_type(expr, scope)));
+            }
+        }
+    }
+    columns
+}
+
+/// A view or WITH query (`relation`) must name each of its columns once
+fn check_unique_columns(columns: &[Column], relation: &str) -> Result<(), PlanningError> {
+    for (position, column) in columns.iter().enumerate() {
+        if columns[..position].iter().any(|other| other.name == column.name) {
+            return Err(PlanningError::InvalidQuery(
+                format!("{} has more than one column named '{}'; give them distinct aliases", relation, column.name)
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// The type of the values an expression produces from rows with these columns, when
+/// it can be told without running the query
+fn expression_type(expr: &Expr, columns: &[Column]) -> Option<DataType> {
+    let numeric = |left: Option<DataType>, right: Option<DataType>| match (left?, right?) {
+        (DataType::Integer, DataType::Integer) => Some(DataType::Integer),
+        (DataType::Integer | DataType::Real, DataType::Integer | DataType::Real) => Some(DataType::Real),
+        _ => None,
+    };
+
+    match expr {
+        Expr::Column(name) => columns.iter().find(|column| &column.name == name).map(|column| column.data_type),
+        Expr::Literal(value) => value.data_type(),
+        Expr::Parameter(_) | Expr::Subquery(_) => None,
+        Expr::Binary { left, operator, right } => match operator {
+            BinaryOperator::Concat => Some(DataType::Text),
+            BinaryOperator::Add | BinaryOperator::Subtract | BinaryOperator::Multiply
+            | BinaryOperator::Divide | BinaryOperator::Modulo => {
+                numeric(expression_type(left, columns), expression_type(right, columns))
+            }
+            _ => Some(DataType::Boolean),
+        },
+        Expr::Unary { operator: UnaryOperator::Not, .. } => Some(DataType::Boolean),
+        Expr::Unary { operator: UnaryOperator::Minus, operand } => expression_type(operand, columns),
+        Expr::IsNull { .. } | Expr::Exists(_) | Expr::InSubquery { .. } | Expr::Like { .. } | Expr::Regexp { .. }
+        | Expr::InList { .. } | Expr::Between { .. } => Some(DataType::Boolean),
+        Expr::Aggregate { function, argument, .. } => match function {
+            AggregateFunction::Count => Some(DataType::Integer),
+            AggregateFunction::Avg => Some(DataType::Real),
+            AggregateFunction::Sum | AggregateFunction::Min | AggregateFunction::Max => {
+                expression_type(argument.as_deref()?, columns)
+            }
+        },
+        Expr::Function { function, arguments } => {
+            let argument = |index: usize| arguments.get(index).and_then(|argument| expression_type(argument, columns));
+            match function {
+                ScalarFunction::Upper | ScalarFunction::Lower | ScalarFunction::Substr | ScalarFunction::Trim
+                | ScalarFunction::Replace => Some(DataType::Text),
+                ScalarFunction::Length => Some(DataType::Integer),
+                ScalarFunction::Abs | ScalarFunction::Nullif | ScalarFunction::Round => argument(0),
+                ScalarFunction::Coalesce => (0..arguments.len()).find_map(argument),
+            }
+        }
+        Expr::Cast { data_type, .. } => Some(*data_type),
+        // The results' common type, once all known results agree (INTEGER widens to REAL)
+        Expr::Case { branches, else_result, .. } => branches.iter()
+            .map(|(_, result)| result)
+            .chain(else_result.as_deref())
+            .filter_map(|result| expression_type(result, columns))
+            .map(Some)
+            .reduce(|left, right| if left == right { left } else { numeric(left, right) })
+            .flatten(),
+    }
+}
+
+/// Resolve the declared column types of a CREATE TABLE
+pub fn column_schema(columns: &[ColumnDefinition]) -> Result<Vec<Column>, PlanningError> {
+    columns
+        .iter()
+        .map(|column| {
+            DataType::from_sql_name(&column.data_type)
+                .map(|data_type| Column::new(&column.name.0, data_type))
+                .ok_or_else(|| PlanningError::InvalidQuery(
+                    format!("Unknown data type '{}' for column '{}'", column.data_type, column.name)
+                ))
+        })
+        .collect()
+}
+
+/// Resolve the primary key of a CREATE TABLE from column and table constraints
+pub fn primary_key_column<'a>(
+    columns: &'a [ColumnDefinition],
+    constraints: &'a [TableConstraint],
+) -> Result<Option<&'a Identifier>, PlanningError> {
+    let mut keys: Vec<&Identifier> = columns
+        .iter()
+        .filter(|column| column.constraints.contains(&ColumnConstraint::PrimaryKey))
+        .map(|column| &column.name)
+        .collect();
+
+    for constraint in constraints {
+        match constraint {
+            TableConstraint::PrimaryKey(key_columns) => {
+                if key_columns.len() > 1 {
+                    return Err(PlanningError::InvalidQuery(
+                        "Composite primary keys are not supported".to_string()
+                    ));
+                }
+                keys.extend(key_columns.iter());
+            }
+        }
+    }
+
+    if keys.len() > 1 {
+        return Err(PlanningError::InvalidQuery(
+            "Multiple primary keys defined".to_string()
+        ));
+    }
+
+    Ok(keys.pop())
+}
+
 // Helper functions for query analysis
 pub fn analyze_query_complexity(plan: &QueryPlan) -> QueryComplexity {
     let mut complexity_score = 0;
@@ -413,13 +2420,52 @@ pub fn analyze_query_complexity(plan: &QueryPlan) -> QueryComplexity {
         QueryType::Insert => 2,
         QueryType::Update => 3,
         QueryType::Delete => 2,
+        QueryType::CreateTable | QueryType::DropTable | QueryType::CreateView | QueryType::DropView => 1,
+        QueryType::AlterTable => 2,
     };
 
+    // Add complexity for each view read by the query
+    complexity_score += 2 * plan.views.len();
+
+    // Add complexity for each materialized WITH query, and more for recursion
+    for cte in &plan.ctes {
+        complexity_score += if cte.recursive_step.is_some() { 4 } else { 2 };
+    }
+
+    // Add complexity for each SELECT combined by UNION, INTERSECT or EXCEPT
+    complexity_score += 2 * plan.set_operations.len();
+
     // Add complexity for WHERE clause
     if plan.condition.is_some() {
         complexity_score += 2;
     }
 
+    // Add complexity for each joined table
+    if let Some(ref joins) = plan.joins {
+        complexity_score += 2 * joins.len();
+    }
+
+    // Add complexity for grouping and aggregation
+    if plan.group_by.is_some() {
+        complexity_score += 2;
+    }
+
+    // Add complexity for duplicate elimination
+    if plan.distinct {
+        complexity_score += 1;
+    }
+
+    // Add complexity for each subquery run by the plan
+    let subqueries = plan.execution_steps.iter()
+        .filter(|step| matches!(step, ExecutionStep::Subquery { .. }))
+        .count();
+    complexity_score += 2 * subqueries;
+
+    // Add complexity for sorting
+    if plan.order_by.is_some() {
+        complexity_score += 1;
+    }
+
     // Add complexity for projections
     if let Some(ref projection) = plan.projection {
         if projection.len() > 5 {
@@ -441,7 +2487,120 @@ pub enum QueryComplexity {
     Complex,
 }
 
-// Query cache for future optimization
+/// A statement parsed, planned and validated once, then executed with bound parameters
+#[derive(Debug, Clone)]
+pub struct PreparedStatement {
+    sql: String,
+    ast: ASTNode,
+    plan: QueryPlan,
+    /// The type each parameter is compared with or stored as, where the statement shows it
+    parameter_types: Vec<Option<DataType>>,
+    /// The storage's operation count when the statement was validated
+    version: u64,
+}
+
+impl PreparedStatement {
+    pub fn plan(&self) -> &QueryPlan {
+        &self.plan
+    }
+
+    pub fn parameter_types(&self) -> &[Option<DataType>] {
+        &self.parameter_types
+    }
+
+    /// Substitute the parameters into the statement, coercing each to its expected type.
+    /// A statement prepared before the storage last changed must be refreshed first.
+    pub fn bind(&self, parameters: &[Value], storage: &StorageEngine) -> Result<ASTNode, PlanningError> {
+        if storage.metadata.total_operations != self.version {
+            return Err(PlanningError::InvalidQuery(
+                "Prepared statement is out of date; the database changed since it was prepared".to_string()
+            ));
+        }
+        if parameters.len() != self.parameter_types.len() {
+            return Err(PlanningError::InvalidQuery(format!(
+                "Statement takes {} parameter(s) but {} were given",
+                self.parameter_types.len(),
+                parameters.len()
+            )));
+        }
+
+        let values = parameters.iter()
+            .zip(&self.parameter_types)
+            .enumerate()
+            .map(|(position, (value, data_type))| match data_type {
+                Some(data_type) => value.coerce_to(*data_type).ok_or_else(|| PlanningError::ParameterMismatch {
+                    index: position + 1,
+                    expected: *data_type,
+                    value: value.to_string(),
+                }),
+                None => Ok(value.clone()),
+            })
+            .collect::<Result<Vec<Value>, PlanningError>>()?;
+        Ok(self.ast.bind_parameters(&values))
+    }
+}
+
+/// Infer parameter types from the columns they are compared with, assigned to or
+/// inserted into. Parameters used only elsewhere take any value.
+fn parameter_types(ast: &ASTNode, columns: &[Column]) -> Vec<Option<DataType>> {
+    let mut types = vec![None; ast.parameter_count()];
+    let column_type = |expr: &Expr| match expr {
+        Expr::Column(name) => columns.iter().find(|column| &column.name == name).map(|column| column.data_type),
+        _ => None,
+    };
+    let mut record = |expr: &Expr, data_type: Option<DataType>| {
+        if let (Expr::Parameter(index), Some(data_type)) = (expr, data_type) {
+            types[index - 1].get_or_insert(data_type);
+        }
+    };
+
+    let _: Result<_, std::convert::Infallible> = ast.rewrite_expressions(&mut |node| {
+        match node {
+            Expr::Binary { left, operator, right } if operator.is_comparison() => {
+                record(right, column_type(left));
+                record(left, column_type(right));
+            }
+            Expr::InList { operand, list, .. } => {
+                for item in list {
+                    record(item, column_type(operand));
+                }
+            }
+            Expr::Between { operand, low, high, .. } => {
+                record(low, column_type(operand));
+                record(high, column_type(operand));
+            }
+            Expr::Like { pattern, .. } | Expr::Regexp { pattern, .. } => record(pattern, Some(DataType::Text)),
+            _ => {}
+        }
+        Ok(None)
+    });
+
+    let column_named = |name: &Identifier| Expr::Column(name.0.clone());
+    match ast {
+        ASTNode::UpdateStatement { assignments, .. }
+        | ASTNode::InsertStatement { on_conflict: Some(OnConflict { action: ConflictAction::Update(assignments), .. }), .. } => {
+            for (column, expr) in assignments {
+                record(expr, column_type(&column_named(column)));
+            }
+        }
+        _ => {}
+    }
+    if let ASTNode::InsertStatement { columns: targets, source: InsertSource::Values(tuples), .. } = ast {
+        let targets: Vec<Expr> = if targets.is_empty() {
+            columns.iter().map(|column| Expr::Column(column.name.clone())).collect()
+        } else {
+            targets.iter().map(column_named).collect()
+        };
+        for values in tuples {
+            for (target, value) in targets.iter().zip(values) {
+                record(value, column_type(target));
+            }
+        }
+    }
+    types
+}
+
+// Query cache for prepared statement plans
 pub struct QueryCache {
     cache: HashMap<String, QueryPlan>,
     max_size: usize,
@@ -473,3 +2632,151 @@ impl QueryCache {
         self.cache.clear();
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db::parser::Parser;
+    use crate::db::schema::Row;
+
+    fn users() -> Vec<Column> {
+        vec![Column::new("id", DataType::Integer), Column::new("name", DataType::Text)]
+    }
+
+    fn validate(sql: &str) -> Result<(), PlanningError> {
+        let mut planner = QueryPlanner::new();
+        let plan = planner.plan(&Parser::parse(sql).unwrap())?;
+        planner.validate(&plan, &storage().tables).map(|_| ())
+    }
+
+    #[test]
+    fn order_by_positions_must_name_a_selected_column() {
+        assert!(validate("SELECT id, name FROM users ORDER BY 2").is_ok());
+        assert!(matches!(validate("SELECT id FROM users ORDER BY 2"), Err(PlanningError::InvalidQuery(_))));
+        assert!(validate("SELECT * FROM users ORDER BY 1").is_ok());
+        assert!(validate("SELECT id, * FROM users ORDER BY 3").is_ok());
+        assert!(validate("SELECT DISTINCT * FROM users ORDER BY 2").is_ok());
+        assert!(matches!(validate("SELECT id, * FROM users ORDER BY 4"), Err(PlanningError::InvalidQuery(_))));
+    }
+
+    #[test]
+    fn recursive_queries_cannot_sort_or_limit_their_rows() {
+        let recursive = |clause: &str| validate(&format!(
+            "WITH RECURSIVE t(n) AS (SELECT 1 FROM users UNION ALL SELECT n + 1 FROM t {}) SELECT * FROM t",
+            clause
+        ));
+        assert!(recursive("").is_ok());
+        for clause in ["ORDER BY n", "LIMIT 5", "OFFSET 2 ROWS"] {
+            assert!(
+                matches!(recursive(clause), Err(PlanningError::InvalidQuery(message)) if message.contains("LIMIT")),
+                "{}", clause
+            );
+        }
+        assert!(validate(
+            "WITH RECURSIVE t(n) AS (SELECT 1 FROM users UNION ALL SELECT n + 1 FROM t WHERE n < 5) SELECT * FROM t ORDER BY n LIMIT 2"
+        ).is_ok());
+    }
+
+    #[test]
+    fn updates_and_deletes_may_qualify_columns_with_their_table() {
+        assert!(validate("UPDATE users SET name = users.name WHERE users.id = 1").is_ok());
+        assert!(validate("DELETE FROM users WHERE users.id = 1").is_ok());
+        assert!(validate("DELETE FROM users WHERE orders.id = 1").is_err());
+    }
+
+    fn storage() -> StorageEngine {
+        let mut storage = StorageEngine::new();
+        storage.create_table("users", users(), Some("id")).unwrap();
+        storage
+    }
+
+    #[test]
+    fn binding_coerces_parameters_to_the_types_they_meet() {
+        let storage = storage();
+        let statement = QueryPlanner::new()
+            .prepare("SELECT * FROM users WHERE id = ? AND name LIKE ? AND ? IS NULL", &storage)
+            .unwrap();
+        assert_eq!(statement.parameter_types(), &[Some(DataType::Integer), Some(DataType::Text), None]);
+
+        let text = |text: &str| Value::Text(text.to_string());
+        assert!(statement.bind(&[text("7"), Value::Integer(1), Value::Real(0.5)], &storage).is_ok());
+        assert!(matches!(
+            statement.bind(&[text("seven"), text("A%"), Value::Null], &storage),
+            Err(PlanningError::ParameterMismatch { index: 1, expected: DataType::Integer, .. })
+        ));
+        assert!(matches!(statement.bind(&[Value::Integer(7)], &storage), Err(PlanningError::InvalidQuery(_))));
+    }
+
+    #[test]
+    fn prepared_statements_are_checked_again_after_the_storage_changes() {
+        let mut storage = storage();
+        let mut planner = QueryPlanner::new();
+        let mut statement = planner.prepare("SELECT * FROM users WHERE name = ?", &storage).unwrap();
+
+        storage.drop_table("users").unwrap();
+        let columns = vec![Column::new("id", DataType::Integer), Column::new("name", DataType::Integer)];
+        storage.create_table("users", columns, None).unwrap();
+        assert!(matches!(statement.bind(&[Value::Integer(1)], &storage), Err(PlanningError::InvalidQuery(_))));
+        planner.refresh_prepared(&mut statement, &storage).unwrap();
+        assert_eq!(statement.parameter_types(), &[Some(DataType::Integer)]);
+        assert!(statement.bind(&[Value::Integer(1)], &storage).is_ok());
+
+        storage.drop_column("users", "name").unwrap();
+        assert!(planner.refresh_prepared(&mut statement, &storage).is_err());
+    }
+
+    #[test]
+    fn cached_plans_are_validated_and_dropped_when_the_storage_changes() {
+        let mut storage = storage();
+        let mut planner = QueryPlanner::new();
+        assert!(planner.prepare("SELECT * FROM orders", &storage).is_err());
+        assert!(planner.cache.get("SELECT * FROM orders").is_none());
+
+        let sql = "SELECT * FROM users WHERE name = ?";
+        let before = planner.prepare(sql, &storage).unwrap();
+        assert!(planner.cache.get(sql).is_some());
+
+        for id in 0..50 {
+            let row = Row { data: HashMap::from([("id".to_string(), Value::Integer(id))]) };
+            storage.insert_row("users", row).unwrap();
+        }
+        let after = planner.prepare(sql, &storage).unwrap();
+        let fresh = QueryPlanner::new().prepare(sql, &storage).unwrap();
+        assert_ne!(after.plan.estimated_cost, before.plan.estimated_cost);
+        assert_eq!(after.plan.estimated_cost, fresh.plan.estimated_cost);
+    }
+
+    #[test]
+    fn views_may_not_read_themselves() {
+        let mut storage = storage();
+        let view = |definition: &str| View { definition: definition.to_string() };
+        storage.create_view("a", view("SELECT * FROM users"), false).unwrap();
+        storage.create_view("b", view("SELECT * FROM a"), false).unwrap();
+        let mut planner = QueryPlanner::new();
+        planner.refresh_statistics(&storage);
+
+        let plan = |planner: &mut QueryPlanner, sql: &str| planner.plan(&Parser::parse(sql).unwrap());
+        assert!(plan(&mut planner, "CREATE OR REPLACE VIEW a AS SELECT * FROM a").is_err());
+        assert!(plan(&mut planner, "CREATE OR REPLACE VIEW a AS SELECT * FROM b").is_err());
+        assert!(plan(&mut planner, "CREATE OR REPLACE VIEW a AS SELECT id FROM users").is_ok());
+
+        // A loop stored by other means is caught when a query reads it
+        storage.create_view("a", view("SELECT * FROM b"), true).unwrap();
+        planner.refresh_statistics(&storage);
+        assert!(matches!(plan(&mut planner, "SELECT * FROM b"), Err(PlanningError::InvalidQuery(_))));
+    }
+
+    #[test]
+    fn compound_selects_order_by_output_positions_in_range() {
+        assert!(validate("SELECT id, name FROM users UNION SELECT id, name FROM users ORDER BY 2").is_ok());
+        assert!(validate("SELECT * FROM users UNION SELECT id, name FROM users ORDER BY 2 DESC").is_ok());
+        assert!(matches!(
+            validate("SELECT id FROM users UNION SELECT id FROM users ORDER BY 2"),
+            Err(PlanningError::InvalidQuery(_))
+        ));
+        assert!(matches!(
+            validate("SELECT id FROM users UNION SELECT id FROM users ORDER BY 0"),
+            Err(PlanningError::InvalidQuery(_))
+        ));
+    }
+}
@@ -1,16 +1,363 @@
+use std::cmp::Ordering;
 use std::collections::HashMap;
+use std::hash::{Hash, Hasher};
 use serde::{Deserialize, Serialize};
 
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Eq)]
 pub struct Table {
-    pub columns: Vec<String>,
+    pub columns: Vec<Column>,
     pub rows: HashMap<usize, Row>,
     pub primary_key: Option<String>,
 }
 
+impl Table {
+    /// Get a column definition by name
+    pub fn column(&self, name: &str) -> Option<&Column> {
+        self.columns.iter().find(|column| column.name == name)
+    }
+
+    pub fn has_column(&self, name: &str) -> bool {
+        self.column(name).is_some()
+    }
+
+    /// Column names in declaration order
+    pub fn column_names(&self) -> Vec<String> {
+        self.columns.iter().map(|column| column.name.clone()).collect()
+    }
+}
+
+/// A named query read like a table. Only its SELECT text is stored, so the view
+/// follows later changes to the tables it reads.
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Eq)]
+pub struct View {
+    pub definition: String,
+}
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Eq)]
+pub struct Column {
+    pub name: String,
+    pub data_type: DataType,
+}
+
+impl Column {
+    pub fn new(name: &str, data_type: DataType) -> Self {
+        Column {
+            name: name.to_string(),
+            data_type,
+        }
+    }
+}
+
+impl std::fmt::Display for Column {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.name, self.data_type)
+    }
+}
+
+
+#[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Copy, Eq, Hash)]
+pub enum DataType {
+    Integer,
+    Real,
+    Text,
+    Boolean,
+    Blob,
+}
+
+impl DataType {
+    /// Resolve a SQL type name (e.g., `INT`, `VARCHAR(255)`) to a storage type
+    pub fn from_sql_name(name: &str) -> Option<DataType> {
+        let base = name.split('(').next().unwrap_or(name).trim().to_uppercase();
+        match base.as_str() {
+            "INTEGER" | "INT" | "BIGINT" | "SMALLINT" | "TINYINT" => Some(DataType::Integer),
+            "REAL" | "FLOAT" | "DOUBLE" | "DECIMAL" | "NUMERIC" => Some(DataType::Real),
+            "TEXT" | "VARCHAR" | "CHAR" | "STRING" | "CLOB" => Some(DataType::Text),
+            "BOOLEAN" | "BOOL" => Some(DataType::Boolean),
+            "BLOB" | "BYTEA" | "BINARY" => Some(DataType::Blob),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for DataType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            DataType::Integer => "INTEGER",
+            DataType::Real => "REAL",
+            DataType::Text => "TEXT",
+            DataType::Boolean => "BOOLEAN",
+            DataType::Blob => "BLOB",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+pub enum Value {
+    Integer(i64),
+    Real(f64),
+    Text(String),
+    Boolean(bool),
+    Blob(Vec<u8>),
+    Null,
+}
+
+impl Value {
+    /// The storage type of this value, or `None` for NULL
+    pub fn data_type(&self) -> Option<DataType> {
+        match self {
+            Value::Integer(_) => Some(DataType::Integer),
+            Value::Real(_) => Some(DataType::Real),
+            Value::Text(_) => Some(DataType::Text),
+            Value::Boolean(_) => Some(DataType::Boolean),
+            Value::Blob(_) => Some(DataType::Blob),
+            Value::Null => None,
+        }
+    }
+
+    pub fn is_null(&self) -> bool {
+        matches!(self, Value::Null)
+    }
+
+    /// Interpret the value as a truth value; NULL and non-boolean values are unknown
+    pub fn as_bool(&self) -> Option<bool> {
+        match self.coerce_to(DataType::Boolean) {
+            Some(Value::Boolean(value)) => Some(value),
+            _ => None,
+        }
+    }
+
+    /// Convert the value to the given type following the column coercion rules.
+    /// Returns `None` when the value cannot be represented in that type.
+    pub fn coerce_to(&self, data_type: DataType) -> Option<Value> {
+        match (self, data_type) {
+            (Value::Null, _) => Some(Value::Null),
+            (Value::Integer(_), DataType::Integer)
+            | (Value::Real(_), DataType::Real)
+            | (Value::Text(_), DataType::Text)
+            | (Value::Boolean(_), DataType::Boolean)
+            | (Value::Blob(_), DataType::Blob) => Some(self.clone()),
+
+            (Value::Real(r), DataType::Integer) if r.fract() == 0.0 && r.abs() < i64::MAX as f64 => {
+                Some(Value::Integer(*r as i64))
+            }
+            (Value::Text(s), DataType::Integer) => s.trim().parse().ok().map(Value::Integer),
+            (Value::Boolean(b), DataType::Integer) => Some(Value::Integer(*b as i64)),
+
+            (Value::Integer(i), DataType::Real) => Some(Value::Real(*i as f64)),
+            (Value::Text(s), DataType::Real) => s.trim().parse().ok().map(Value::Real),
+
+            (Value::Integer(_) | Value::Real(_) | Value::Boolean(_), DataType::Text) => {
+                Some(Value::Text(self.to_string()))
+            }
+
+            (Value::Integer(0), DataType::Boolean) => Some(Value::Boolean(false)),
+            (Value::Integer(1), DataType::Boolean) => Some(Value::Boolean(true)),
+            (Value::Text(s), DataType::Boolean) => match s.trim().to_lowercase().as_str() {
+                "true" | "t" | "yes" | "1" => Some(Value::Boolean(true)),
+                "false" | "f" | "no" | "0" => Some(Value::Boolean(false)),
+                _ => None,
+            },
+
+            (Value::Text(s), DataType::Blob) => Some(Value::Blob(s.as_bytes().to_vec())),
+
+            _ => None,
+        }
+    }
+
+    /// Compare two values with SQL semantics: numbers compare numerically across
+    /// INTEGER and REAL, text that looks like the other operand's type is coerced,
+    /// and NULL or incompatible operands are unordered (`None`).
+    pub fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Null, _) | (_, Value::Null) => None,
+            (Value::Integer(a), Value::Integer(b)) => Some(a.cmp(b)),
+            (Value::Integer(a), Value::Real(b)) => compare_integer_real(*a, *b),
+            (Value::Real(a), Value::Integer(b)) => compare_integer_real(*b, *a).map(Ordering::reverse),
+            (Value::Real(a), Value::Real(b)) => a.partial_cmp(b),
+            (Value::Text(a), Value::Text(b)) => Some(a.cmp(b)),
+            (Value::Boolean(a), Value::Boolean(b)) => Some(a.cmp(b)),
+            (Value::Blob(a), Value::Blob(b)) => Some(a.cmp(b)),
+            (Value::Text(_), _) => {
+                let coerced = self.coerce_to(other.data_type()?)?;
+                coerced.compare(other)
+            }
+            (_, Value::Text(_)) => {
+                let coerced = other.coerce_to(self.data_type()?)?;
+                self.compare(&coerced)
+            }
+            _ => None,
+        }
+    }
+
+    /// Total order used for sorting non-NULL values. Values that SQL cannot compare
+    /// (e.g., text and blobs) are ordered by type so that sorting stays deterministic.
+    pub fn sort_cmp(&self, other: &Value) -> Ordering {
+        self.compare(other).unwrap_or_else(|| self.type_rank().cmp(&other.type_rank()))
+    }
+
+    /// Numbers as equality and hashing see them: a REAL holding an integer exactly is
+    /// that INTEGER, both zeros are the same, and every NaN is the same NaN.
+    fn numeric_key(&self) -> Option<NumericKey> {
+        match *self {
+            Value::Integer(i) => Some(NumericKey::Integer(i)),
+            Value::Real(r) => Some(match exact_integer(r) {
+                Some(i) => NumericKey::Integer(i),
+                None if r.is_nan() => NumericKey::Real(f64::NAN.to_bits()),
+                None => NumericKey::Real(r.to_bits()),
+            }),
+            _ => None,
+        }
+    }
+
+    fn type_rank(&self) -> u8 {
+        match self {
+            Value::Null => 0,
+            Value::Boolean(_) => 1,
+            Value::Integer(_) | Value::Real(_) => 2,
+            Value::Text(_) => 3,
+            Value::Blob(_) => 4,
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Hash)]
+enum NumericKey {
+    Integer(i64),
+    Real(u64),
+}
+
+/// Compare an INTEGER with a REAL exactly, instead of rounding the integer to a float
+fn compare_integer_real(integer: i64, real: f64) -> Option<Ordering> {
+    if real.is_nan() {
+        return None;
+    }
+    let whole = real.trunc();
+    match exact_integer(whole) {
+        Some(whole_integer) => Some(integer.cmp(&whole_integer).then(0.0.partial_cmp(&(real - whole))?)),
+        // Beyond the i64 range in one direction or the other
+        None if real > 0.0 => Some(Ordering::Less),
+        None => Some(Ordering::Greater),
+    }
+}
+
+/// The INTEGER a REAL holds exactly, or `None` when it has a fraction or lies outside
+/// the i64 range. Both bounds are powers of two, so the range check itself is exact.
+fn exact_integer(real: f64) -> Option<i64> {
+    const LIMIT: f64 = 9_223_372_036_854_775_808.0;
+    (real.fract() == 0.0 && (-LIMIT..LIMIT).contains(&real)).then_some(real as i64)
+}
+
+/// Equality agrees with `compare` for numbers, so DISTINCT, GROUP BY and set operations
+/// treat `1` and `1.0` as one value. Unlike `compare`, NaN equals itself, as `Eq` needs.
+impl PartialEq for Value {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (Value::Integer(_) | Value::Real(_), Value::Integer(_) | Value::Real(_)) => {
+                self.numeric_key() == other.numeric_key()
+            }
+            (Value::Text(a), Value::Text(b)) => a == b,
+            (Value::Boolean(a), Value::Boolean(b)) => a == b,
+            (Value::Blob(a), Value::Blob(b)) => a == b,
+            (Value::Null, Value::Null) => true,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for Value {}
+
+impl Hash for Value {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        if let Some(key) = self.numeric_key() {
+            return key.hash(state);
+        }
+        std::mem::discriminant(self).hash(state);
+        match self {
+            Value::Integer(_) | Value::Real(_) => {}
+            Value::Text(s) => s.hash(state),
+            Value::Boolean(b) => b.hash(state),
+            Value::Blob(bytes) => bytes.hash(state),
+            Value::Null => {}
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Integer(i) => write!(f, "{}", i),
+            Value::Real(r) => write!(f, "{:?}", r),
+            Value::Text(s) => write!(f, "{}", s),
+            Value::Boolean(b) => write!(f, "{}", b),
+            Value::Blob(bytes) => {
+                write!(f, "X'")?;
+                for byte in bytes {
+                    write!(f, "{:02X}", byte)?;
+                }
+                write!(f, "'")
+            }
+            Value::Null => write!(f, "NULL"),
+        }
+    }
+}
+
 
 #[derive(Debug, Serialize, Deserialize, PartialEq, Clone, Eq)]
 pub struct Row {
-    pub data: HashMap<String, String>
-}
\ No newline at end of file
+    pub data: HashMap<String, Value>
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+
+    #[test]
+    fn numbers_are_equal_and_hash_alike_when_they_compare_equal() {
+        let distinct: HashSet<Value> = [Value::Integer(1), Value::Real(1.0), Value::Real(0.0), Value::Real(-0.0)]
+            .into_iter()
+            .collect();
+        assert_eq!(distinct.len(), 2);
+        assert_eq!(Value::Integer(0), Value::Real(-0.0));
+        assert_ne!(Value::Integer(1), Value::Real(1.5));
+    }
+
+    #[test]
+    fn integers_compare_exactly_with_reals_beyond_2_pow_53() {
+        let big = 9_007_199_254_740_992_i64;
+        assert_eq!(Value::Integer(big + 1).compare(&Value::Real(big as f64)), Some(Ordering::Greater));
+        assert_ne!(Value::Integer(big + 1), Value::Real(big as f64));
+        assert_eq!(Value::Integer(i64::MAX).compare(&Value::Real(i64::MAX as f64)), Some(Ordering::Less));
+        assert_eq!(Value::Integer(-3).compare(&Value::Real(-2.5)), Some(Ordering::Less));
+    }
+
+    #[test]
+    fn text_compares_as_the_type_of_the_other_operand() {
+        assert_eq!(Value::Text("10".to_string()).compare(&Value::Integer(9)), Some(Ordering::Greater));
+        assert_eq!(Value::Real(2.5).compare(&Value::Text("2.5".to_string())), Some(Ordering::Equal));
+        assert_eq!(Value::Text("10".to_string()).compare(&Value::Text("9".to_string())), Some(Ordering::Less));
+        assert_eq!(Value::Text("abc".to_string()).compare(&Value::Integer(5)), None);
+        assert_eq!(Value::Integer(1).compare(&Value::Blob(vec![1])), None);
+    }
+
+    #[test]
+    fn coercion_keeps_values_that_fit_the_column_type() {
+        assert_eq!(Value::Text(" 42 ".to_string()).coerce_to(DataType::Integer), Some(Value::Integer(42)));
+        assert_eq!(Value::Real(3.0).coerce_to(DataType::Integer), Some(Value::Integer(3)));
+        assert_eq!(Value::Integer(7).coerce_to(DataType::Real), Some(Value::Real(7.0)));
+        assert_eq!(Value::Text("yes".to_string()).coerce_to(DataType::Boolean), Some(Value::Boolean(true)));
+        assert_eq!(Value::Real(1.5).coerce_to(DataType::Text), Some(Value::Text("1.5".to_string())));
+        assert_eq!(Value::Null.coerce_to(DataType::Integer), Some(Value::Null));
+    }
+
+    #[test]
+    fn coercion_rejects_values_that_would_change() {
+        assert_eq!(Value::Real(3.5).coerce_to(DataType::Integer), None);
+        assert_eq!(Value::Text("abc".to_string()).coerce_to(DataType::Real), None);
+        assert_eq!(Value::Integer(2).coerce_to(DataType::Boolean), None);
+        assert_eq!(Value::Blob(vec![0xCA]).coerce_to(DataType::Text), None);
+    }
+}
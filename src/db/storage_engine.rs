@@ -1,7 +1,6 @@
-use super::query::{QueryStatistics, PlanningError};
-use super::schema::{Row, Table};
+use super::schema::{Column, DataType, Row, Table, Value, View};
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::fs::{File, OpenOptions};
 use std::io::{Error, ErrorKind, Read, Write};
 use std::path::Path;
@@ -10,6 +9,7 @@ use std::time::{SystemTime, UNIX_EPOCH};
 #[derive(Debug, Serialize, Deserialize, PartialEq, Eq, Clone)]
 pub struct StorageEngine {
     pub tables: HashMap<String, Table>,
+    pub views: HashMap<String, View>,
     pub metadata: StorageMetadata,
 }
 
@@ -33,7 +33,7 @@ impl Default for StorageMetadata {
             .as_secs();
         
         StorageMetadata {
-            version: "1.0.0".to_string(),
+            version: "2.0.0".to_string(),
             created_at: now,
             last_modified: now,
             total_operations: 0,
@@ -55,10 +55,17 @@ impl StorageMetadata {
     }
 }
 
+impl Default for StorageEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 impl StorageEngine {
     pub fn new() -> Self {
         StorageEngine {
             tables: HashMap::new(),
+            views: HashMap::new(),
             metadata: StorageMetadata::default(),
         }
     }
@@ -74,13 +81,13 @@ impl StorageEngine {
                 let mut total_values = 0;
                 
                 for row in table.rows.values() {
-                    if let Some(value) = row.data.get(column) {
+                    if let Some(value) = row.data.get(&column.name).filter(|value| !value.is_null()) {
                         unique_values.insert(value.clone());
                         total_values += 1;
                     }
                 }
                 
-                column_stats.insert(column.clone(), ColumnStatistics {
+                column_stats.insert(column.name.clone(), ColumnStatistics {
                     unique_values: unique_values.len(),
                     total_values,
                     selectivity: if total_values > 0 { 
@@ -105,7 +112,7 @@ impl StorageEngine {
     pub fn validate_table_schema(&self, table_name: &str, columns: &[String]) -> Result<(), StorageError> {
         if let Some(table) = self.tables.get(table_name) {
             for column in columns {
-                if !table.columns.contains(column) {
+                if !table.has_column(column) {
                     return Err(StorageError::ColumnNotFound {
                         table: table_name.to_string(),
                         column: column.clone(),
@@ -119,7 +126,7 @@ impl StorageEngine {
     }
 
     /// Create a new table with enhanced validation
-    pub fn create_table(&mut self, name: &str, columns: Vec<String>, primary_key: Option<&str>) -> Result<(), StorageError> {
+    pub fn create_table(&mut self, name: &str, columns: Vec<Column>, primary_key: Option<&str>) -> Result<(), StorageError> {
         // Validate table name
         if name.trim().is_empty() {
             return Err(StorageError::InvalidTableName(name.to_string()));
@@ -128,6 +135,9 @@ impl StorageEngine {
         if self.tables.contains_key(name) {
             return Err(StorageError::TableAlreadyExists(name.to_string()));
         }
+        if self.views.contains_key(name) {
+            return Err(StorageError::ViewAlreadyExists(name.to_string()));
+        }
 
         // Validate columns
         if columns.is_empty() {
@@ -137,14 +147,14 @@ impl StorageEngine {
         // Check for duplicate column names
         let mut unique_columns = std::collections::HashSet::new();
         for column in &columns {
-            if !unique_columns.insert(column.clone()) {
-                return Err(StorageError::InvalidSchema(format!("Duplicate column name: {}", column)));
+            if !unique_columns.insert(column.name.clone()) {
+                return Err(StorageError::InvalidSchema(format!("Duplicate column name: {}", column.name)));
             }
         }
 
         // Validate primary key
         if let Some(pk) = primary_key {
-            if !columns.contains(&pk.to_string()) {
+            if !columns.iter().any(|column| column.name == pk) {
                 return Err(StorageError::InvalidSchema(
                     format!("Primary key '{}' must be one of the table columns", pk)
                 ));
@@ -167,126 +177,248 @@ impl StorageEngine {
 
     /// Insert a row with enhanced validation
     pub fn insert_row(&mut self, table_name: &str, row: Row) -> Result<(), StorageError> {
+        self.insert_rows(table_name, vec![row]).map(|_| ())
+    }
+
+    /// Insert a batch of rows atomically: every row is validated, including primary
+    /// key uniqueness within the batch, before any is stored. Returns the stored rows.
+    pub fn insert_rows(&mut self, table_name: &str, rows: Vec<Row>) -> Result<Vec<Row>, StorageError> {
         // Get immutable reference first for validation
         let table = self.tables.get(table_name)
             .ok_or_else(|| StorageError::TableNotFound(table_name.to_string()))?;
 
         // Validate row data against table schema
-        self.validate_row_data(&row, table)?;
-
-        // Now get mutable reference for insertion
-        let table = self.tables.get_mut(table_name).unwrap();
+        let rows = rows.into_iter()
+            .map(|row| self.validate_row_data(row, table_name, table))
+            .collect::<Result<Vec<Row>, StorageError>>()?;
 
-        // Validate primary key uniqueness
+        // Validate primary key uniqueness against the table and the rest of the batch
         if let Some(pk) = &table.primary_key {
-            if let Some(pk_value) = row.data.get(pk) {
-                // Check for existing primary key
-                for existing_row in table.rows.values() {
-                    if let Some(existing_pk_value) = existing_row.data.get(pk) {
-                        if existing_pk_value == pk_value {
-                            return Err(StorageError::PrimaryKeyViolation {
-                                table: table_name.to_string(),
-                                key: pk.clone(),
-                                value: pk_value.clone(),
-                            });
-                        }
-                    }
+            let mut keys: HashSet<&Value> = table.rows.values()
+                .filter_map(|row| row.data.get(pk))
+                .collect();
+            for row in &rows {
+                let Some(pk_value) = row.data.get(pk).filter(|value| !value.is_null()) else {
+                    return Err(StorageError::MissingPrimaryKey {
+                        table: table_name.to_string(),
+                        key: pk.clone(),
+                    });
+                };
+                if !keys.insert(pk_value) {
+                    return Err(StorageError::PrimaryKeyViolation {
+                        table: table_name.to_string(),
+                        key: pk.clone(),
+                        value: pk_value.to_string(),
+                    });
                 }
-            } else {
-                return Err(StorageError::MissingPrimaryKey {
+            }
+        }
+
+        // Now get mutable reference for insertion. Row ids continue after the largest
+        // one, as deletes leave gaps that the row count would land in.
+        let table = self.tables.get_mut(table_name).unwrap();
+        let next_id = table.rows.keys().max().map_or(0, |id| id + 1);
+        table.rows.extend((next_id..).zip(rows.iter().cloned()));
+
+        self.metadata.update_timestamp();
+        self.metadata.total_rows_inserted += rows.len() as u64;
+        Ok(rows)
+    }
+
+    /// Insert a batch of rows, resolving primary key conflicts with `on_conflict`. It gets
+    /// the stored row and the proposed one, and returns new values for the stored row or
+    /// `None` to leave it alone. Rows apply in order, so later rows see earlier ones, but
+    /// a row may change only once: updating a row the batch already inserted or updated
+    /// is an error. Nothing is stored unless the whole batch succeeds. Returns the
+    /// inserted and updated rows.
+    pub fn upsert_rows<U>(&mut self, table_name: &str, rows: Vec<Row>, on_conflict: U) -> Result<Vec<Row>, StorageError>
+    where
+        U: Fn(&Row, &Row) -> Option<Vec<(String, Value)>>,
+    {
+        let table = self.tables.get(table_name)
+            .ok_or_else(|| StorageError::TableNotFound(table_name.to_string()))?;
+        let Some(pk) = &table.primary_key else {
+            return Err(StorageError::InvalidSchema(
+                format!("table '{}' has no primary key to detect conflicts on", table_name)
+            ));
+        };
+        let missing_key = || StorageError::MissingPrimaryKey {
+            table: table_name.to_string(),
+            key: pk.clone(),
+        };
+
+        // Changed rows by row id, and the row id holding each primary key
+        let mut staged: HashMap<usize, Row> = HashMap::new();
+        let mut keys: HashMap<Value, usize> = table.rows.iter()
+            .filter_map(|(row_id, row)| Some((row.data.get(pk)?.clone(), *row_id)))
+            .collect();
+        let next_id = table.rows.keys().max().map_or(0, |id| id + 1);
+        let (mut inserted, mut updated) = (0, 0);
+        let mut affected = Vec::new();
+
+        for row in rows {
+            let row = self.validate_row_data(row, table_name, table)?;
+            let key = row.data.get(pk).filter(|value| !value.is_null()).ok_or_else(missing_key)?.clone();
+            let Some(&row_id) = keys.get(&key) else {
+                let row_id = next_id + inserted;
+                keys.insert(key, row_id);
+                staged.insert(row_id, row);
+                affected.push(row_id);
+                inserted += 1;
+                continue;
+            };
+
+            let existing = staged.get(&row_id).unwrap_or_else(|| &table.rows[&row_id]);
+            let Some(updates) = on_conflict(existing, &row) else {
+                continue;
+            };
+            if staged.contains_key(&row_id) {
+                return Err(StorageError::RowChangedTwice {
                     table: table_name.to_string(),
                     key: pk.clone(),
+                    value: key.to_string(),
                 });
             }
+            let mut new_row = existing.clone();
+            for (column, value) in updates {
+                let data_type = table.column(&column)
+                    .ok_or_else(|| StorageError::ColumnNotFound {
+                        table: table_name.to_string(),
+                        column: column.clone(),
+                    })?
+                    .data_type;
+                let value = coerce_value(table_name, &column, data_type, value)?;
+                new_row.data.insert(column, value);
+            }
+
+            // The update may move the row to another key, but not onto a taken one
+            let new_key = new_row.data.get(pk).filter(|value| !value.is_null()).ok_or_else(missing_key)?.clone();
+            if new_key != key {
+                if keys.contains_key(&new_key) {
+                    return Err(StorageError::PrimaryKeyViolation {
+                        table: table_name.to_string(),
+                        key: pk.clone(),
+                        value: new_key.to_string(),
+                    });
+                }
+                keys.remove(&key);
+                keys.insert(new_key, row_id);
+            }
+            staged.insert(row_id, new_row);
+            affected.push(row_id);
+            updated += 1;
         }
 
-        let row_id = table.rows.len();
-        table.rows.insert(row_id, row);
-        
-        self.metadata.update_timestamp();
-        self.metadata.total_rows_inserted += 1;
-        Ok(())
+        let affected: Vec<Row> = affected.iter().map(|row_id| staged[row_id].clone()).collect();
+        let table = self.tables.get_mut(table_name).unwrap();
+        table.rows.extend(staged);
+
+        if !affected.is_empty() {
+            self.metadata.update_timestamp();
+            self.metadata.total_rows_inserted += inserted as u64;
+            self.metadata.total_rows_updated += updated;
+        }
+        Ok(affected)
     }
 
-    /// Update rows with enhanced error handling
-    pub fn update_rows<F>(
+    /// Update matching rows atomically. `updates` computes each row's new values from
+    /// its old ones; every new row is built and checked before any is stored. Returns
+    /// the updated rows in storage order.
+    pub fn update_rows<F, U>(
         &mut self,
         table_name: &str,
-        updates: HashMap<String, String>,
+        updates: U,
         condition: F,
-    ) -> Result<usize, StorageError>
+    ) -> Result<Vec<Row>, StorageError>
     where
         F: Fn(&Row) -> bool,
+        U: Fn(&Row) -> Vec<(String, Value)>,
     {
-        let table = self.tables.get_mut(table_name)
+        let table = self.tables.get(table_name)
             .ok_or_else(|| StorageError::TableNotFound(table_name.to_string()))?;
 
-        // Validate update columns exist
-        for column in updates.keys() {
-            if !table.columns.contains(column) {
-                return Err(StorageError::ColumnNotFound {
-                    table: table_name.to_string(),
-                    column: column.clone(),
-                });
+        // Validate update columns exist and coerce values to the column types
+        let mut changes = Vec::new();
+        for (&row_id, row) in &table.rows {
+            if !condition(row) {
+                continue;
             }
+            let mut new_row = row.clone();
+            for (column, value) in updates(row) {
+                let data_type = table.column(&column)
+                    .ok_or_else(|| StorageError::ColumnNotFound {
+                        table: table_name.to_string(),
+                        column: column.clone(),
+                    })?
+                    .data_type;
+                let value = coerce_value(table_name, &column, data_type, value)?;
+                new_row.data.insert(column, value);
+            }
+            changes.push((row_id, new_row));
         }
 
-        // Check primary key constraints for updates
+        // Check primary key constraints against the computed keys, so rows may trade
+        // keys with each other but not collide with an untouched row or one another
         if let Some(pk) = &table.primary_key {
-            if let Some(new_pk_value) = updates.get(pk) {
-                // Check if the new primary key value would create a duplicate
-                for row in table.rows.values() {
-                    if !condition(row) { // Skip rows that won't be updated
-                        if let Some(existing_pk_value) = row.data.get(pk) {
-                            if existing_pk_value == new_pk_value {
-                                return Err(StorageError::PrimaryKeyViolation {
-                                    table: table_name.to_string(),
-                                    key: pk.clone(),
-                                    value: new_pk_value.clone(),
-                                });
-                            }
-                        }
+            let changed: HashSet<usize> = changes.iter().map(|(row_id, _)| *row_id).collect();
+            let mut keys: HashSet<&Value> = table.rows.iter()
+                .filter(|(row_id, _)| !changed.contains(row_id))
+                .filter_map(|(_, row)| row.data.get(pk))
+                .collect();
+            for (row_id, row) in &changes {
+                match row.data.get(pk).filter(|value| !value.is_null()) {
+                    Some(pk_value) if !keys.insert(pk_value) => {
+                        return Err(StorageError::PrimaryKeyViolation {
+                            table: table_name.to_string(),
+                            key: pk.clone(),
+                            value: pk_value.to_string(),
+                        });
                     }
+                    Some(_) => {}
+                    None if table.rows[row_id].data.get(pk) != row.data.get(pk) => {
+                        return Err(StorageError::MissingPrimaryKey {
+                            table: table_name.to_string(),
+                            key: pk.clone(),
+                        });
+                    }
+                    None => {}
                 }
             }
         }
 
-        let mut updated_count = 0;
-        for row in table.rows.values_mut() {
-            if condition(row) {
-                for (column, value) in &updates {
-                    row.data.insert(column.clone(), value.clone());
-                }
-                updated_count += 1;
-            }
-        }
+        changes.sort_by_key(|(row_id, _)| *row_id);
+        let table = self.tables.get_mut(table_name).unwrap();
+        table.rows.extend(changes.iter().cloned());
 
-        if updated_count > 0 {
+        if !changes.is_empty() {
             self.metadata.update_timestamp();
-            self.metadata.total_rows_updated += updated_count as u64;
+            self.metadata.total_rows_updated += changes.len() as u64;
         }
 
-        Ok(updated_count)
+        Ok(changes.into_iter().map(|(_, row)| row).collect())
     }
 
-    /// Delete rows with count tracking
-    pub fn delete_rows<F>(&mut self, table_name: &str, condition: F) -> Result<usize, StorageError>
+    /// Delete rows with count tracking; returns the removed rows in storage order
+    pub fn delete_rows<F>(&mut self, table_name: &str, condition: F) -> Result<Vec<Row>, StorageError>
     where
         F: Fn(&Row) -> bool,
     {
         let table = self.tables.get_mut(table_name)
             .ok_or_else(|| StorageError::TableNotFound(table_name.to_string()))?;
 
-        let initial_count = table.rows.len();
-        table.rows.retain(|_, row| !condition(row));
-        let deleted_count = initial_count - table.rows.len();
+        let mut row_ids: Vec<usize> = table.rows.iter()
+            .filter(|(_, row)| condition(row))
+            .map(|(row_id, _)| *row_id)
+            .collect();
+        row_ids.sort_unstable();
+        let deleted: Vec<Row> = row_ids.iter().filter_map(|row_id| table.rows.remove(row_id)).collect();
 
-        if deleted_count > 0 {
+        if !deleted.is_empty() {
             self.metadata.update_timestamp();
-            self.metadata.total_rows_deleted += deleted_count as u64;
+            self.metadata.total_rows_deleted += deleted.len() as u64;
         }
 
-        Ok(deleted_count)
+        Ok(deleted)
     }
 
     /// Drop a table
@@ -299,6 +431,152 @@ impl StorageEngine {
         }
     }
 
+    /// Create a view, or replace the definition of an existing one when `replace` is set.
+    /// Views share their names with tables.
+    pub fn create_view(&mut self, name: &str, view: View, replace: bool) -> Result<(), StorageError> {
+        if name.trim().is_empty() {
+            return Err(StorageError::InvalidTableName(name.to_string()));
+        }
+        if self.tables.contains_key(name) {
+            return Err(StorageError::TableAlreadyExists(name.to_string()));
+        }
+        if self.views.contains_key(name) && !replace {
+            return Err(StorageError::ViewAlreadyExists(name.to_string()));
+        }
+
+        self.views.insert(name.to_string(), view);
+        self.metadata.update_timestamp();
+        Ok(())
+    }
+
+    /// Drop a view
+    pub fn drop_view(&mut self, name: &str) -> Result<(), StorageError> {
+        if self.views.remove(name).is_some() {
+            self.metadata.update_timestamp();
+            Ok(())
+        } else {
+            Err(StorageError::ViewNotFound(name.to_string()))
+        }
+    }
+
+    /// Add a column to an existing table; existing rows have no value for it
+    pub fn add_column(&mut self, table_name: &str, column: Column, primary_key: bool) -> Result<(), StorageError> {
+        let table = self.tables.get_mut(table_name)
+            .ok_or_else(|| StorageError::TableNotFound(table_name.to_string()))?;
+
+        if column.name.trim().is_empty() {
+            return Err(StorageError::InvalidSchema("Column name cannot be empty".to_string()));
+        }
+
+        if table.has_column(&column.name) {
+            return Err(StorageError::InvalidSchema(format!("Duplicate column name: {}", column.name)));
+        }
+
+        if primary_key {
+            if let Some(pk) = &table.primary_key {
+                return Err(StorageError::InvalidSchema(
+                    format!("Table '{}' already has primary key '{}'", table_name, pk)
+                ));
+            }
+            if !table.rows.is_empty() {
+                return Err(StorageError::MissingPrimaryKey {
+                    table: table_name.to_string(),
+                    key: column.name.clone(),
+                });
+            }
+            table.primary_key = Some(column.name.clone());
+        }
+
+        table.columns.push(column);
+
+        self.metadata.update_timestamp();
+        Ok(())
+    }
+
+    /// Drop a column and remove its values from every row
+    pub fn drop_column(&mut self, table_name: &str, column: &str) -> Result<(), StorageError> {
+        let table = self.tables.get_mut(table_name)
+            .ok_or_else(|| StorageError::TableNotFound(table_name.to_string()))?;
+
+        let position = table.columns.iter().position(|existing| existing.name == column)
+            .ok_or_else(|| StorageError::ColumnNotFound {
+                table: table_name.to_string(),
+                column: column.to_string(),
+            })?;
+
+        if table.columns.len() == 1 {
+            return Err(StorageError::InvalidSchema("Table must have at least one column".to_string()));
+        }
+
+        table.columns.remove(position);
+        for row in table.rows.values_mut() {
+            row.data.remove(column);
+        }
+
+        // Dropping the key column drops the primary key constraint with it
+        if table.primary_key.as_deref() == Some(column) {
+            table.primary_key = None;
+        }
+
+        self.metadata.update_timestamp();
+        Ok(())
+    }
+
+    /// Rename a column, rewriting row data and the primary key
+    pub fn rename_column(&mut self, table_name: &str, column: &str, new_name: &str) -> Result<(), StorageError> {
+        let table = self.tables.get_mut(table_name)
+            .ok_or_else(|| StorageError::TableNotFound(table_name.to_string()))?;
+
+        let position = table.columns.iter().position(|existing| existing.name == column)
+            .ok_or_else(|| StorageError::ColumnNotFound {
+                table: table_name.to_string(),
+                column: column.to_string(),
+            })?;
+
+        if new_name.trim().is_empty() {
+            return Err(StorageError::InvalidSchema("Column name cannot be empty".to_string()));
+        }
+
+        if table.has_column(new_name) {
+            return Err(StorageError::InvalidSchema(format!("Duplicate column name: {}", new_name)));
+        }
+
+        table.columns[position].name = new_name.to_string();
+        for row in table.rows.values_mut() {
+            if let Some(value) = row.data.remove(column) {
+                row.data.insert(new_name.to_string(), value);
+            }
+        }
+
+        if table.primary_key.as_deref() == Some(column) {
+            table.primary_key = Some(new_name.to_string());
+        }
+
+        self.metadata.update_timestamp();
+        Ok(())
+    }
+
+    /// Rename a table
+    pub fn rename_table(&mut self, table_name: &str, new_name: &str) -> Result<(), StorageError> {
+        if new_name.trim().is_empty() {
+            return Err(StorageError::InvalidTableName(new_name.to_string()));
+        }
+
+        if self.tables.contains_key(new_name) {
+            return Err(StorageError::TableAlreadyExists(new_name.to_string()));
+        }
+        if self.views.contains_key(new_name) {
+            return Err(StorageError::ViewAlreadyExists(new_name.to_string()));
+        }
+
+        let table = self.tables.remove(table_name)
+            .ok_or_else(|| StorageError::TableNotFound(table_name.to_string()))?;
+        self.tables.insert(new_name.to_string(), table);
+
+        self.metadata.update_timestamp();
+        Ok(())
+    }
+
     /// Get all table names
     pub fn get_table_names(&self) -> Vec<String> {
         self.tables.keys().cloned().collect()
@@ -309,23 +587,28 @@ impl StorageEngine {
         self.tables.get(table_name)
     }
 
-    /// Validate row data against table schema
-    fn validate_row_data(&self, row: &Row, table: &Table) -> Result<(), StorageError> {
-        // Check for unknown columns
-        for column in row.data.keys() {
-            if !table.columns.contains(column) {
-                return Err(StorageError::ColumnNotFound {
-                    table: "unknown".to_string(), // We don't have table name here
+    /// Validate row data against table schema, coercing values to the column types
+    fn validate_row_data(&self, row: Row, table_name: &str, table: &Table) -> Result<Row, StorageError> {
+        let mut data = HashMap::new();
+        for (column, value) in row.data {
+            // Check for unknown columns
+            let data_type = table.column(&column)
+                .ok_or_else(|| StorageError::ColumnNotFound {
+                    table: table_name.to_string(),
                     column: column.clone(),
-                });
-            }
+                })?
+                .data_type;
+            let value = coerce_value(table_name, &column, data_type, value)?;
+            data.insert(column, value);
         }
-        Ok(())
+        Ok(Row { data })
     }
 
-    /// Serialize storage engine
+    /// Serialize storage engine, prefixed with the file format header
     pub fn serialize(&self, buffer: &mut Vec<u8>) -> Result<(), std::io::Error> {
         buffer.clear();
+        buffer.extend_from_slice(STORAGE_MAGIC);
+        buffer.extend_from_slice(&STORAGE_FORMAT_VERSION.to_le_bytes());
         match bincode::serialize(self) {
             Ok(data) => {
                 buffer.extend(data);
@@ -338,9 +621,40 @@ impl StorageEngine {
         }
     }
 
-    /// Deserialize storage engine
+    /// Deserialize storage engine, migrating files written by older versions
     pub fn deserialize(buffer: &[u8]) -> Result<Self, std::io::Error> {
-        match bincode::deserialize(buffer) {
+        let Some(payload) = buffer.strip_prefix(STORAGE_MAGIC.as_slice()) else {
+            // Files without a header predate typed values
+            return match bincode::deserialize::<LegacyStorageEngine>(buffer) {
+                Ok(legacy) => Ok(legacy.migrate()),
+                Err(e) => Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Deserialization failed: {}", e),
+                )),
+            };
+        };
+
+        if payload.len() < 4 {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                "Deserialization failed: truncated header",
+            ));
+        }
+        let (version, payload) = payload.split_at(4);
+        let version = u32::from_le_bytes([version[0], version[1], version[2], version[3]]);
+        let engine = match version {
+            STORAGE_FORMAT_VERSION => bincode::deserialize(payload),
+            // Version 2 files have no views
+            2 => bincode::deserialize::<StorageEngineV2>(payload).map(StorageEngineV2::migrate),
+            _ => {
+                return Err(std::io::Error::new(
+                    std::io::ErrorKind::InvalidData,
+                    format!("Deserialization failed: unsupported format version {}", version),
+                ));
+            }
+        };
+
+        match engine {
             Ok(engine) => Ok(engine),
             Err(e) => Err(std::io::Error::new(
                 std::io::ErrorKind::InvalidData,
@@ -350,6 +664,130 @@ impl StorageEngine {
     }
 }
 
+/// Coerce a value to a column type, reporting a type mismatch when it doesn't fit
+fn coerce_value(table: &str, column: &str, data_type: DataType, value: Value) -> Result<Value, StorageError> {
+    value.coerce_to(data_type).ok_or_else(|| StorageError::TypeMismatch {
+        table: table.to_string(),
+        column: column.to_string(),
+        expected: data_type,
+        value: value.to_string(),
+    })
+}
+
+const STORAGE_MAGIC: &[u8; 4] = b"HVDB";
+const STORAGE_FORMAT_VERSION: u32 = 3;
+
+// Typed layout of format version 2, before views were stored
+#[derive(Deserialize)]
+struct StorageEngineV2 {
+    tables: HashMap<String, Table>,
+    metadata: StorageMetadata,
+}
+
+impl StorageEngineV2 {
+    fn migrate(self) -> StorageEngine {
+        StorageEngine {
+            tables: self.tables,
+            views: HashMap::new(),
+            metadata: self.metadata,
+        }
+    }
+}
+
+// Untyped on-disk layout used before format version 2, where every value was a string
+#[derive(Deserialize)]
+struct LegacyStorageEngine {
+    tables: HashMap<String, LegacyTable>,
+    metadata: StorageMetadata,
+}
+
+#[derive(Deserialize)]
+struct LegacyTable {
+    columns: Vec<String>,
+    rows: HashMap<usize, LegacyRow>,
+    primary_key: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct LegacyRow {
+    data: HashMap<String, String>,
+}
+
+impl LegacyStorageEngine {
+    fn migrate(self) -> StorageEngine {
+        let tables = self.tables
+            .into_iter()
+            .map(|(name, table)| (name, table.migrate()))
+            .collect();
+
+        StorageEngine {
+            tables,
+            views: HashMap::new(),
+            metadata: StorageMetadata {
+                version: StorageMetadata::default().version,
+                ..self.metadata
+            },
+        }
+    }
+}
+
+impl LegacyTable {
+    /// Infer a type per column from the stored strings and convert the rows. A column
+    /// becomes numeric only when every value reads back exactly as it was stored.
+    fn migrate(self) -> Table {
+        let columns: Vec<Column> = self.columns
+            .iter()
+            .map(|name| {
+                let values = || self.rows.values().filter_map(|row| row.data.get(name));
+                let data_type = if values().next().is_none() {
+                    DataType::Text
+                } else if values().all(|value| round_trips(value, DataType::Integer)) {
+                    DataType::Integer
+                } else if values().all(|value| round_trips(value, DataType::Real)) {
+                    DataType::Real
+                } else {
+                    DataType::Text
+                };
+                Column::new(name, data_type)
+            })
+            .collect();
+
+        let rows = self.rows
+            .into_iter()
+            .map(|(id, row)| {
+                let data = row.data
+                    .into_iter()
+                    .map(|(column, value)| {
+                        let text = Value::Text(value);
+                        let data_type = columns.iter()
+                            .find(|c| c.name == column)
+                            .map_or(DataType::Text, |c| c.data_type);
+                        let value = text.coerce_to(data_type).unwrap_or(text);
+                        (column, value)
+                    })
+                    .collect();
+                (id, Row { data })
+            })
+            .collect();
+
+        Table {
+            columns,
+            rows,
+            primary_key: self.primary_key,
+        }
+    }
+}
+
+/// Whether a stored string converts to the number type and prints back unchanged, so
+/// that values such as `00123`, `1e5` or `nan` are not rewritten by the migration
+fn round_trips(value: &str, data_type: DataType) -> bool {
+    match data_type {
+        DataType::Integer => value.parse::<i64>().is_ok_and(|integer| integer.to_string() == value),
+        DataType::Real => value.parse::<f64>().is_ok_and(|real| real.is_finite() && real.to_string() == value),
+        _ => true,
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct FileSystem {
     pub storage_engine: StorageEngine,
@@ -371,66 +809,122 @@ impl FileSystem {
     }
 
     /// Create table with file persistence
-    pub fn create_table(&mut self, name: &str, columns: Vec<String>, primary_key: Option<&str>) {
-        if let Err(e) = self.storage_engine.create_table(name, columns, primary_key) {
-            eprintln!("Failed to create table: {}", e);
-            return;
-        }
-        if let Err(e) = self.save_to_file() {
-            eprintln!("Failed to save after table creation: {}", e);
-        }
+    pub fn create_table(&mut self, name: &str, columns: Vec<Column>, primary_key: Option<&str>) -> Result<(), StorageError> {
+        self.storage_engine.create_table(name, columns, primary_key)?;
+        self.save_to_file()?;
+        Ok(())
+    }
+
+    /// Drop table with file persistence
+    pub fn drop_table(&mut self, name: &str) -> Result<(), StorageError> {
+        self.storage_engine.drop_table(name)?;
+        self.save_to_file()?;
+        Ok(())
+    }
+
+    /// Create or replace a view with file persistence
+    pub fn create_view(&mut self, name: &str, view: View, replace: bool) -> Result<(), StorageError> {
+        self.storage_engine.create_view(name, view, replace)?;
+        self.save_to_file()?;
+        Ok(())
+    }
+
+    /// Drop view with file persistence
+    pub fn drop_view(&mut self, name: &str) -> Result<(), StorageError> {
+        self.storage_engine.drop_view(name)?;
+        self.save_to_file()?;
+        Ok(())
+    }
+
+    /// Add column with file persistence
+    pub fn add_column(&mut self, table_name: &str, column: Column, primary_key: bool) -> Result<(), StorageError> {
+        self.storage_engine.add_column(table_name, column, primary_key)?;
+        self.save_to_file()?;
+        Ok(())
+    }
+
+    /// Drop column with file persistence
+    pub fn drop_column(&mut self, table_name: &str, column: &str) -> Result<(), StorageError> {
+        self.storage_engine.drop_column(table_name, column)?;
+        self.save_to_file()?;
+        Ok(())
+    }
+
+    /// Rename column with file persistence
+    pub fn rename_column(&mut self, table_name: &str, column: &str, new_name: &str) -> Result<(), StorageError> {
+        self.storage_engine.rename_column(table_name, column, new_name)?;
+        self.save_to_file()?;
+        Ok(())
+    }
+
+    /// Rename table with file persistence
+    pub fn rename_table(&mut self, table_name: &str, new_name: &str) -> Result<(), StorageError> {
+        self.storage_engine.rename_table(table_name, new_name)?;
+        self.save_to_file()?;
+        Ok(())
     }
 
     /// Insert row with file persistence
     pub fn insert_row(&mut self, table_name: &str, row: Row) -> Result<(), std::io::Error> {
-        match self.storage_engine.insert_row(table_name, row) {
-            Ok(_) => self.save_to_file(),
+        self.insert_rows(table_name, vec![row]).map(|_| ())
+    }
+
+    /// Insert rows atomically, saving the file once for the whole batch
+    pub fn insert_rows(&mut self, table_name: &str, rows: Vec<Row>) -> Result<Vec<Row>, std::io::Error> {
+        match self.storage_engine.insert_rows(table_name, rows) {
+            Ok(rows) => self.save_to_file().map(|_| rows),
+            Err(e) => Err(Error::new(ErrorKind::InvalidInput, e.to_string())),
+        }
+    }
+
+    /// Upsert rows atomically, saving the file once for the whole batch
+    pub fn upsert_rows<U>(&mut self, table_name: &str, rows: Vec<Row>, on_conflict: U) -> Result<Vec<Row>, std::io::Error>
+    where
+        U: Fn(&Row, &Row) -> Option<Vec<(String, Value)>>,
+    {
+        match self.storage_engine.upsert_rows(table_name, rows, on_conflict) {
+            Ok(rows) => self.save_to_file().map(|_| rows),
             Err(e) => Err(Error::new(ErrorKind::InvalidInput, e.to_string())),
         }
     }
 
     /// Update rows with file persistence
-    pub fn update_rows<F>(
+    pub fn update_rows<F, U>(
         &mut self,
         table_name: &str,
-        updates: HashMap<String, String>,
+        updates: U,
         condition: F,
     ) -> Result<Vec<Row>, String>
     where
         F: Fn(&Row) -> bool,
+        U: Fn(&Row) -> Vec<(String, Value)>,
     {
-        match self.storage_engine.update_rows(table_name, updates.clone(), condition) {
-            Ok(count) => {
+        match self.storage_engine.update_rows(table_name, updates, condition) {
+            Ok(rows) => {
                 if let Err(e) = self.save_to_file() {
                     return Err(format!("Failed to save after update: {}", e));
                 }
-                // Return a dummy row to maintain compatibility
-                let mut result_row_data = HashMap::new();
-                for (key, value) in updates {
-                    result_row_data.insert(key, value);
-                }
-                Ok(vec![Row { data: result_row_data }])
+                Ok(rows)
             }
             Err(e) => Err(e.to_string()),
         }
     }
 
     /// Delete rows with file persistence
-    pub fn delete_rows<F>(&mut self, table_name: &str, condition: F)
+    pub fn delete_rows<F>(&mut self, table_name: &str, condition: F) -> Result<Vec<Row>, String>
     where
         F: Fn(&Row) -> bool,
     {
         match self.storage_engine.delete_rows(table_name, condition) {
-            Ok(count) => {
-                if count > 0 {
+            Ok(rows) => {
+                if !rows.is_empty() {
                     if let Err(e) = self.save_to_file() {
-                        eprintln!("Failed to save after delete: {}", e);
+                        return Err(format!("Failed to save after delete: {}", e));
                     }
                 }
+                Ok(rows)
             }
-            Err(e) => {
-                eprintln!("Failed to delete rows: {}", e);
-            }
+            Err(e) => Err(e.to_string()),
         }
     }
 
@@ -446,7 +940,7 @@ impl FileSystem {
             for column in &projection {
                 row_data.insert(
                     column.0.clone(),
-                    row.data.get(&column.0).cloned().unwrap_or_default(),
+                    row.data.get(&column.0).cloned().unwrap_or(Value::Null),
                 );
             }
             result.push(Row { data: row_data });
@@ -486,11 +980,16 @@ impl FileSystem {
 pub enum StorageError {
     TableNotFound(String),
     TableAlreadyExists(String),
+    ViewNotFound(String),
+    ViewAlreadyExists(String),
     ColumnNotFound { table: String, column: String },
     InvalidTableName(String),
     InvalidSchema(String),
     PrimaryKeyViolation { table: String, key: String, value: String },
     MissingPrimaryKey { table: String, key: String },
+    /// One batch proposed the same primary key for a row it had already changed
+    RowChangedTwice { table: String, key: String, value: String },
+    TypeMismatch { table: String, column: String, expected: DataType, value: String },
     IoError(std::io::Error),
 }
 
@@ -499,6 +998,8 @@ impl std::fmt::Display for StorageError {
         match self {
             StorageError::TableNotFound(table) => write!(f, "Table '{}' not found", table),
             StorageError::TableAlreadyExists(table) => write!(f, "Table '{}' already exists", table),
+            StorageError::ViewNotFound(view) => write!(f, "View '{}' not found", view),
+            StorageError::ViewAlreadyExists(view) => write!(f, "View '{}' already exists", view),
             StorageError::ColumnNotFound { table, column } => {
                 write!(f, "Column '{}' not found in table '{}'", column, table)
             }
@@ -510,6 +1011,12 @@ impl std::fmt::Display for StorageError {
             StorageError::MissingPrimaryKey { table, key } => {
                 write!(f, "Missing primary key '{}' in table '{}'", key, table)
             }
+            StorageError::RowChangedTwice { table, key, value } => {
+                write!(f, "Cannot change the row of table '{}' with key '{}' = '{}' a second time in one statement", table, key, value)
+            }
+            StorageError::TypeMismatch { table, column, expected, value } => {
+                write!(f, "Type mismatch in table '{}': value '{}' is not a valid {} for column '{}'", table, value, expected, column)
+            }
             StorageError::IoError(e) => write!(f, "IO error: {}", e),
         }
     }
@@ -537,3 +1044,213 @@ pub struct ColumnStatistics {
     pub total_values: usize,
     pub selectivity: f64,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn users() -> StorageEngine {
+        let mut storage = StorageEngine::new();
+        let columns = vec![Column::new("id", DataType::Integer), Column::new("name", DataType::Text)];
+        storage.create_table("users", columns, Some("id")).unwrap();
+        let row = Row { data: HashMap::from([
+            ("id".to_string(), Value::Integer(1)),
+            ("name".to_string(), Value::Text("Ann".to_string())),
+        ]) };
+        storage.insert_row("users", row).unwrap();
+        storage
+    }
+
+    #[test]
+    fn renaming_the_key_column_renames_row_data_and_primary_key() {
+        let mut storage = users();
+        storage.rename_column("users", "id", "user_id").unwrap();
+
+        let table = &storage.tables["users"];
+        assert_eq!(table.primary_key.as_deref(), Some("user_id"));
+        assert!(table.rows.values().all(|row| row.data.contains_key("user_id") && !row.data.contains_key("id")));
+    }
+
+    #[test]
+    fn dropping_the_key_column_drops_the_primary_key() {
+        let mut storage = users();
+        storage.drop_column("users", "id").unwrap();
+
+        let table = &storage.tables["users"];
+        assert_eq!(table.primary_key, None);
+        assert_eq!(table.column_names(), vec!["name".to_string()]);
+        assert!(matches!(storage.drop_column("users", "name"), Err(StorageError::InvalidSchema(_))));
+    }
+
+    #[test]
+    fn a_key_column_cannot_be_added_to_a_table_with_rows() {
+        let mut storage = users();
+        storage.drop_column("users", "id").unwrap();
+        assert!(matches!(storage.add_column("users", Column::new("id", DataType::Integer), true), Err(StorageError::MissingPrimaryKey { .. })));
+    }
+
+    #[test]
+    fn renaming_a_table_onto_an_existing_name_fails() {
+        let mut storage = users();
+        storage.create_table("orders", vec![Column::new("id", DataType::Integer)], None).unwrap();
+        assert!(matches!(storage.rename_table("users", "orders"), Err(StorageError::TableAlreadyExists(_))));
+
+        storage.rename_table("users", "people").unwrap();
+        assert!(storage.tables.contains_key("people") && !storage.tables.contains_key("users"));
+    }
+
+    #[test]
+    fn legacy_columns_become_numeric_only_when_every_value_round_trips() {
+        let samples = [
+            ("count", ["1", "-20"]),
+            ("price", ["1", "2.5"]),
+            ("zip", ["00123", "4"]),
+            ("code", ["1e5", "7"]),
+            ("score", ["nan", "1.5"]),
+            ("ceiling", ["inf", "1.5"]),
+        ];
+        let rows = (0..2)
+            .map(|position| {
+                let data = samples.iter()
+                    .map(|(column, values)| (column.to_string(), values[position].to_string()))
+                    .collect();
+                (position, LegacyRow { data })
+            })
+            .collect();
+        let columns = samples.iter().map(|(column, _)| column.to_string()).collect();
+        let table = LegacyTable { columns, rows, primary_key: None }.migrate();
+
+        let data_type = |name| table.column(name).unwrap().data_type;
+        assert_eq!(data_type("count"), DataType::Integer);
+        assert_eq!(data_type("price"), DataType::Real);
+        for name in ["zip", "code", "score", "ceiling"] {
+            assert_eq!(data_type(name), DataType::Text, "{}", name);
+        }
+        assert_eq!(table.rows[&0].data["zip"], Value::Text("00123".to_string()));
+        assert_eq!(table.rows[&1].data["count"], Value::Integer(-20));
+        assert_eq!(table.rows[&0].data["price"], Value::Real(1.0));
+    }
+
+    #[test]
+    fn a_batch_insert_stores_nothing_when_any_row_breaks_the_primary_key() {
+        let mut storage = users();
+        let user = |id: i64| Row { data: HashMap::from([
+            ("id".to_string(), Value::Integer(id)),
+            ("name".to_string(), Value::Text(format!("user {}", id))),
+        ]) };
+
+        let existing = storage.insert_rows("users", vec![user(2), user(1)]);
+        assert!(matches!(existing, Err(StorageError::PrimaryKeyViolation { .. })));
+        let within_batch = storage.insert_rows("users", vec![user(3), user(4), user(3)]);
+        assert!(matches!(within_batch, Err(StorageError::PrimaryKeyViolation { .. })));
+        assert_eq!(storage.tables["users"].rows.len(), 1);
+
+        assert_eq!(storage.insert_rows("users", vec![user(2), user(3)]).unwrap().len(), 2);
+        assert_eq!(storage.tables["users"].rows.len(), 3);
+    }
+
+    #[test]
+    fn deleted_rows_come_back_in_storage_order() {
+        let mut storage = users();
+        for id in 2..=6 {
+            let row = Row { data: HashMap::from([("id".to_string(), Value::Integer(id))]) };
+            storage.insert_row("users", row).unwrap();
+        }
+        let deleted = storage.delete_rows("users", |row| row.data["id"] != Value::Integer(3)).unwrap();
+        let ids: Vec<Value> = deleted.iter().map(|row| row.data["id"].clone()).collect();
+        assert_eq!(ids, [1, 2, 4, 5, 6].map(Value::Integer));
+        assert_eq!(storage.tables["users"].rows.len(), 1);
+        assert_eq!(storage.metadata.total_rows_deleted, 5);
+    }
+
+    #[test]
+    fn an_update_may_shift_keys_onto_ones_it_also_moves() {
+        let mut storage = users();
+        for id in [2, 3] {
+            let row = Row { data: HashMap::from([("id".to_string(), Value::Integer(id))]) };
+            storage.insert_row("users", row).unwrap();
+        }
+        let ids = |storage: &StorageEngine| {
+            let mut ids: Vec<Value> = storage.tables["users"].rows.values().map(|row| row.data["id"].clone()).collect();
+            ids.sort_by(|a, b| a.sort_cmp(b));
+            ids
+        };
+        let shift = |row: &Row| match row.data["id"] {
+            Value::Integer(id) => vec![("id".to_string(), Value::Integer(id + 1))],
+            _ => vec![],
+        };
+
+        assert_eq!(storage.update_rows("users", shift, |_| true).unwrap().len(), 3);
+        assert_eq!(ids(&storage), vec![Value::Integer(2), Value::Integer(3), Value::Integer(4)]);
+
+        // Moving only some keys must not land on a row left in place
+        let collision = storage.update_rows("users", shift, |row| row.data["id"] == Value::Integer(2));
+        assert!(matches!(collision, Err(StorageError::PrimaryKeyViolation { .. })));
+        assert_eq!(ids(&storage), vec![Value::Integer(2), Value::Integer(3), Value::Integer(4)]);
+    }
+
+    #[test]
+    fn an_upsert_changes_each_row_at_most_once() {
+        let mut storage = users();
+        let user = |id: i64, name: &str| Row { data: HashMap::from([
+            ("id".to_string(), Value::Integer(id)),
+            ("name".to_string(), Value::Text(name.to_string())),
+        ]) };
+        let rename = |_: &Row, proposed: &Row| Some(vec![("name".to_string(), proposed.data["name"].clone())]);
+        let keep = |_: &Row, _: &Row| None;
+
+        for batch in [vec![user(1, "Al"), user(1, "Bo")], vec![user(2, "Al"), user(2, "Bo")]] {
+            let twice = storage.upsert_rows("users", batch, rename);
+            assert!(matches!(twice, Err(StorageError::RowChangedTwice { .. })));
+            assert_eq!(storage.tables["users"].rows.len(), 1);
+        }
+
+        // Rows left alone on a conflict may come up again
+        let inserted = storage.upsert_rows("users", vec![user(2, "Al"), user(2, "Bo"), user(1, "Cy")], keep).unwrap();
+        assert_eq!(inserted, vec![user(2, "Al")]);
+        assert_eq!(storage.upsert_rows("users", vec![user(1, "Al"), user(2, "Bo")], rename).unwrap().len(), 2);
+    }
+
+    #[test]
+    fn serialized_storage_reads_back_with_its_views() {
+        let mut storage = users();
+        storage.create_view("names", View { definition: "SELECT name FROM users".to_string() }, false).unwrap();
+        let mut buffer = Vec::new();
+        storage.serialize(&mut buffer).unwrap();
+
+        let read = StorageEngine::deserialize(&buffer).unwrap();
+        assert_eq!(read.tables, storage.tables);
+        assert_eq!(read.views, storage.views);
+        assert_eq!(read.metadata, storage.metadata);
+    }
+
+    #[test]
+    fn version_2_files_read_back_without_views() {
+        let storage = users();
+        let mut buffer = STORAGE_MAGIC.to_vec();
+        buffer.extend_from_slice(&2u32.to_le_bytes());
+        buffer.extend(bincode::serialize(&(&storage.tables, &storage.metadata)).unwrap());
+
+        let read = StorageEngine::deserialize(&buffer).unwrap();
+        assert_eq!(read.tables, storage.tables);
+        assert!(read.views.is_empty());
+
+        buffer[4] = 9;
+        assert!(StorageEngine::deserialize(&buffer).is_err());
+    }
+
+    #[test]
+    fn legacy_files_without_a_header_are_migrated() {
+        let row = HashMap::from([("id".to_string(), "1".to_string()), ("name".to_string(), "Ann".to_string())]);
+        let table = (vec!["id".to_string(), "name".to_string()], HashMap::from([(0usize, row)]), Some("id".to_string()));
+        let legacy = (HashMap::from([("users".to_string(), table)]), StorageMetadata::default());
+        let buffer = bincode::serialize(&legacy).unwrap();
+
+        let read = StorageEngine::deserialize(&buffer).unwrap();
+        let users = &read.tables["users"];
+        assert_eq!(users.column("id").unwrap().data_type, DataType::Integer);
+        assert_eq!(users.primary_key.as_deref(), Some("id"));
+        assert_eq!(users.rows[&0].data["name"], Value::Text("Ann".to_string()));
+        assert!(read.views.is_empty());
+    }
+}
@@ -1,17 +1,14 @@
-use db::{
+use hyper_vault::db::{
     executor::{QueryExecutor, ExecutionError}, 
-    lexer::Tokenizer, 
-    parser::Parser, 
-    query::{QueryPlanner, QueryComplexity, analyze_query_complexity},
-    schema::Row,
-    storage_engine::{FileSystem, StorageError},
+    parser::{ASTNode, Parser},
+    query::{PreparedStatement, QueryPlan, QueryPlanner, QueryType, QueryComplexity, analyze_query_complexity},
+    schema::{Column, DataType, Row, Value},
+    storage_engine::FileSystem,
 };
 use std::collections::HashMap;
 use std::io::{self, Write};
 use std::time::Instant;
 
-mod db;
-
 fn main() {
     println!("🚀 Welcome to HyperVault Database!");
     println!("=====================================");
@@ -29,6 +26,13 @@ fn main() {
     // Display startup information
     display_startup_info(&filesystem);
 
+    // Run a script file given on the command line instead of the CLI loop
+    if let Some(path) = std::env::args().nth(1) {
+        run_script_file(&mut filesystem, &mut query_planner, &mut HashMap::new(), &path);
+        display_session_summary(&query_planner);
+        return;
+    }
+
     // Start the CLI loop
     run_cli(&mut filesystem, &mut query_planner);
 }
@@ -37,38 +41,40 @@ fn initialize_sample_data(filesystem: &mut FileSystem) {
     // Check if users table already exists, if not create it
     if !filesystem.storage_engine.tables.contains_key("users") {
         println!("📦 Initializing sample 'users' table...");
-        filesystem.create_table(
+        let created = filesystem.create_table(
             "users",
             vec![
-                "id".to_string(),
-                "name".to_string(),
-                "email".to_string(),
-                "age".to_string(),
+                Column::new("id", DataType::Integer),
+                Column::new("name", DataType::Text),
+                Column::new("email", DataType::Text),
+                Column::new("age", DataType::Integer),
             ],
             Some("id"),
         );
+        if let Err(e) = created {
+            eprintln!("❌ Failed to create sample table: {}", e);
+            return;
+        }
 
         // Insert sample data
         let sample_users = vec![
-            ("1", "Anthony Etienne", "anthony.etienne@gmail.com", "25"),
-            ("2", "Jane Doe", "jane.doe@example.com", "30"),
-            ("3", "Bob Smith", "bob.smith@example.com", "28"),
-            ("4", "Alice Johnson", "alice.johnson@example.com", "35"),
+            (1, "Anthony Etienne", "anthony.etienne@gmail.com", 25),
+            (2, "Jane Doe", "jane.doe@example.com", 30),
+            (3, "Bob Smith", "bob.smith@example.com", 28),
+            (4, "Alice Johnson", "alice.johnson@example.com", 35),
         ];
 
-        for (id, name, email, age) in sample_users {
-            let _ = filesystem.insert_row(
-                "users",
-                Row {
-                    data: HashMap::from([
-                        ("id".to_string(), id.to_string()),
-                        ("name".to_string(), name.to_string()),
-                        ("email".to_string(), email.to_string()),
-                        ("age".to_string(), age.to_string()),
-                    ]),
-                },
-            );
-        }
+        let rows = sample_users.into_iter()
+            .map(|(id, name, email, age)| Row {
+                data: HashMap::from([
+                    ("id".to_string(), Value::Integer(id)),
+                    ("name".to_string(), Value::Text(name.to_string())),
+                    ("email".to_string(), Value::Text(email.to_string())),
+                    ("age".to_string(), Value::Integer(age)),
+                ]),
+            })
+            .collect();
+        let _ = filesystem.insert_rows("users", rows);
 
         println!("✅ Sample data initialized successfully!");
         println!();
@@ -93,6 +99,7 @@ fn format_timestamp(timestamp: u64) -> String {
 }
 
 fn run_cli(filesystem: &mut FileSystem, query_planner: &mut QueryPlanner) {
+    let mut prepared = HashMap::new();
     loop {
         // Display prompt
         print!("hypervault> ");
@@ -101,11 +108,18 @@ fn run_cli(filesystem: &mut FileSystem, query_planner: &mut QueryPlanner) {
         // Read user input
         let mut input = String::new();
         match io::stdin().read_line(&mut input) {
+            Ok(0) => {
+                // End of input (Ctrl-D or the end of a piped script) ends the session
+                println!();
+                println!("👋 Goodbye! Thanks for using HyperVault Database!");
+                display_session_summary(query_planner);
+                break;
+            }
             Ok(_) => {
                 let input = input.trim();
                 
                 // Handle special commands
-                match input.to_lowercase().as_str() {
+                match input.trim_end_matches(';').trim_end().to_lowercase().as_str() {
                     "" => continue, // Skip empty input
                     "exit" | "quit" | "q" => {
                         println!("👋 Goodbye! Thanks for using HyperVault Database!");
@@ -137,8 +151,11 @@ fn run_cli(filesystem: &mut FileSystem, query_planner: &mut QueryPlanner) {
                     _ => {}
                 }
 
-                // Process SQL command
-                execute_sql_command(filesystem, query_planner, input);
+                // Run a script file, or the statements on the line
+                match strip_keyword(input, "SOURCE") {
+                    Some(path) => run_script_file(filesystem, query_planner, &mut prepared, path.trim_end_matches(';')),
+                    None => run_script(filesystem, query_planner, &mut prepared, input),
+                }
             }
             Err(error) => {
                 eprintln!("❌ Error reading input: {}", error);
@@ -149,111 +166,359 @@ fn run_cli(filesystem: &mut FileSystem, query_planner: &mut QueryPlanner) {
     }
 }
 
+/// Read a `.sql` file and run its statements
+fn run_script_file(
+    filesystem: &mut FileSystem,
+    query_planner: &mut QueryPlanner,
+    prepared: &mut HashMap<String, PreparedStatement>,
+    path: &str,
+) {
+    match std::fs::read_to_string(path) {
+        Ok(script) => {
+            println!("📜 Running script: {}", path);
+            run_script(filesystem, query_planner, prepared, &script);
+        }
+        Err(e) => eprintln!("❌ Cannot read script '{}': {}", path, e),
+    }
+}
+
+/// Run `;`-separated statements in order, showing each one's results. Every SQL
+/// statement is parsed first, so a syntax error does not leave a script half applied.
+fn run_script(
+    filesystem: &mut FileSystem,
+    query_planner: &mut QueryPlanner,
+    prepared: &mut HashMap<String, PreparedStatement>,
+    script: &str,
+) {
+    let statements = match Parser::split_statements(script) {
+        Ok(statements) => statements,
+        Err(err) => {
+            eprintln!("❌ Parse Error: {}", err);
+            return;
+        }
+    };
+    if let [statement] = statements.as_slice() {
+        run_command(filesystem, query_planner, prepared, statement);
+        return;
+    }
+
+    let mut parsed = Vec::new();
+    for (position, statement) in statements.iter().enumerate() {
+        if is_prepared_command(statement) {
+            parsed.push(None);
+            continue;
+        }
+        match Parser::parse(statement) {
+            Ok(ast) => parsed.push(Some(ast)),
+            Err(err) => {
+                eprintln!("❌ Parse Error in statement {}: {}", position + 1, err);
+                eprintln!("   {}", statement);
+                println!("💡 Tip: No statements were run. Type 'help' for examples.");
+                return;
+            }
+        }
+    }
+
+    for (position, (statement, ast)) in statements.iter().zip(parsed).enumerate() {
+        println!("── Statement {} of {}", position + 1, statements.len());
+        match ast {
+            Some(ast) => {
+                println!("🔍 Executing: {}", statement);
+                execute_statement(filesystem, query_planner, ast, Instant::now());
+            }
+            None => run_command(filesystem, query_planner, prepared, statement),
+        }
+    }
+}
+
+fn is_prepared_command(statement: &str) -> bool {
+    ["PREPARE", "EXECUTE", "DEALLOCATE"].iter().any(|keyword| strip_keyword(statement, keyword).is_some())
+}
+
+/// Run one statement: a prepared statement command or plain SQL
+fn run_command(
+    filesystem: &mut FileSystem,
+    query_planner: &mut QueryPlanner,
+    prepared: &mut HashMap<String, PreparedStatement>,
+    statement: &str,
+) {
+    let keyword = statement.split_whitespace().next().unwrap_or_default().to_uppercase();
+    match keyword.as_str() {
+        "PREPARE" => prepare_command(filesystem, query_planner, prepared, statement),
+        "EXECUTE" => execute_prepared_command(filesystem, query_planner, prepared, statement),
+        "DEALLOCATE" => {
+            let name = strip_keyword(statement, "DEALLOCATE").unwrap_or_default().to_lowercase();
+            match prepared.remove(&name) {
+                Some(_) => println!("✅ Deallocated '{}'", name),
+                None => eprintln!("❌ Prepared statement '{}' not found", name),
+            }
+        }
+        _ => execute_sql_command(filesystem, query_planner, statement),
+    }
+}
+
 fn execute_sql_command(filesystem: &mut FileSystem, query_planner: &mut QueryPlanner, input: &str) {
     println!("🔍 Executing: {}", input);
     
     let start_time = Instant::now();
-    let mut success = true;
     
     // Parse the SQL command
     match Parser::parse(input) {
         Ok(ast) => {
             println!("✅ Query parsed successfully");
+            execute_statement(filesystem, query_planner, ast, start_time);
+        }
+        Err(err) => {
+            eprintln!("❌ Parse Error: {}", err);
+            println!("💡 Tip: Check your SQL syntax. Type 'help' for examples.");
+            // We can't determine query type for failed parses, so we'll skip statistics update
+        }
+    }
+}
+
+/// Plan, validate and run a parsed statement
+fn execute_statement(filesystem: &mut FileSystem, query_planner: &mut QueryPlanner, ast: ASTNode, start_time: Instant) {
+    // Create and validate query plan using up-to-date table statistics
+    query_planner.refresh_statistics(&filesystem.storage_engine);
+    match query_planner.plan(&ast) {
+        Ok(plan) => {
+            // Analyze query complexity
+            let complexity = analyze_query_complexity(&plan);
+            println!("📈 Query complexity: {:?}", complexity);
             
-            // Create and validate query plan
-            match query_planner.plan(&ast) {
-                Ok(mut plan) => {
-                    // Analyze query complexity
-                    let complexity = analyze_query_complexity(&plan);
-                    println!("📈 Query complexity: {:?}", complexity);
-                    
-                    // Display query plan for complex queries
-                    if matches!(complexity, QueryComplexity::Complex) {
-                        println!("📋 Query plan:");
-                        display_query_plan(&plan);
-                    }
-                    
-                    // Validate plan if table exists
-                    if let Some(table) = filesystem.storage_engine.tables.get(&plan.table.0) {
-                        if let Err(e) = query_planner.validate_plan(&plan, true, &table.columns) {
-                            eprintln!("❌ Query validation failed: {}", e);
-                            success = false;
-                            return;
-                        }
-                    }
-                    
-                    // Execute the query
-                    let mut execution_engine = QueryExecutor::new(filesystem);
-                    match execution_engine.execute(ast) {
-                        Ok(result) => {
-                            println!("📊 Query Results:");
-                            display_results(&result);
-                            
-                            // Update statistics
-                            let execution_time = start_time.elapsed().as_secs_f64();
-                            query_planner.optimizer.update_statistics(&plan.query_type, execution_time, true);
-                        }
-                        Err(err) => {
-                            eprintln!("❌ Execution Error: {}", format_execution_error(&err));
-                            success = false;
-                        }
-                    }
-                }
-                Err(e) => {
-                    eprintln!("❌ Query Planning Error: {}", e);
-                    success = false;
+            // Display query plan for complex queries
+            if matches!(complexity, QueryComplexity::Complex) {
+                println!("📋 Query plan:");
+                display_query_plan(&plan);
+            }
+            
+            // Validate plan if the table or view exists; a view's query is always validated
+            let storage = &filesystem.storage_engine;
+            let exists = storage.tables.contains_key(&plan.table.0)
+                || storage.views.contains_key(&plan.table.0)
+                || plan.ctes.iter().any(|cte| cte.name == plan.table);
+            if exists || matches!(plan.query_type, QueryType::CreateView) {
+                let validation = query_planner.validate(&plan, &filesystem.storage_engine.tables);
+                if let Err(e) = validation {
+                    eprintln!("❌ Query validation failed: {}", e);
+                    let execution_time = start_time.elapsed().as_secs_f64();
+                    query_planner.optimizer.update_statistics(&plan.query_type, execution_time, false);
+                    return;
                 }
             }
+            
+            run_query(filesystem, query_planner, ast, &plan, start_time);
+        }
+        Err(e) => {
+            eprintln!("❌ Query Planning Error: {}", e);
+        }
+    }
+}
+
+/// Execute a planned statement and show its results
+fn run_query(filesystem: &mut FileSystem, query_planner: &mut QueryPlanner, ast: ASTNode, plan: &QueryPlan, start_time: Instant) {
+    // An upsert's count mixes inserted and updated rows
+    let upsert = matches!(ast, ASTNode::InsertStatement { on_conflict: Some(_), .. });
+    let mut execution_engine = QueryExecutor::new(filesystem);
+    match execution_engine.execute(ast) {
+        Ok(result) => {
+            if let Some(count) = result.affected_rows {
+                let verb = match plan.query_type {
+                    QueryType::Update => "updated",
+                    QueryType::Delete => "deleted",
+                    _ if upsert => "inserted or updated",
+                    _ => "inserted",
+                };
+                println!("✅ {} row(s) {}", count, verb);
+            }
+            // Schema changes report a status line instead of an empty result table, and
+            // writes show rows only when they have a RETURNING clause
+            let schema_change = match plan.query_type {
+                QueryType::CreateTable => Some(("Table", "created")),
+                QueryType::DropTable => Some(("Table", "dropped")),
+                QueryType::AlterTable => Some(("Table", "altered")),
+                QueryType::CreateView => Some(("View", "created")),
+                QueryType::DropView => Some(("View", "dropped")),
+                _ => None,
+            };
+            if let Some((kind, verb)) = schema_change {
+                println!("✅ {} '{}' {}", kind, plan.table.0, verb);
+            } else if result.affected_rows.is_none() || !result.columns.is_empty() {
+                println!("📊 Query Results:");
+                display_results(&result.columns, &result.rows);
+            }
+
+            // Update statistics
+            let execution_time = start_time.elapsed().as_secs_f64();
+            query_planner.optimizer.update_statistics(&plan.query_type, execution_time, true);
         }
         Err(err) => {
-            eprintln!("❌ Parse Error: {}", err);
-            println!("💡 Tip: Check your SQL syntax. Type 'help' for examples.");
-            success = false;
+            eprintln!("❌ Execution Error: {}", format_execution_error(&err));
+            let execution_time = start_time.elapsed().as_secs_f64();
+            query_planner.optimizer.update_statistics(&plan.query_type, execution_time, false);
         }
     }
-    
-    // Update statistics for failed queries
-    if !success {
-        let execution_time = start_time.elapsed().as_secs_f64();
-        // We can't determine query type for failed parses, so we'll skip statistics update
+}
+
+/// Strip a leading keyword, in any case, and the whitespace after it
+fn strip_keyword<'a>(input: &'a str, keyword: &str) -> Option<&'a str> {
+    let rest = input.get(keyword.len()..)?;
+    if !input[..keyword.len()].eq_ignore_ascii_case(keyword) {
+        return None;
+    }
+    match rest.chars().next() {
+        None => Some(rest),
+        Some(c) if c.is_whitespace() => Some(rest.trim_start()),
+        Some(_) => None,
+    }
+}
+
+/// `PREPARE name AS statement`: parse, plan and validate a statement with placeholders
+fn prepare_command(
+    filesystem: &FileSystem,
+    query_planner: &mut QueryPlanner,
+    prepared: &mut HashMap<String, PreparedStatement>,
+    input: &str,
+) {
+    let definition = strip_keyword(input, "PREPARE").and_then(|rest| {
+        let (name, rest) = rest.split_once(char::is_whitespace)?;
+        Some((name.to_lowercase(), strip_keyword(rest.trim_start(), "AS")?))
+    });
+    let Some((name, statement)) = definition else {
+        eprintln!("❌ Usage: PREPARE name AS statement");
+        return;
+    };
+
+    match query_planner.prepare(statement, &filesystem.storage_engine) {
+        Ok(statement) => {
+            let types: Vec<String> = statement.parameter_types().iter()
+                .map(|data_type| data_type.map_or_else(|| "ANY".to_string(), |data_type| data_type.to_string()))
+                .collect();
+            if types.is_empty() {
+                println!("✅ Prepared '{}'", name);
+            } else {
+                println!("✅ Prepared '{}' with parameters: {}", name, types.join(", "));
+            }
+            prepared.insert(name, statement);
+        }
+        Err(e) => eprintln!("❌ Prepare failed: {}", e),
+    }
+}
+
+/// `EXECUTE name(value, ...)`: run a prepared statement with bound parameters
+fn execute_prepared_command(
+    filesystem: &mut FileSystem,
+    query_planner: &mut QueryPlanner,
+    prepared: &mut HashMap<String, PreparedStatement>,
+    input: &str,
+) {
+    println!("🔍 Executing: {}", input);
+    let start_time = Instant::now();
+
+    let rest = strip_keyword(input, "EXECUTE").unwrap_or_default();
+    let call = match rest.split_once('(') {
+        Some((name, arguments)) => arguments.trim_end().strip_suffix(')').map(|arguments| (name.trim(), arguments)),
+        None => Some((rest.trim(), "")),
+    };
+    let Some((name, arguments)) = call.filter(|(name, _)| !name.is_empty()) else {
+        eprintln!("❌ Usage: EXECUTE name(value, ...)");
+        return;
+    };
+    let Some(statement) = prepared.get_mut(&name.to_lowercase()) else {
+        eprintln!("❌ Prepared statement '{}' not found", name);
+        return;
+    };
+    // Writes since PREPARE may have changed the tables the statement uses
+    if let Err(e) = query_planner.refresh_prepared(statement, &filesystem.storage_engine) {
+        eprintln!("❌ Prepared statement '{}' is no longer valid: {}", name, e);
+        return;
+    }
+
+    let parameters = match Parser::parse_values(arguments) {
+        Ok(parameters) => parameters,
+        Err(err) => {
+            eprintln!("❌ Parse Error: {}", err);
+            return;
+        }
+    };
+    match statement.bind(&parameters, &filesystem.storage_engine) {
+        Ok(ast) => run_query(filesystem, query_planner, ast, statement.plan(), start_time),
+        Err(e) => eprintln!("❌ Query validation failed: {}", e),
     }
 }
 
 fn format_execution_error(error: &ExecutionError) -> String {
     match error {
         ExecutionError::TableNotFound => "Table not found".to_string(),
-        ExecutionError::InsertFailed => "Insert operation failed".to_string(),
-        ExecutionError::UpdateFailed => "Update operation failed".to_string(),
+        ExecutionError::InsertFailed(msg) => format!("Insert operation failed: {}", msg),
+        ExecutionError::UpdateFailed(msg) => format!("Update operation failed: {}", msg),
+        ExecutionError::DeleteFailed(msg) => format!("Delete operation failed: {}", msg),
+        ExecutionError::CreateTableFailed(msg) => format!("Create table failed: {}", msg),
+        ExecutionError::DropTableFailed(msg) => format!("Drop table failed: {}", msg),
+        ExecutionError::AlterTableFailed(msg) => format!("Alter table failed: {}", msg),
+        ExecutionError::CreateViewFailed(msg) => format!("Create view failed: {}", msg),
+        ExecutionError::DropViewFailed(msg) => format!("Drop view failed: {}", msg),
+        ExecutionError::ViewFailed(msg) => format!("View failed: {}", msg),
+        ExecutionError::RecursionLimit(query, iterations) => format!(
+            "Recursive query '{}' was still adding rows after {} iterations; UNION instead of UNION ALL stops at rows seen before",
+            query, iterations
+        ),
+        ExecutionError::AmbiguousColumn(column) => format!("Column reference '{}' is ambiguous", column),
+        ExecutionError::SubqueryFailed(msg) => format!("Subquery failed: {}", msg),
+        ExecutionError::SetOperationFailed(msg) => format!("Set operation failed: {}", msg),
+        ExecutionError::UnboundParameter(count) => {
+            format!("Statement has {} parameter(s); use PREPARE and EXECUTE to bind them", count)
+        }
         ExecutionError::InvalidQuery => "Invalid query structure".to_string(),
     }
 }
 
-fn display_query_plan(plan: &db::query::QueryPlan) {
+fn display_query_plan(plan: &hyper_vault::db::query::QueryPlan) {
     println!("   Table: {}", plan.table.0);
     println!("   Estimated Cost: {:.2}", plan.estimated_cost);
+    for (view, view_plan) in &plan.views {
+        println!("   View {} (estimated cost {:.2}):", view, view_plan.estimated_cost);
+        for (i, step) in view_plan.execution_steps.iter().enumerate() {
+            println!("     {}. {:?}", i + 1, step);
+        }
+    }
+    for cte in &plan.ctes {
+        println!("   WITH {} (estimated cost {:.2}):", cte.name, cte.plan.estimated_cost);
+        for (i, step) in cte.plan.execution_steps.iter().enumerate() {
+            println!("     {}. {:?}", i + 1, step);
+        }
+        if let Some(ref step_plan) = cte.recursive_step {
+            println!("   Recursive step of {} (estimated cost {:.2} per iteration):", cte.name, step_plan.estimated_cost);
+            for (i, step) in step_plan.execution_steps.iter().enumerate() {
+                println!("     {}. {:?}", i + 1, step);
+            }
+        }
+    }
+    for (branch, operation) in plan.set_operations.iter().enumerate() {
+        println!(
+            "   SELECT {} ({}{} on {}, estimated cost {:.2}):",
+            branch + 2,
+            operation.operator,
+            if operation.all { " ALL" } else { "" },
+            operation.plan.table,
+            operation.plan.estimated_cost
+        );
+        for (i, step) in operation.plan.execution_steps.iter().enumerate() {
+            println!("     {}. {:?}", i + 1, step);
+        }
+    }
     println!("   Execution Steps:");
     for (i, step) in plan.execution_steps.iter().enumerate() {
         println!("     {}. {:?}", i + 1, step);
     }
 }
 
-fn display_results(results: &[Row]) {
+fn display_results(columns: &[String], results: &[Row]) {
     if results.is_empty() {
         println!("   No rows returned.");
         return;
     }
 
-    // Get all unique column names from the results
-    let mut columns: Vec<String> = Vec::new();
-    for row in results {
-        for key in row.data.keys() {
-            if !columns.contains(key) {
-                columns.push(key.clone());
-            }
-        }
-    }
-    columns.sort();
-
     if columns.is_empty() {
         println!("   No data to display.");
         return;
@@ -261,22 +526,22 @@ fn display_results(results: &[Row]) {
 
     // Calculate column widths for formatting
     let mut col_widths: HashMap<String, usize> = HashMap::new();
-    for col in &columns {
+    for col in columns {
         col_widths.insert(col.clone(), col.len().max(12)); // Minimum width of 12
     }
 
     for row in results {
-        for col in &columns {
+        for col in columns {
             if let Some(value) = row.data.get(col) {
                 let current_width = col_widths.get(col).unwrap_or(&12);
-                col_widths.insert(col.clone(), (*current_width).max(value.len()));
+                col_widths.insert(col.clone(), (*current_width).max(value.to_string().len()));
             }
         }
     }
 
     // Print header
     print!("   ");
-    for col in &columns {
+    for col in columns {
         let width = col_widths.get(col).unwrap_or(&12);
         print!("| {:width$} ", col, width = width);
     }
@@ -284,7 +549,7 @@ fn display_results(results: &[Row]) {
 
     // Print separator
     print!("   ");
-    for col in &columns {
+    for col in columns {
         let width = col_widths.get(col).unwrap_or(&12);
         print!("|{}", "-".repeat(width + 2));
     }
@@ -293,10 +558,9 @@ fn display_results(results: &[Row]) {
     // Print rows
     for row in results {
         print!("   ");
-        for col in &columns {
+        for col in columns {
             let width = col_widths.get(col).unwrap_or(&12);
-            let null_str = "NULL".to_string();
-            let value = row.data.get(col).unwrap_or(&null_str);
+            let value = row.data.get(col).unwrap_or(&Value::Null).to_string();
             print!("| {:width$} ", value, width = width);
         }
         println!("|");
@@ -311,21 +575,83 @@ fn display_help() {
     println!();
     println!("🔧 Special Commands:");
     println!("   help, h              - Show this help message");
-    println!("   show tables          - List all tables in the database");
+    println!("   show tables          - List all tables and views in the database");
     println!("   show all, show data  - Display all data from all tables");
     println!("   show stats, stats    - Show database and query statistics");
     println!("   clear, cls           - Clear the screen");
     println!("   exit, quit, q        - Exit the database");
     println!();
     println!("📝 SQL Commands:");
+    println!("   CREATE TABLE orders (id INTEGER PRIMARY KEY, customer INTEGER, total REAL)");
+    println!("   CREATE TABLE IF NOT EXISTS tags (name TEXT, PRIMARY KEY (name))");
+    println!("   ALTER TABLE orders ADD COLUMN status TEXT");
+    println!("   ALTER TABLE orders RENAME COLUMN total TO amount");
+    println!("   ALTER TABLE orders RENAME TO purchases");
+    println!("   DROP TABLE IF EXISTS purchases");
+    println!("   CREATE VIEW adults AS SELECT id, name, age FROM users WHERE age >= 30");
+    println!("   CREATE OR REPLACE VIEW adults AS SELECT id, name FROM users WHERE age >= 18");
+    println!("   SELECT name FROM adults ORDER BY name");
+    println!("   DROP VIEW IF EXISTS adults");
+    println!("   WITH older AS (SELECT id, name FROM users WHERE age > 28) SELECT name FROM older");
+    println!("   WITH RECURSIVE chain (id, depth) AS (SELECT id, 0 FROM employees WHERE manager_id IS NULL");
+    println!("       UNION ALL SELECT e.id, c.depth + 1 FROM employees e JOIN chain c ON e.manager_id = c.id)");
+    println!("       SELECT id, depth FROM chain ORDER BY depth");
     println!("   SELECT * FROM users");
     println!("   SELECT id, name FROM users WHERE age > '25'");
     println!("   SELECT * FROM users WHERE name = 'Anthony Etienne'");
+    println!("   SELECT * FROM users WHERE age > 25 AND (name = 'Jane Doe' OR NOT id = 3)");
+    println!("   SELECT * FROM users WHERE email IS NULL");
+    println!("   SELECT id, name FROM users ORDER BY age DESC NULLS LAST, name");
+    println!("   SELECT * FROM users ORDER BY id LIMIT 10 OFFSET 20");
+    println!("   SELECT COUNT(*), AVG(age), MIN(age), MAX(age) FROM users");
+    println!("   SELECT age, COUNT(*) FROM users GROUP BY age HAVING COUNT(*) > 1 ORDER BY age");
+    println!("   SELECT DISTINCT age FROM users ORDER BY age");
+    println!("   SELECT COUNT(DISTINCT email) FROM users");
+    println!("   SELECT * FROM users OFFSET 5 ROWS FETCH FIRST 5 ROWS ONLY");
+    println!("   SELECT u.name, o.total FROM users u JOIN orders o ON o.customer = u.id");
+    println!("   SELECT u.name, COUNT(o.id) FROM users AS u LEFT JOIN orders AS o ON o.customer = u.id GROUP BY u.name");
+    println!("   SELECT * FROM users CROSS JOIN orders");
+    println!("   SELECT name FROM users WHERE id IN (SELECT customer FROM orders)");
+    println!("   SELECT name FROM users u WHERE NOT EXISTS (SELECT id FROM orders o WHERE o.customer = u.id)");
+    println!("   SELECT name, age FROM users WHERE age = (SELECT MAX(age) FROM users)");
+    println!("   SELECT id, email FROM staging EXCEPT SELECT id, email FROM production ORDER BY id");
+    println!("   SELECT id FROM staging UNION ALL SELECT id FROM production");
+    println!("   SELECT id FROM staging INTERSECT SELECT id FROM production");
+    println!("   SELECT name || ' <' || email || '>' AS contact, age + 1 AS next_age FROM users ORDER BY next_age");
+    println!("   SELECT UPPER(name), SUBSTR(email, 1, 5), COALESCE(email, 'n/a'), CAST(age AS TEXT) FROM users");
+    println!("   SELECT name, CASE WHEN age < 30 THEN 'under 30' WHEN age < 40 THEN '30s' ELSE '40+' END AS bracket FROM users");
+    println!("   SELECT CASE status WHEN 'A' THEN 'active' WHEN 'S' THEN 'suspended' ELSE 'unknown' END FROM accounts");
+    println!("   SELECT * FROM users WHERE age BETWEEN 25 AND 30");
+    println!("   SELECT * FROM users WHERE id IN (1, 3, 5) AND name NOT IN ('Jane Doe')");
+    println!("   SELECT * FROM users WHERE email LIKE '%@example.com'");
+    println!("   SELECT * FROM users WHERE name ILIKE 'j%' AND name NOT LIKE '%\\_%' ESCAPE '\\'");
+    println!("   SELECT * FROM users WHERE email REGEXP '^[a-z]+\\.[a-z]+@'");
     println!("   INSERT INTO users (id, name, email, age) VALUES ('5', 'John Doe', 'john@example.com', '32')");
+    println!("   INSERT INTO users (id, name, age) VALUES (6, 'Ann Lee', 41), (7, 'Tom Hart', 29)");
+    println!("   INSERT INTO archive (id, name) SELECT id, name FROM users WHERE age > 30");
+    println!("   INSERT INTO users (id, name) VALUES (1, 'Anthony') ON CONFLICT (id) DO NOTHING");
+    println!("   INSERT INTO users (id, age) VALUES (1, 26) ON CONFLICT (id) DO UPDATE SET age = excluded.age");
     println!("   UPDATE users SET age = '26' WHERE id = '1'");
     println!("   UPDATE users SET email = 'new.email@example.com' WHERE name = 'Jane Doe'");
+    println!("   UPDATE users SET age = age + 1, name = UPPER(name) WHERE age < 30");
+    println!("   UPDATE users SET email = CASE WHEN email IS NULL THEN 'unknown' ELSE LOWER(email) END");
+    println!("   UPDATE users SET age = age + 1 WHERE id = 1 RETURNING id, age");
     println!("   DELETE FROM users WHERE age > '35'");
     println!("   DELETE FROM users WHERE id = '4'");
+    println!("   DELETE FROM users WHERE age < 18 RETURNING *");
+    println!();
+    println!("🧩 Prepared Statements:");
+    println!("   PREPARE by_age AS SELECT name FROM users WHERE age > ? AND name LIKE ?");
+    println!("   EXECUTE by_age(25, 'J%')");
+    println!("   PREPARE add_user AS INSERT INTO users (id, name, age) VALUES ($1, $2, $3)");
+    println!("   EXECUTE add_user(8, 'Kim Park', 33)");
+    println!("   DEALLOCATE by_age");
+    println!();
+    println!("📜 Scripts:");
+    println!("   UPDATE users SET age = age + 1 WHERE id = 1; SELECT * FROM users WHERE id = 1;");
+    println!("   source fixtures.sql             - Run the statements in a .sql file");
+    println!("   hyper_vault migrate.sql         - Run a script from the command line and exit");
+    println!("   -- line comments and /* block comments */ are ignored");
     println!();
     println!("🎯 Advanced Features:");
     println!("   - Query optimization and planning");
@@ -334,8 +660,16 @@ fn display_help() {
     println!("   - Enhanced error messages");
     println!();
     println!("💡 Tips:");
-    println!("   - Use single quotes for string values: 'value'");
+    println!("   - Use single quotes for string values: 'value'; double a quote inside one: 'O''Brien'");
+    println!("   - Double-quote names with spaces or keywords: SELECT \"last name\", \"order\" FROM people");
+    println!("   - Literals: -42, 2.5, 1e6, TRUE, FALSE, NULL and hex blobs X'CAFE'");
     println!("   - Supported operators: =, >, <, >=, <=, !=, <>");
+    println!("   - Combine conditions with AND, OR, NOT and parentheses");
+    println!("   - Use NULL for missing values and test them with IS NULL / IS NOT NULL");
+    println!("   - Functions: UPPER, LOWER, LENGTH, SUBSTR, TRIM, REPLACE, COALESCE, NULLIF, ABS, ROUND, CAST");
+    println!("   - LIKE wildcards: % matches any text, _ one character; ILIKE ignores case");
+    println!("   - Aggregates: COUNT(*), COUNT(col), SUM, AVG, MIN, MAX with GROUP BY and HAVING");
+    println!("   - Qualify columns shared by joined tables with the table name or alias: u.id");
     println!("   - Use * to select all columns: SELECT * FROM table");
     println!("   - Commands are case-insensitive");
     println!("   - Complex queries show execution plans");
@@ -353,12 +687,11 @@ fn show_tables(filesystem: &FileSystem) {
     
     if filesystem.storage_engine.tables.is_empty() {
         println!("   No tables found in the database.");
-        return;
     }
 
     for (table_name, table) in &filesystem.storage_engine.tables {
         println!("   🗂️  Table: {}", table_name);
-        println!("      Columns: {}", table.columns.join(", "));
+        println!("      Columns: {}", format_columns(&table.columns));
         if let Some(pk) = &table.primary_key {
             println!("      Primary Key: {}", pk);
         }
@@ -375,6 +708,19 @@ fn show_tables(filesystem: &FileSystem) {
         }
         println!();
     }
+
+    if !filesystem.storage_engine.views.is_empty() {
+        println!("👁️  Views:");
+        for (view_name, view) in &filesystem.storage_engine.views {
+            println!("   🔎 View: {}", view_name);
+            println!("      Definition: {}", view.definition);
+            println!();
+        }
+    }
+}
+
+fn format_columns(columns: &[Column]) -> String {
+    columns.iter().map(|column| column.to_string()).collect::<Vec<_>>().join(", ")
 }
 
 fn show_all_data(filesystem: &FileSystem) {
@@ -390,7 +736,7 @@ fn show_all_data(filesystem: &FileSystem) {
     
     for (table_name, table) in &filesystem.storage_engine.tables {
         println!("📋 Table: {}", table_name);
-        println!("   Columns: {}", table.columns.join(", "));
+        println!("   Columns: {}", format_columns(&table.columns));
         if let Some(pk) = &table.primary_key {
             println!("   Primary Key: {}", pk);
         }
@@ -404,7 +750,7 @@ fn show_all_data(filesystem: &FileSystem) {
 
         // Convert table rows to Vec<Row> for display_results function
         let rows: Vec<Row> = table.rows.values().cloned().collect();
-        display_results(&rows);
+        display_results(&table.column_names(), &rows);
         total_rows += rows.len();
         
         println!();
@@ -439,6 +785,7 @@ fn show_database_statistics(filesystem: &FileSystem, query_planner: &QueryPlanne
     println!("   INSERT Queries: {}", query_stats.insert_queries);
     println!("   UPDATE Queries: {}", query_stats.update_queries);
     println!("   DELETE Queries: {}", query_stats.delete_queries);
+    println!("   DDL Queries: {}", query_stats.ddl_queries);
     println!("   Failed Queries: {}", query_stats.failed_queries);
     if query_stats.total_queries > 0 {
         println!("   Success Rate: {:.1}%", 
@@ -448,7 +795,7 @@ fn show_database_statistics(filesystem: &FileSystem, query_planner: &QueryPlanne
     println!();
     
     println!("📋 Table Details:");
-    for (table_name, table) in &filesystem.storage_engine.tables {
+    for table_name in filesystem.storage_engine.tables.keys() {
         if let Some(stats) = filesystem.storage_engine.get_table_stats(table_name) {
             println!("   {} ({} rows):", table_name, stats.row_count);
             for (column, col_stats) in &stats.column_stats {